@@ -0,0 +1,6 @@
+-- Column additions are skipped when the column already exists (see SqliteDb::migrate)
+alter table cards add column altitude text;
+alter table cards add column deleted_at datetime;
+
+create index if not exists idx_cards_kind_state on cards (kind, state);
+create index if not exists idx_cards_altitude on cards (altitude);
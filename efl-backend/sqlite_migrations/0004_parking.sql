@@ -0,0 +1,10 @@
+create table if not exists parked_cards (
+  card_id    text primary key,
+  original   json not null,
+  reason     text not null,
+  parked_at  datetime not null default current_timestamp
+);
+
+alter table wakes add column fired_at datetime;
+
+create index if not exists idx_wakes_card on wakes (card_id);
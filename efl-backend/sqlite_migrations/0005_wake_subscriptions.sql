@@ -0,0 +1,10 @@
+create table if not exists wake_subscriptions (
+  id          integer primary key autoincrement,
+  card_id     text not null,
+  event       text not null,
+  created_at  datetime not null default current_timestamp,
+  fired_at    datetime
+);
+
+create index if not exists idx_wake_subs_event on wake_subscriptions (event);
+create index if not exists idx_wake_subs_card on wake_subscriptions (card_id);
@@ -0,0 +1,15 @@
+create table if not exists altitude_state (
+  user_id     text primary key,
+  current     text not null,
+  previous    text,
+  updated_at  datetime not null default current_timestamp
+);
+
+create table if not exists altitude_gates (
+  id          integer primary key autoincrement,
+  user_id     text not null,
+  gate        json not null,
+  passed_at   datetime not null
+);
+
+create index if not exists idx_altitude_gates_user on altitude_gates (user_id, passed_at);
@@ -0,0 +1,13 @@
+-- The current Gmail draft for each card; replaced when the card is drafted again
+create table if not exists gmail_drafts (
+  card_id     text primary key,
+  draft_id    text not null,
+  kind        text not null,
+  state       text not null,
+  message_id  text not null,
+  thread_id   text not null,
+  to_addr     text not null,
+  subject     text not null,
+  body        text not null,
+  updated_at  datetime not null default current_timestamp
+);
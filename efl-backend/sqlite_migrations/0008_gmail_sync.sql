@@ -0,0 +1,19 @@
+-- Where the Gmail sync worker left off in the mailbox history
+create table if not exists gmail_sync_state (
+  account     text primary key,
+  history_id  text not null,
+  synced_at   datetime not null default current_timestamp
+);
+
+-- Parsed messages and their classification, so the feed never waits on Gmail or DSPy
+create table if not exists gmail_messages (
+  id           text primary key,
+  thread_id    text not null,
+  message      text not null,       -- GmailMessage JSON
+  class        text,                -- DSPy classification JSON; null when it was unavailable
+  labels       text not null default '[]',
+  unread       integer not null default 1,
+  received_at  datetime not null,
+  updated_at   datetime not null default current_timestamp
+);
+create index if not exists idx_gmail_messages_unread on gmail_messages(unread, received_at);
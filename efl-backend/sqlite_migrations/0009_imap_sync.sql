@@ -0,0 +1,12 @@
+-- Messages synced over IMAP share the cache with Gmail ones
+alter table gmail_messages add column source text not null default 'gmail';
+
+-- Where the IMAP sync left off; a new uid_validity means the UIDs were renumbered
+create table if not exists imap_sync_state (
+  account       text not null,
+  mailbox       text not null,
+  uid_validity  integer not null,
+  last_uid      integer not null,
+  synced_at     datetime not null default current_timestamp,
+  primary key (account, mailbox)
+);
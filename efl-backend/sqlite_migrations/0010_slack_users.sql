@@ -0,0 +1,6 @@
+-- Display names from users.info, so break-ins show a name without a lookup per message
+create table if not exists slack_users (
+  user_id       text primary key,
+  display_name  text not null,
+  fetched_at    datetime not null default current_timestamp
+);
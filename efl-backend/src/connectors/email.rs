@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+use crate::connectors::gmail::GmailClient;
+use crate::connectors::gmail_mime::{GmailAttachment, ListUnsubscribe};
+use crate::connectors::imap::{self, is_imap_id, ImapConfig, ImapSource};
+
+// Where mail comes from. Card building only talks to this trait; each source maps its own
+// shapes (Gmail labels, IMAP flags) into `EmailMessage`.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MailState {
+    #[default]
+    Unread,
+    // Read and still in the inbox
+    Read,
+    // Out of the inbox and read
+    Archived,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailMessage {
+    pub id: String,
+    pub thread_id: String,
+    pub snippet: String,
+    pub sender: String,
+    pub subject: String,
+    pub date: String,
+    // Full text with the quoted thread stripped; empty when only metadata was fetched
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub attachments: Vec<GmailAttachment>,
+    #[serde(default)]
+    pub list_unsubscribe: Option<ListUnsubscribe>,
+    // Threading headers for replies
+    #[serde(default)]
+    pub rfc822_message_id: String,
+    #[serde(default)]
+    pub references: String,
+    #[serde(default)]
+    pub reply_to: String,
+    #[serde(default)]
+    pub state: MailState,
+}
+
+impl EmailMessage {
+    // What classification and drafting should read: the body when we have it
+    pub fn text(&self) -> &str {
+        if self.body.is_empty() { &self.snippet } else { &self.body }
+    }
+
+    pub fn is_unread(&self) -> bool {
+        self.state == MailState::Unread
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MailChange {
+    MarkRead,
+    // Out of the inbox and read
+    Archive,
+}
+
+#[async_trait]
+pub trait EmailSource: Send + Sync {
+    // Ids of unread messages, newest first
+    async fn list_new(&mut self, limit: u32) -> Result<Vec<String>>;
+    async fn get_message(&self, id: &str) -> Result<EmailMessage>;
+    // Every message in the thread, read ones included
+    async fn thread(&self, thread_id: &str) -> Result<Vec<EmailMessage>>;
+    async fn mutate(&mut self, ids: &[String], change: MailChange) -> Result<()>;
+}
+
+// The configured sources for `ids`, each with the ids it owns. IMAP ids carry their
+// prefix; everything else is Gmail.
+pub async fn sources_for(ids: &[String], sqlite_pool: Option<SqlitePool>) -> Result<Vec<(Box<dyn EmailSource>, Vec<String>)>> {
+    let (imap_ids, gmail_ids): (Vec<String>, Vec<String>) = ids.iter().cloned().partition(|id| is_imap_id(id));
+    let mut sources: Vec<(Box<dyn EmailSource>, Vec<String>)> = vec![];
+    if !imap_ids.is_empty() {
+        let config = ImapConfig::from_env().ok_or_else(|| anyhow!("IMAP_HOST isn't set"))?;
+        sources.push((Box::new(ImapSource::new(config)), imap_ids));
+    }
+    if !gmail_ids.is_empty() {
+        let mut client = GmailClient::from_env_with_db(sqlite_pool).await;
+        client.ensure_access_token().await?;
+        sources.push((Box::new(client), gmail_ids));
+    }
+    Ok(sources)
+}
+
+#[async_trait]
+impl EmailSource for GmailClient {
+    async fn list_new(&mut self, limit: u32) -> Result<Vec<String>> {
+        self.list_unread_ids(limit).await
+    }
+
+    async fn get_message(&self, id: &str) -> Result<EmailMessage> {
+        GmailClient::get_message(self, id).await
+    }
+
+    async fn thread(&self, thread_id: &str) -> Result<Vec<EmailMessage>> {
+        self.get_thread(thread_id).await
+    }
+
+    async fn mutate(&mut self, ids: &[String], change: MailChange) -> Result<()> {
+        match change {
+            MailChange::MarkRead => self.mark_read(ids).await,
+            MailChange::Archive => self.archive(ids).await,
+        }
+    }
+}
+
+#[async_trait]
+impl EmailSource for ImapSource {
+    async fn list_new(&mut self, limit: u32) -> Result<Vec<String>> {
+        let (mut client, mailbox) = self.open().await?;
+        let unseen = client.uid_search("UNSEEN").await?;
+        client.logout().await;
+        Ok(unseen.iter().rev()
+            .take(limit as usize)
+            .map(|uid| imap::message_id(mailbox.uid_validity, *uid))
+            .collect())
+    }
+
+    async fn get_message(&self, id: &str) -> Result<EmailMessage> {
+        let (_, uid) = imap::parse_message_id(id).ok_or_else(|| anyhow!("{} isn't an IMAP message id", id))?;
+        self.fetch(&uid.to_string()).await?.into_iter()
+            .find(|m| m.id == id)
+            .ok_or_else(|| anyhow!("no message {}", id))
+    }
+
+    async fn thread(&self, thread_id: &str) -> Result<Vec<EmailMessage>> {
+        let root = imap::thread_root(thread_id).ok_or_else(|| anyhow!("{} isn't an IMAP thread id", thread_id))?;
+        self.search(&format!("OR HEADER Message-ID {root} HEADER References {root}", root = imap::quote(root))).await
+    }
+
+    async fn mutate(&mut self, ids: &[String], change: MailChange) -> Result<()> {
+        let (mut client, mailbox) = self.open().await?;
+        // Ids from another UIDVALIDITY no longer name anything in the mailbox
+        let uids: Vec<u32> = ids.iter()
+            .filter_map(|id| imap::parse_message_id(id))
+            .filter(|(validity, _)| *validity == mailbox.uid_validity)
+            .map(|(_, uid)| uid)
+            .collect();
+        client.mark_seen(&uids).await?;
+        if change == MailChange::Archive {
+            // Without an archive mailbox, archived mail stays put but read
+            if let Some(archive) = &self.config.archive_mailbox {
+                client.uid_move(&uids, archive).await?;
+            }
+        }
+        client.logout().await;
+        Ok(())
+    }
+}
+
+// Mail held in memory, so the email -> card flow runs without an account: in tests, and
+// offline with EMAIL_FIXTURE pointing at a JSON array of messages
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    messages: Vec<EmailMessage>,
+}
+
+impl MemorySource {
+    // In the order given, which stands for arrival order
+    pub fn new(messages: Vec<EmailMessage>) -> Self {
+        Self { messages }
+    }
+
+    // None unless EMAIL_FIXTURE is set. Messages without a state count as unread.
+    pub fn from_env() -> Result<Option<Self>> {
+        let Some(path) = std::env::var("EMAIL_FIXTURE").ok().filter(|p| !p.is_empty()) else { return Ok(None) };
+        let json = std::fs::read_to_string(&path).with_context(|| format!("reading {path}"))?;
+        let messages: Vec<EmailMessage> = serde_json::from_str(&json).with_context(|| format!("parsing {path}"))?;
+        Ok(Some(Self::new(messages)))
+    }
+}
+
+#[async_trait]
+impl EmailSource for MemorySource {
+    async fn list_new(&mut self, limit: u32) -> Result<Vec<String>> {
+        Ok(self.messages.iter().rev()
+            .filter(|m| m.is_unread())
+            .take(limit as usize)
+            .map(|m| m.id.clone())
+            .collect())
+    }
+
+    async fn get_message(&self, id: &str) -> Result<EmailMessage> {
+        self.messages.iter().find(|m| m.id == id).cloned().ok_or_else(|| anyhow!("no message {}", id))
+    }
+
+    async fn thread(&self, thread_id: &str) -> Result<Vec<EmailMessage>> {
+        Ok(self.messages.iter().filter(|m| m.thread_id == thread_id).cloned().collect())
+    }
+
+    async fn mutate(&mut self, ids: &[String], change: MailChange) -> Result<()> {
+        for message in self.messages.iter_mut().filter(|m| ids.contains(&m.id)) {
+            message.state = match change {
+                MailChange::MarkRead if message.state == MailState::Archived => MailState::Archived,
+                MailChange::MarkRead => MailState::Read,
+                MailChange::Archive => MailState::Archived,
+            };
+        }
+        Ok(())
+    }
+}
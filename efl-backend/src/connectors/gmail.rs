@@ -1,8 +1,31 @@
+use std::net::{IpAddr, SocketAddr};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
+use crate::connectors::email::{EmailMessage, MailState};
+use crate::connectors::gmail_mime::{header, parse_body, parse_list_unsubscribe};
 use crate::sqlite::oauth::OAuthToken;
 use sqlx::SqlitePool;
 
+pub const MODIFY_SCOPE: &str = "https://www.googleapis.com/auth/gmail.modify";
+pub const SETTINGS_SCOPE: &str = "https://www.googleapis.com/auth/gmail.settings.basic";
+// Requested when connecting: modify covers reading, labels, archiving and mark-read;
+// settings.basic covers the filters behind "block sender"
+pub const OAUTH_SCOPES: &[&str] = &[MODIFY_SCOPE, SETTINGS_SCOPE];
+
+const API: &str = "https://gmail.googleapis.com/gmail/v1/users/me";
+// batchModify takes at most this many ids per call
+const BATCH_MODIFY_LIMIT: usize = 1000;
+
+#[derive(thiserror::Error, Debug)]
+pub enum GmailError {
+    // The stored grant predates write access; the user has to reconnect Gmail
+    #[error("Gmail access is missing the {0} scope; reconnect Gmail to grant it")]
+    ScopeRequired(&'static str),
+    // History ids expire after about a week; the caller has to sync from scratch
+    #[error("Gmail history {0} is no longer available; a full sync is needed")]
+    HistoryExpired(String),
+}
+
 #[derive(Clone)]
 pub struct GmailClient {
     pub access_token: Option<String>,
@@ -10,6 +33,8 @@ pub struct GmailClient {
     pub client_secret: Option<String>,
     pub refresh_token: Option<String>,
     pub sqlite_pool: Option<SqlitePool>,
+    // Scopes recorded with the stored OAuth grant; None when the token came from the environment
+    pub granted_scopes: Option<Vec<String>>,
 }
 
 impl GmailClient {
@@ -20,6 +45,7 @@ impl GmailClient {
             client_secret: std::env::var("GMAIL_CLIENT_SECRET").ok(),
             refresh_token: std::env::var("GMAIL_REFRESH_TOKEN").ok(),
             sqlite_pool: None,
+            granted_scopes: None,
         }
     }
     
@@ -30,9 +56,14 @@ impl GmailClient {
         // Try to load refresh token from database if not in env
         if client.refresh_token.is_none() {
             if let Some(pool) = &pool {
-                if let Ok(Some(token)) = OAuthToken::get_refresh_token("gmail", pool).await {
-                    tracing::info!("Loaded Gmail refresh token from database");
-                    client.refresh_token = Some(token);
+                if let Ok(Some(token)) = OAuthToken::get_by_service("gmail", pool).await {
+                    if let Some(refresh_token) = token.refresh_token {
+                        tracing::info!("Loaded Gmail refresh token from database");
+                        client.refresh_token = Some(refresh_token);
+                        client.granted_scopes = token.scopes.map(|s| {
+                            s.split(|c: char| c.is_whitespace() || c == ',').filter(|s| !s.is_empty()).map(str::to_string).collect()
+                        });
+                    }
                 }
             }
         }
@@ -40,7 +71,7 @@ impl GmailClient {
         client
     }
 
-    async fn ensure_access_token(&mut self) -> Result<String> {
+    pub async fn ensure_access_token(&mut self) -> Result<String> {
         if let Some(tok) = &self.access_token { return Ok(tok.clone()); }
         let (cid, csec, rtok) = match (&self.client_id, &self.client_secret, &self.refresh_token) {
             (Some(a), Some(b), Some(c)) => (a.clone(), b.clone(), c.clone()),
@@ -62,11 +93,19 @@ impl GmailClient {
         Ok(data.access_token)
     }
 
-    pub async fn list_unread(&mut self, max_results: u32) -> Result<Vec<GmailMessage>> {
+    pub async fn list_unread(&mut self, max_results: u32) -> Result<Vec<EmailMessage>> {
+        let mut out = Vec::new();
+        for id in self.list_unread_ids(max_results).await? {
+            if let Ok(msg) = self.get_message(&id).await { out.push(msg); }
+        }
+        Ok(out)
+    }
+
+    pub async fn list_unread_ids(&mut self, max_results: u32) -> Result<Vec<String>> {
         let token = self.ensure_access_token().await?;
         #[derive(Deserialize)]
-        struct ListOut { messages: Option<Vec<GmailId>>, nextPageToken: Option<String> }
-        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages?q=is:unread&maxResults={}", max_results);
+        struct ListOut { messages: Option<Vec<GmailId>> }
+        let url = format!("{API}/messages?q=is:unread&maxResults={}", max_results);
         let resp = reqwest::Client::new()
             .get(url)
             .bearer_auth(token)
@@ -74,54 +113,246 @@ impl GmailClient {
             .map_err(|e| anyhow!("gmail list http: {e}"))?;
         if !resp.status().is_success() { return Err(anyhow!("gmail list failed: {}", resp.status())); }
         let list: ListOut = resp.json().await.map_err(|e| anyhow!("gmail list parse: {e}"))?;
-        let mut out = Vec::new();
-        if let Some(ids) = list.messages {
-            for mid in ids.into_iter().take(max_results as usize) {
-                if let Ok(msg) = self.get_message(&mid.id).await { out.push(msg); }
-            }
-        }
-        Ok(out)
+        Ok(list.messages.unwrap_or_default().into_iter().take(max_results as usize).map(|m| m.id).collect())
     }
 
-    pub async fn get_message(&self, id: &str) -> Result<GmailMessage> {
+    pub async fn get_message(&self, id: &str) -> Result<EmailMessage> {
         let token = self.access_token.clone().ok_or_else(|| anyhow!("no access token"))?;
-        #[derive(Deserialize)]
-        struct MsgOut { id: String, threadId: String, snippet: String, payload: Option<GmailPayload> }
-        let url = format!("https://gmail.googleapis.com/gmail/v1/users/me/messages/{}?format=metadata", id);
+        let url = format!("{API}/messages/{}?format=full", id);
         let resp = reqwest::Client::new()
             .get(url)
             .bearer_auth(token)
             .send().await
             .map_err(|e| anyhow!("gmail get http: {e}"))?;
         if !resp.status().is_success() { return Err(anyhow!("gmail get failed: {}", resp.status())); }
-        let msg: MsgOut = resp.json().await.map_err(|e| anyhow!("gmail get parse: {e}"))?;
-        
-        // Extract headers if available
-        let mut sender = String::new();
-        let mut subject = String::new();
-        let mut date = String::new();
-        
-        if let Some(payload) = msg.payload {
-            if let Some(headers) = payload.headers {
-                for header in headers {
-                    match header.name.as_str() {
-                        "From" => sender = header.value,
-                        "Subject" => subject = header.value,
-                        "Date" => date = header.value,
-                        _ => {}
-                    }
-                }
+        let msg: FullMessage = resp.json().await.map_err(|e| anyhow!("gmail get parse: {e}"))?;
+        Ok(msg.into_message())
+    }
+
+    // Every message in the thread, read ones included, oldest first
+    pub async fn get_thread(&self, thread_id: &str) -> Result<Vec<EmailMessage>> {
+        let token = self.access_token.clone().ok_or_else(|| anyhow!("no access token"))?;
+        #[derive(Deserialize)]
+        struct ThreadOut { #[serde(default)] messages: Vec<FullMessage> }
+        let url = format!("{API}/threads/{}?format=full", thread_id);
+        let resp = reqwest::Client::new()
+            .get(url)
+            .bearer_auth(token)
+            .send().await
+            .map_err(|e| anyhow!("gmail thread http: {e}"))?;
+        if !resp.status().is_success() { return Err(anyhow!("gmail thread failed: {}", resp.status())); }
+        let thread: ThreadOut = resp.json().await.map_err(|e| anyhow!("gmail thread parse: {e}"))?;
+        Ok(thread.messages.into_iter().map(FullMessage::into_message).collect())
+    }
+
+    // The mailbox's current history id; an incremental sync continues from here
+    pub async fn history_id(&mut self) -> Result<String> {
+        let token = self.ensure_access_token().await?;
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ProfileOut { history_id: String }
+        let resp = reqwest::Client::new()
+            .get(format!("{API}/profile"))
+            .bearer_auth(token)
+            .send().await
+            .map_err(|e| anyhow!("gmail profile http: {e}"))?;
+        if !resp.status().is_success() { return Err(anyhow!("gmail profile failed: {}", resp.status())); }
+        let profile: ProfileOut = resp.json().await.map_err(|e| anyhow!("gmail profile parse: {e}"))?;
+        Ok(profile.history_id)
+    }
+
+    // Everything that happened to messages since `start_history_id`, folded to one change per message
+    pub async fn history(&mut self, start_history_id: &str) -> Result<GmailHistory> {
+        let token = self.ensure_access_token().await?;
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct HistoryOut {
+            #[serde(default)]
+            history: Vec<HistoryRecord>,
+            next_page_token: Option<String>,
+            history_id: String,
+        }
+        let mut records = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut query = vec![
+                ("startHistoryId", start_history_id.to_string()),
+                ("historyTypes", "messageAdded".to_string()),
+                ("historyTypes", "messageDeleted".to_string()),
+                ("historyTypes", "labelAdded".to_string()),
+                ("historyTypes", "labelRemoved".to_string()),
+            ];
+            query.extend(page_token.take().map(|t| ("pageToken", t)));
+            let resp = reqwest::Client::new()
+                .get(format!("{API}/history"))
+                .bearer_auth(&token)
+                .query(&query)
+                .send().await
+                .map_err(|e| anyhow!("gmail history http: {e}"))?;
+            if resp.status() == reqwest::StatusCode::NOT_FOUND {
+                return Err(GmailError::HistoryExpired(start_history_id.to_string()).into());
+            }
+            if !resp.status().is_success() { return Err(anyhow!("gmail history failed: {}", resp.status())); }
+            let page: HistoryOut = resp.json().await.map_err(|e| anyhow!("gmail history parse: {e}"))?;
+            records.extend(page.history);
+            match page.next_page_token {
+                Some(next) => page_token = Some(next),
+                None => return Ok(GmailHistory { history_id: page.history_id, changes: fold_history(&records) }),
             }
         }
-        
-        Ok(GmailMessage { 
-            id: msg.id, 
-            thread_id: msg.threadId, 
-            snippet: msg.snippet,
-            sender,
-            subject,
-            date,
-        })
+    }
+
+    fn require_scope(&self, scope: &'static str) -> Result<()> {
+        match &self.granted_scopes {
+            Some(granted) if !granted.iter().any(|g| g == scope) => Err(GmailError::ScopeRequired(scope).into()),
+            _ => Ok(()),
+        }
+    }
+
+    // Google answers 403 when the token lacks a scope the stored grant didn't tell us about
+    fn check_write(status: reqwest::StatusCode, what: &str, scope: &'static str) -> Result<()> {
+        match status {
+            s if s.is_success() => Ok(()),
+            reqwest::StatusCode::FORBIDDEN => Err(GmailError::ScopeRequired(scope).into()),
+            s => Err(anyhow!("gmail {what} failed: {s}")),
+        }
+    }
+
+    pub async fn modify_labels(&mut self, ids: &[String], add: &[&str], remove: &[&str]) -> Result<()> {
+        self.require_scope(MODIFY_SCOPE)?;
+        let token = self.ensure_access_token().await?;
+        for chunk in ids.chunks(BATCH_MODIFY_LIMIT) {
+            let resp = reqwest::Client::new()
+                .post(format!("{API}/messages/batchModify"))
+                .bearer_auth(&token)
+                .json(&serde_json::json!({ "ids": chunk, "addLabelIds": add, "removeLabelIds": remove }))
+                .send().await
+                .map_err(|e| anyhow!("gmail modify http: {e}"))?;
+            Self::check_write(resp.status(), "modify", MODIFY_SCOPE)?;
+        }
+        Ok(())
+    }
+
+    // Out of the inbox and read, so it also drops out of the unread feed
+    pub async fn archive(&mut self, ids: &[String]) -> Result<()> {
+        self.modify_labels(ids, &[], &["INBOX", "UNREAD"]).await
+    }
+
+    pub async fn mark_read(&mut self, ids: &[String]) -> Result<()> {
+        self.modify_labels(ids, &[], &["UNREAD"]).await
+    }
+
+    // Future mail from `from` skips the inbox and arrives read; returns the filter id
+    pub async fn create_block_filter(&mut self, from: &str) -> Result<String> {
+        self.require_scope(SETTINGS_SCOPE)?;
+        let token = self.ensure_access_token().await?;
+        #[derive(Deserialize)]
+        struct FilterOut { id: String }
+        let resp = reqwest::Client::new()
+            .post(format!("{API}/settings/filters"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "criteria": { "from": from },
+                "action": { "removeLabelIds": ["INBOX", "UNREAD"] }
+            }))
+            .send().await
+            .map_err(|e| anyhow!("gmail filter http: {e}"))?;
+        Self::check_write(resp.status(), "filter", SETTINGS_SCOPE)?;
+        let filter: FilterOut = resp.json().await.map_err(|e| anyhow!("gmail filter parse: {e}"))?;
+        Ok(filter.id)
+    }
+
+    // Drafts go through the same scope as labels: gmail.modify covers compose and send
+    pub async fn create_draft(&mut self, thread_id: &str, raw: &str) -> Result<String> {
+        self.require_scope(MODIFY_SCOPE)?;
+        let token = self.ensure_access_token().await?;
+        let resp = reqwest::Client::new()
+            .post(format!("{API}/drafts"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "message": { "raw": raw, "threadId": thread_id } }))
+            .send().await
+            .map_err(|e| anyhow!("gmail draft http: {e}"))?;
+        Self::check_write(resp.status(), "draft", MODIFY_SCOPE)?;
+        let draft: GmailId = resp.json().await.map_err(|e| anyhow!("gmail draft parse: {e}"))?;
+        Ok(draft.id)
+    }
+
+    // Returns the id of the sent message
+    pub async fn send_draft(&mut self, draft_id: &str) -> Result<String> {
+        self.require_scope(MODIFY_SCOPE)?;
+        let token = self.ensure_access_token().await?;
+        let resp = reqwest::Client::new()
+            .post(format!("{API}/drafts/send"))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "id": draft_id }))
+            .send().await
+            .map_err(|e| anyhow!("gmail send http: {e}"))?;
+        Self::check_write(resp.status(), "send", MODIFY_SCOPE)?;
+        let sent: GmailId = resp.json().await.map_err(|e| anyhow!("gmail send parse: {e}"))?;
+        Ok(sent.id)
+    }
+
+    pub async fn delete_draft(&mut self, draft_id: &str) -> Result<()> {
+        self.require_scope(MODIFY_SCOPE)?;
+        let token = self.ensure_access_token().await?;
+        let resp = reqwest::Client::new()
+            .delete(format!("{API}/drafts/{draft_id}"))
+            .bearer_auth(token)
+            .send().await
+            .map_err(|e| anyhow!("gmail draft delete http: {e}"))?;
+        // Already gone in Gmail (sent or deleted there) is as good as deleted
+        if resp.status() == reqwest::StatusCode::NOT_FOUND { return Ok(()); }
+        Self::check_write(resp.status(), "draft delete", MODIFY_SCOPE)
+    }
+}
+
+// RFC 8058 one-click unsubscribe: a bare POST to the List-Unsubscribe URL, no Gmail auth involved.
+// The URL is whatever the sender put in the header, so it must be https to a public address;
+// the request goes to the addresses that were checked and doesn't follow redirects.
+pub async fn one_click_unsubscribe(url: &str) -> Result<()> {
+    let parsed = reqwest::Url::parse(url).map_err(|e| anyhow!("unsubscribe url: {e}"))?;
+    if parsed.scheme() != "https" {
+        return Err(anyhow!("unsubscribe url must be https: {url}"));
+    }
+    let port = parsed.port_or_known_default().unwrap_or(443);
+    let mut client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+    let host = parsed.host_str().ok_or_else(|| anyhow!("unsubscribe url has no host: {url}"))?;
+    let addrs: Vec<SocketAddr> = match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await
+                .map_err(|e| anyhow!("unsubscribe host {host}: {e}"))?
+                .collect();
+            client = client.resolve_to_addrs(host, &addrs);
+            addrs
+        }
+    };
+    if addrs.is_empty() || !addrs.iter().all(|a| is_public_ip(a.ip())) {
+        return Err(anyhow!("unsubscribe url doesn't point at a public host: {url}"));
+    }
+    let resp = client.build()?
+        .post(parsed.clone())
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .send().await
+        .map_err(|e| anyhow!("unsubscribe http: {e}"))?;
+    if !resp.status().is_success() { return Err(anyhow!("unsubscribe failed: {}", resp.status())); }
+    Ok(())
+}
+
+// Routable on the internet: not loopback, private, link-local, CGNAT, multicast and the like
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let [a, b, ..] = v4.octets();
+            !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified()
+                || v4.is_broadcast() || v4.is_multicast() || v4.is_documentation()
+                || a == 0 || (a == 100 && (64..128).contains(&b)) || a >= 240)
+        }
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => is_public_ip(IpAddr::V4(v4)),
+            None => !(v6.is_loopback() || v6.is_unspecified() || v6.is_multicast()
+                || v6.is_unique_local() || v6.is_unicast_link_local()),
+        },
     }
 }
 
@@ -129,19 +360,175 @@ impl GmailClient {
 pub struct GmailId { pub id: String }
 
 #[derive(Debug, Clone, Deserialize)]
-pub struct GmailPayload { pub headers: Option<Vec<GmailHeader>> }
+#[serde(rename_all = "camelCase")]
+pub struct HistoryMessage {
+    pub id: String,
+    // The message's labels after the change
+    pub label_ids: Option<Vec<String>>,
+}
 
 #[derive(Debug, Clone, Deserialize)]
-pub struct GmailHeader { pub name: String, pub value: String }
+pub struct HistoryMessageRef { pub message: HistoryMessage }
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HistoryRecord {
+    pub messages_added: Vec<HistoryMessageRef>,
+    pub messages_deleted: Vec<HistoryMessageRef>,
+    pub labels_added: Vec<HistoryMessageRef>,
+    pub labels_removed: Vec<HistoryMessageRef>,
+}
+
+// The net effect of a history range on one message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageChange {
+    pub id: String,
+    // New since the start of the range, so it has to be fetched
+    pub added: bool,
+    pub deleted: bool,
+    // Latest known labels; None when no record in the range carried them
+    pub labels: Option<Vec<String>>,
+}
+
+pub struct GmailHistory {
+    // Where the next sync picks up
+    pub history_id: String,
+    pub changes: Vec<MessageChange>,
+}
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// Records come oldest first, so later label sets replace earlier ones
+pub fn fold_history(records: &[HistoryRecord]) -> Vec<MessageChange> {
+    let mut changes: Vec<MessageChange> = Vec::new();
+    for record in records {
+        let events = record.messages_added.iter().map(|m| (m, true, false))
+            .chain(record.labels_added.iter().chain(&record.labels_removed).map(|m| (m, false, false)))
+            .chain(record.messages_deleted.iter().map(|m| (m, false, true)));
+        for (entry, added, deleted) in events {
+            let message = &entry.message;
+            let index = match changes.iter().position(|c| c.id == message.id) {
+                Some(index) => index,
+                None => {
+                    changes.push(MessageChange { id: message.id.clone(), added: false, deleted: false, labels: None });
+                    changes.len() - 1
+                }
+            };
+            let change = &mut changes[index];
+            change.added |= added;
+            change.deleted |= deleted;
+            if message.label_ids.is_some() {
+                change.labels = message.label_ids.clone();
+            }
+        }
+    }
+    changes
+}
+
+// One node of a `format=full` MIME tree
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailPayload {
+    #[serde(default)]
+    pub mime_type: String,
+    pub filename: Option<String>,
+    pub headers: Option<Vec<GmailHeader>>,
+    pub body: Option<GmailBody>,
+    pub parts: Option<Vec<GmailPayload>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailBody {
+    #[serde(default)]
+    pub size: u64,
+    // base64url; absent for attachments, which are fetched separately
+    pub data: Option<String>,
+    pub attachment_id: Option<String>,
+}
+
+// messages.get / threads.get with format=full
+#[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GmailMessage { 
-    pub id: String, 
-    pub thread_id: String, 
-    pub snippet: String,
-    pub sender: String,
-    pub subject: String,
-    pub date: String,
+struct FullMessage { id: String, thread_id: String, snippet: String, label_ids: Option<Vec<String>>, payload: Option<GmailPayload> }
+
+impl FullMessage {
+    fn into_message(self) -> EmailMessage {
+        message_from_payload(self.id, self.thread_id, self.snippet, self.payload, &self.label_ids.unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GmailHeader { pub name: String, pub value: String }
+
+// A Gmail payload as a source-neutral message; `labels` decide its state
+pub fn message_from_payload(id: String, thread_id: String, snippet: String, payload: Option<GmailPayload>, labels: &[String]) -> EmailMessage {
+    let headers = payload.as_ref().and_then(|p| p.headers.clone()).unwrap_or_default();
+    let value = |name: &str| header(&headers, name).unwrap_or_default().to_string();
+    let parsed = payload.as_ref().map(parse_body).unwrap_or_default();
+    EmailMessage {
+        id,
+        thread_id,
+        snippet,
+        sender: value("From"),
+        subject: value("Subject"),
+        date: value("Date"),
+        body: parsed.text,
+        attachments: parsed.attachments,
+        list_unsubscribe: header(&headers, "List-Unsubscribe")
+            .and_then(|v| parse_list_unsubscribe(v, header(&headers, "List-Unsubscribe-Post"))),
+        rfc822_message_id: value("Message-ID"),
+        references: value("References"),
+        reply_to: value("Reply-To"),
+        state: mail_state(labels),
+    }
+}
+
+// Unread mail outside the inbox still counts as unread, as it does for `is:unread`
+pub fn mail_state(labels: &[String]) -> MailState {
+    if is_unread(labels) {
+        MailState::Unread
+    } else if labels.iter().any(|l| l == "INBOX") {
+        MailState::Read
+    } else {
+        MailState::Archived
+    }
 }
 
+// What `is:unread` matches: unread and not in spam or trash
+pub fn is_unread(labels: &[String]) -> bool {
+    labels.iter().any(|l| l == "UNREAD") && !labels.iter().any(|l| l == "SPAM" || l == "TRASH")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fold_history_keeps_latest_labels() {
+        let records: Vec<HistoryRecord> = serde_json::from_value(serde_json::json!([
+            { "messagesAdded": [{ "message": { "id": "m1", "labelIds": ["INBOX", "UNREAD"] } }] },
+            { "labelsRemoved": [{ "message": { "id": "m1", "labelIds": ["INBOX"] } }] },
+            { "labelsAdded": [{ "message": { "id": "m2", "labelIds": ["UNREAD"] } }] },
+            { "messagesDeleted": [{ "message": { "id": "m2" } }] },
+        ])).unwrap();
+        let changes = fold_history(&records);
+        assert_eq!(changes, vec![
+            MessageChange { id: "m1".into(), added: true, deleted: false, labels: Some(vec!["INBOX".into()]) },
+            MessageChange { id: "m2".into(), added: false, deleted: true, labels: Some(vec!["UNREAD".into()]) },
+        ]);
+        assert!(!is_unread(&changes[0].labels.clone().unwrap()));
+        assert!(is_unread(&["UNREAD".to_string(), "INBOX".to_string()]));
+        assert!(!is_unread(&["UNREAD".to_string(), "SPAM".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_only_posts_to_public_https() {
+        for url in ["http://news.example.com/u", "https://127.0.0.1/u", "https://[::1]/u", "https://10.0.0.8/u",
+                    "https://169.254.169.254/latest", "https://localhost/u", "file:///etc/passwd"] {
+            assert!(one_click_unsubscribe(url).await.is_err(), "{url}");
+        }
+        assert!(is_public_ip("93.184.216.34".parse().unwrap()));
+        assert!(!is_public_ip("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_ip("::ffff:192.168.1.1".parse().unwrap()));
+        assert!(!is_public_ip("fd00::1".parse().unwrap()));
+    }
+}
@@ -0,0 +1,298 @@
+use base64::alphabet;
+use base64::engine::{DecodePaddingMode, Engine, GeneralPurpose, GeneralPurposeConfig};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use crate::connectors::gmail::{GmailHeader, GmailPayload};
+
+// Turns a Gmail `format=full` payload tree into what the cards need: readable body
+// text without the quoted thread below it, attachment metadata, and the
+// List-Unsubscribe header.
+
+// Gmail encodes part bodies as base64url, sometimes with padding and sometimes without
+const BASE64URL: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailAttachment {
+    pub filename: String,
+    pub mime_type: String,
+    pub size: u64,
+    // Fetch the bytes with messages/{id}/attachments/{attachmentId}
+    pub attachment_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListUnsubscribe {
+    pub http: Option<String>,
+    pub mailto: Option<String>,
+    // RFC 8058: a POST to `http` unsubscribes without a confirmation page
+    pub one_click: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedBody {
+    pub text: String,
+    pub attachments: Vec<GmailAttachment>,
+}
+
+pub fn header<'a>(headers: &'a [GmailHeader], name: &str) -> Option<&'a str> {
+    headers.iter().find(|h| h.name.eq_ignore_ascii_case(name)).map(|h| h.value.as_str())
+}
+
+pub fn parse_body(payload: &GmailPayload) -> ParsedBody {
+    let mut texts = vec![];
+    let mut parsed = ParsedBody::default();
+    walk(payload, &mut texts, &mut parsed.attachments);
+    parsed.text = strip_quoted_reply(&texts.join("\n\n"));
+    parsed
+}
+
+fn walk(part: &GmailPayload, texts: &mut Vec<String>, attachments: &mut Vec<GmailAttachment>) {
+    let body = part.body.as_ref();
+    let filename = part.filename.as_deref().unwrap_or("");
+    if !filename.is_empty() || body.is_some_and(|b| b.attachment_id.is_some()) {
+        attachments.push(GmailAttachment {
+            filename: filename.to_string(),
+            mime_type: part.mime_type.clone(),
+            size: body.map_or(0, |b| b.size),
+            attachment_id: body.and_then(|b| b.attachment_id.clone()),
+        });
+        return;
+    }
+    let parts = part.parts.as_deref().unwrap_or_default();
+    let mime = part.mime_type.to_ascii_lowercase();
+    match mime.as_str() {
+        // Same content several ways; plain text reads best, otherwise take the richest (last) one
+        "multipart/alternative" => {
+            let pick = parts.iter().find(|p| p.mime_type.eq_ignore_ascii_case("text/plain")).or(parts.last());
+            if let Some(p) = pick {
+                walk(p, texts, attachments);
+            }
+        }
+        m if m.starts_with("multipart/") => {
+            for p in parts {
+                walk(p, texts, attachments);
+            }
+        }
+        "text/plain" | "text/html" => {
+            let Some(raw) = body.and_then(|b| b.data.as_deref()).and_then(|d| BASE64URL.decode(d).ok()) else {
+                return;
+            };
+            let raw = String::from_utf8_lossy(&raw);
+            let text = if mime == "text/html" { html_to_text(&raw) } else { raw.replace("\r\n", "\n") };
+            if !text.trim().is_empty() {
+                texts.push(text);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+pub fn html_to_text(html: &str) -> String {
+    static INVISIBLE: OnceLock<Regex> = OnceLock::new();
+    static WHITESPACE: OnceLock<Regex> = OnceLock::new();
+    static BREAK: OnceLock<Regex> = OnceLock::new();
+    static BLOCK_END: OnceLock<Regex> = OnceLock::new();
+    static LIST_ITEM: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    static NUMERIC_ENTITY: OnceLock<Regex> = OnceLock::new();
+    static BLANK_LINES: OnceLock<Regex> = OnceLock::new();
+
+    let text = regex(&INVISIBLE, r"(?is)<script\b.*?</script>|<style\b.*?</style>|<head\b.*?</head>|<!--.*?-->").replace_all(html, "");
+    // Source line breaks are just formatting; the tags decide where lines break
+    let text = regex(&WHITESPACE, r"\s+").replace_all(&text, " ");
+    let text = regex(&BREAK, r"(?i)<br\s*/?>").replace_all(&text, "\n");
+    let text = regex(&BLOCK_END, r"(?i)</(p|div|tr|li|h[1-6]|table|blockquote|ul|ol)\s*>").replace_all(&text, "\n");
+    let text = regex(&LIST_ITEM, r"(?i)<li\b[^>]*>").replace_all(&text, "- ");
+    let text = regex(&TAG, r"<[^>]*>").replace_all(&text, "");
+    let text = text
+        .replace("&nbsp;", " ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&apos;", "'");
+    let text = regex(&NUMERIC_ENTITY, r"&#(x?)([0-9a-fA-F]+);").replace_all(&text, |c: &regex::Captures| {
+        let radix = if c[1].is_empty() { 10 } else { 16 };
+        u32::from_str_radix(&c[2], radix).ok().and_then(char::from_u32).map(String::from).unwrap_or_default()
+    });
+    // Last, so "&amp;lt;" stays "&lt;"
+    let text = text.replace("&amp;", "&");
+    let lines: Vec<&str> = text.lines().map(str::trim).collect();
+    regex(&BLANK_LINES, r"\n{3,}").replace_all(&lines.join("\n"), "\n\n").trim().to_string()
+}
+
+// Drops the quoted thread under a reply: "On <date>, <someone> wrote:", Outlook's
+// "-----Original Message-----" / "From: ... Sent: ..." headers, and "> " lines
+pub fn strip_quoted_reply(text: &str) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut kept = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        let next = lines.get(i + 1).map_or("", |l| l.trim());
+        let attribution = trimmed.starts_with("On ") && (trimmed.ends_with("wrote:") || next.ends_with("wrote:"));
+        let outlook = trimmed.starts_with("-----Original Message")
+            || trimmed.starts_with("________________")
+            || (trimmed.starts_with("From:") && (next.starts_with("Sent:") || next.starts_with("Date:")));
+        if attribution || outlook {
+            break;
+        }
+        if !trimmed.starts_with('>') {
+            kept.push(*line);
+        }
+    }
+    kept.join("\n").trim().to_string()
+}
+
+// List-Unsubscribe: <mailto:leave@example.com?subject=unsubscribe>, <https://example.com/u/123>
+pub fn parse_list_unsubscribe(value: &str, post: Option<&str>) -> Option<ListUnsubscribe> {
+    static TARGET: OnceLock<Regex> = OnceLock::new();
+    let mut unsubscribe = ListUnsubscribe::default();
+    for target in regex(&TARGET, r"<([^>]+)>").captures_iter(value) {
+        let target = target[1].trim();
+        let lowered = target.to_ascii_lowercase();
+        if lowered.starts_with("mailto:") {
+            unsubscribe.mailto.get_or_insert_with(|| target.to_string());
+        } else if lowered.starts_with("https://") || lowered.starts_with("http://") {
+            unsubscribe.http.get_or_insert_with(|| target.to_string());
+        }
+    }
+    if unsubscribe.http.is_none() && unsubscribe.mailto.is_none() {
+        return None;
+    }
+    unsubscribe.one_click = unsubscribe.http.is_some()
+        && post.is_some_and(|p| p.replace(' ', "").eq_ignore_ascii_case("List-Unsubscribe=One-Click"));
+    Some(unsubscribe)
+}
+
+// "Re: " once, however many times the thread has gone back and forth
+pub fn reply_subject(subject: &str) -> String {
+    let trimmed = subject.trim();
+    if trimmed.get(..3).is_some_and(|p| p.eq_ignore_ascii_case("re:")) {
+        trimmed.to_string()
+    } else {
+        format!("Re: {}", trimmed)
+    }
+}
+
+// One header line's worth of `value`. Control characters become spaces: a decoded
+// encoded-word can carry CRLF, which would otherwise start a header of the sender's choosing.
+pub fn header_value(value: &str) -> String {
+    value.chars().map(|c| if c.is_control() { ' ' } else { c }).collect::<String>().trim().to_string()
+}
+
+// RFC 2047 encoded-word for non-ASCII header values
+fn encode_header(value: &str) -> String {
+    let value = header_value(value);
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", base64::engine::general_purpose::STANDARD.encode(&value))
+    }
+}
+
+// A plain-text reply as Gmail's `raw` (base64url RFC 2822)
+pub fn reply_raw(to: &str, subject: &str, in_reply_to: &str, references: &str, body: &str) -> String {
+    base64::engine::general_purpose::URL_SAFE.encode(reply_message(to, subject, in_reply_to, references, body))
+}
+
+// A plain-text RFC 2822 reply without From/Date, which the sending side adds. In-Reply-To
+// and References point at the message being answered so every client threads it.
+pub fn reply_message(to: &str, subject: &str, in_reply_to: &str, references: &str, body: &str) -> String {
+    let (in_reply_to, references) = (header_value(in_reply_to), header_value(references));
+    let mut headers = vec![
+        format!("To: {}", header_value(to)),
+        format!("Subject: {}", encode_header(subject)),
+    ];
+    if !in_reply_to.is_empty() {
+        headers.push(format!("In-Reply-To: {}", in_reply_to));
+        let chain = format!("{} {}", references, in_reply_to);
+        headers.push(format!("References: {}", chain.trim()));
+    }
+    headers.push("MIME-Version: 1.0".to_string());
+    headers.push("Content-Type: text/plain; charset=UTF-8".to_string());
+    headers.push("Content-Transfer-Encoding: 8bit".to_string());
+    format!("{}\r\n\r\n{}", headers.join("\r\n"), body.replace("\r\n", "\n").replace('\n', "\r\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(text: &str) -> String {
+        base64::engine::general_purpose::URL_SAFE.encode(text)
+    }
+
+    #[test]
+    fn test_parse_multipart_message() {
+        let payload: GmailPayload = serde_json::from_value(serde_json::json!({
+            "mimeType": "multipart/mixed",
+            "filename": "",
+            "headers": [],
+            "parts": [
+                {
+                    "mimeType": "multipart/alternative",
+                    "filename": "",
+                    "parts": [
+                        { "mimeType": "text/plain", "filename": "", "body": { "size": 10, "data": encode("Can you review the draft by Friday?\r\n\r\nOn Mon, Oct 12, 2026 at 9:00 AM Ana <ana@example.com>\r\nwrote:\r\n> earlier message\r\n") } },
+                        { "mimeType": "text/html", "filename": "", "body": { "size": 10, "data": encode("<p>ignored</p>") } }
+                    ]
+                },
+                { "mimeType": "application/pdf", "filename": "draft.pdf", "body": { "size": 48213, "attachmentId": "ANGjdJ8" } }
+            ]
+        })).unwrap();
+
+        let parsed = parse_body(&payload);
+        assert_eq!(parsed.text, "Can you review the draft by Friday?");
+        assert_eq!(parsed.attachments.len(), 1);
+        assert_eq!(parsed.attachments[0].filename, "draft.pdf");
+        assert_eq!(parsed.attachments[0].attachment_id.as_deref(), Some("ANGjdJ8"));
+    }
+
+    #[test]
+    fn test_html_and_headers() {
+        let html = "<html><head><style>p{}</style></head><body><p>Hi&nbsp;team,</p><ul><li>Ship &amp; tell</li><li>Caf&#233;</li></ul>Thanks<br>Bo</body></html>";
+        assert_eq!(html_to_text(html), "Hi team,\n- Ship & tell\n- Café\n\nThanks\nBo");
+
+        let unsub = parse_list_unsubscribe(
+            "<mailto:leave@news.example.com?subject=unsubscribe>, <https://news.example.com/u/42>",
+            Some("List-Unsubscribe=One-Click"),
+        ).unwrap();
+        assert_eq!(unsub.http.as_deref(), Some("https://news.example.com/u/42"));
+        assert!(unsub.mailto.is_some());
+        assert!(unsub.one_click);
+        assert!(parse_list_unsubscribe("nothing useful", None).is_none());
+    }
+
+    #[test]
+    fn test_reply_threads_with_headers() {
+        let raw = reply_raw("Ana <ana@example.com>", &reply_subject("Launch plan"), "<b@mail>", "<a@mail>", "Works for me.\nThanks");
+        let message = String::from_utf8(BASE64URL.decode(raw).unwrap()).unwrap();
+        assert!(message.contains("Subject: Re: Launch plan\r\n"));
+        assert!(message.contains("In-Reply-To: <b@mail>\r\n"));
+        assert!(message.contains("References: <a@mail> <b@mail>\r\n"));
+        assert!(message.ends_with("\r\n\r\nWorks for me.\r\nThanks"));
+        assert_eq!(reply_subject("RE: Launch plan"), "RE: Launch plan");
+    }
+
+    #[test]
+    fn test_reply_headers_drop_line_breaks() {
+        // What mail-parser makes of =?UTF-8?B?aGkNCkJjYzogZXZpbEBleGFtcGxlLmNvbQ==?=
+        let subject = reply_subject("hi\r\nBcc: evil@example.com");
+        let message = reply_message("Ana <ana@example.com>\nCc: x@example.com", &subject, "<b@mail>\r\nBcc: y@example.com", "<a@mail>\r", "ok");
+        let (headers, _) = message.split_once("\r\n\r\n").unwrap();
+        assert!(headers.lines().all(|line| !line.starts_with("Bcc:") && !line.starts_with("Cc:")));
+        assert!(headers.contains("Subject: Re: hi  Bcc: evil@example.com\r\n"));
+        assert!(headers.contains("References: <a@mail> <b@mail>  Bcc: y@example.com\r\n"));
+        assert_eq!(headers.matches("\r\n").count(), 6);
+    }
+}
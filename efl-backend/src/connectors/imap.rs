@@ -0,0 +1,500 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use anyhow::{anyhow, bail, Result};
+use mail_parser::{Address, HeaderValue, MessageParser, MimeHeaders};
+use regex::Regex;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls;
+use crate::connectors::email::{EmailMessage, MailState};
+use crate::connectors::gmail_mime::{html_to_text, parse_list_unsubscribe, strip_quoted_reply, GmailAttachment};
+
+// Plain IMAP (Fastmail, Dovecot, ...) as an alternative to the Gmail API. Only the handful
+// of commands the sync and the card actions need: LOGIN, SELECT, UID SEARCH/FETCH/STORE/MOVE
+// and IDLE. Messages come out as `EmailMessage`, so classification and cards don't care where
+// mail came from.
+
+// Ids of IMAP messages in the shared message cache: imap:<uidvalidity>:<uid>
+const ID_PREFIX: &str = "imap:";
+const SNIPPET_CHARS: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct ImapConfig {
+    pub host: String,
+    pub port: u16,
+    // Implicit TLS (993); off for a local GreenMail/Dovecot on a plain port
+    pub tls: bool,
+    pub username: String,
+    pub password: String,
+    pub mailbox: String,
+    // Where archived mail is moved, e.g. "Archive"
+    pub archive_mailbox: Option<String>,
+}
+
+impl ImapConfig {
+    // None unless IMAP_HOST is set
+    pub fn from_env() -> Option<Self> {
+        let host = std::env::var("IMAP_HOST").ok().filter(|h| !h.is_empty())?;
+        let tls = std::env::var("IMAP_TLS").map_or(true, |v| v != "false");
+        Some(Self {
+            host,
+            port: std::env::var("IMAP_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(if tls { 993 } else { 143 }),
+            tls,
+            username: std::env::var("IMAP_USERNAME").unwrap_or_default(),
+            password: std::env::var("IMAP_PASSWORD").unwrap_or_default(),
+            mailbox: std::env::var("IMAP_MAILBOX").unwrap_or_else(|_| "INBOX".to_string()),
+            archive_mailbox: std::env::var("IMAP_ARCHIVE_MAILBOX").ok().filter(|m| !m.is_empty()),
+        })
+    }
+
+    // Sync state is kept per account and mailbox
+    pub fn account(&self) -> String {
+        format!("{}@{}", self.username, self.host)
+    }
+}
+
+pub fn is_imap_id(id: &str) -> bool {
+    id.starts_with(ID_PREFIX)
+}
+
+pub fn message_id(uid_validity: u32, uid: u32) -> String {
+    format!("{ID_PREFIX}{uid_validity}:{uid}")
+}
+
+// (uidvalidity, uid) back out of a cache id
+pub fn parse_message_id(id: &str) -> Option<(u32, u32)> {
+    let (validity, uid) = id.strip_prefix(ID_PREFIX)?.split_once(':')?;
+    Some((validity.parse().ok()?, uid.parse().ok()?))
+}
+
+// The Message-ID a thread is keyed by
+pub fn thread_root(thread_id: &str) -> Option<&str> {
+    thread_id.strip_prefix(ID_PREFIX)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MailboxState {
+    pub uid_validity: u32,
+    pub uid_next: u32,
+    pub exists: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct FetchedMessage {
+    pub uid: u32,
+    pub flags: Vec<String>,
+    pub raw: Vec<u8>,
+}
+
+// One response line with the literals ({n} blocks) it carried
+#[derive(Debug, Default)]
+struct Response {
+    text: String,
+    literals: Vec<Vec<u8>>,
+}
+
+trait Io: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Io for T {}
+
+pub struct ImapClient {
+    stream: BufReader<Box<dyn Io>>,
+    tag: u32,
+}
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).expect("valid regex"))
+}
+
+// "... BODY[] {1234}" -> 1234
+fn literal_len(line: &str) -> Option<usize> {
+    let open = line.strip_suffix('}')?.rfind('{')?;
+    line[open + 1..line.len() - 1].trim_end_matches('+').parse().ok()
+}
+
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+// "1,2,3" for UID commands
+fn uid_set(uids: &[u32]) -> String {
+    uids.iter().map(u32::to_string).collect::<Vec<_>>().join(",")
+}
+
+fn parse_select(responses: &[Response]) -> MailboxState {
+    static VALIDITY: OnceLock<Regex> = OnceLock::new();
+    static NEXT: OnceLock<Regex> = OnceLock::new();
+    static EXISTS: OnceLock<Regex> = OnceLock::new();
+    let mut state = MailboxState::default();
+    for r in responses {
+        let number = |re: &Regex| re.captures(&r.text).and_then(|c| c[1].parse().ok());
+        if let Some(v) = number(regex(&VALIDITY, r"UIDVALIDITY (\d+)")) { state.uid_validity = v; }
+        if let Some(v) = number(regex(&NEXT, r"UIDNEXT (\d+)")) { state.uid_next = v; }
+        if let Some(v) = number(regex(&EXISTS, r"^\* (\d+) EXISTS")) { state.exists = v; }
+    }
+    state
+}
+
+fn parse_search(responses: &[Response]) -> Vec<u32> {
+    responses.iter()
+        .filter_map(|r| r.text.strip_prefix("* SEARCH"))
+        .flat_map(|rest| rest.split_whitespace().filter_map(|n| n.parse().ok()))
+        .collect()
+}
+
+fn parse_fetch(response: &Response) -> Option<FetchedMessage> {
+    static FETCH: OnceLock<Regex> = OnceLock::new();
+    static UID: OnceLock<Regex> = OnceLock::new();
+    static FLAGS: OnceLock<Regex> = OnceLock::new();
+    if !regex(&FETCH, r"^\* \d+ FETCH").is_match(&response.text) {
+        return None;
+    }
+    let uid = regex(&UID, r"UID (\d+)").captures(&response.text)?[1].parse().ok()?;
+    let flags = regex(&FLAGS, r"FLAGS \(([^)]*)\)").captures(&response.text)
+        .map(|c| c[1].split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default();
+    Some(FetchedMessage { uid, flags, raw: response.literals.first().cloned().unwrap_or_default() })
+}
+
+async fn tls_connect(host: &str, tcp: TcpStream) -> Result<Box<dyn Io>> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    let config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let name = rustls::pki_types::ServerName::try_from(host.to_string())?;
+    let stream = tokio_rustls::TlsConnector::from(Arc::new(config)).connect(name, tcp).await?;
+    Ok(Box::new(stream))
+}
+
+impl ImapClient {
+    // Connects and logs in
+    pub async fn connect(config: &ImapConfig) -> Result<Self> {
+        let tcp = TcpStream::connect((config.host.as_str(), config.port)).await
+            .map_err(|e| anyhow!("imap connect {}:{}: {e}", config.host, config.port))?;
+        let stream: Box<dyn Io> = if config.tls { tls_connect(&config.host, tcp).await? } else { Box::new(tcp) };
+        let mut client = Self { stream: BufReader::new(stream), tag: 0 };
+        let greeting = client.read_response().await?;
+        if !greeting.text.starts_with("* OK") {
+            bail!("imap greeting: {}", greeting.text);
+        }
+        client.command(&format!("LOGIN {} {}", quote(&config.username), quote(&config.password))).await?;
+        Ok(client)
+    }
+
+    async fn read_response(&mut self) -> Result<Response> {
+        let mut response = Response::default();
+        loop {
+            let mut line = Vec::new();
+            if self.stream.read_until(b'\n', &mut line).await? == 0 {
+                bail!("imap connection closed");
+            }
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+            response.text.push_str(line);
+            let Some(len) = literal_len(line) else { return Ok(response) };
+            let mut literal = vec![0; len];
+            self.stream.read_exact(&mut literal).await?;
+            response.literals.push(literal);
+        }
+    }
+
+    async fn send(&mut self, command: &str) -> Result<String> {
+        self.tag += 1;
+        let tag = format!("A{}", self.tag);
+        let stream = self.stream.get_mut();
+        stream.write_all(format!("{tag} {command}\r\n").as_bytes()).await?;
+        stream.flush().await?;
+        Ok(tag)
+    }
+
+    // Untagged responses up to the tagged completion; NO/BAD become errors
+    async fn command(&mut self, command: &str) -> Result<Vec<Response>> {
+        let tag = self.send(command).await?;
+        let mut responses = Vec::new();
+        loop {
+            let response = self.read_response().await?;
+            if let Some(status) = response.text.strip_prefix(&format!("{tag} ")) {
+                if status.starts_with("OK") {
+                    return Ok(responses);
+                }
+                let verb = command.split_whitespace().take(2).collect::<Vec<_>>().join(" ");
+                bail!("imap {}: {}", verb, status);
+            }
+            responses.push(response);
+        }
+    }
+
+    pub async fn select(&mut self, mailbox: &str) -> Result<MailboxState> {
+        Ok(parse_select(&self.command(&format!("SELECT {}", quote(mailbox))).await?))
+    }
+
+    // e.g. "UNSEEN"
+    pub async fn uid_search(&mut self, criteria: &str) -> Result<Vec<u32>> {
+        Ok(parse_search(&self.command(&format!("UID SEARCH {criteria}")).await?))
+    }
+
+    // Whole messages without setting \Seen; `set` is a UID set such as "42:*"
+    pub async fn uid_fetch(&mut self, set: &str) -> Result<Vec<FetchedMessage>> {
+        let responses = self.command(&format!("UID FETCH {set} (UID FLAGS BODY.PEEK[])")).await?;
+        Ok(responses.iter().filter_map(parse_fetch).collect())
+    }
+
+    pub async fn mark_seen(&mut self, uids: &[u32]) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        self.command(&format!("UID STORE {} +FLAGS.SILENT (\\Seen)", uid_set(uids))).await?;
+        Ok(())
+    }
+
+    // RFC 6851
+    pub async fn uid_move(&mut self, uids: &[u32], mailbox: &str) -> Result<()> {
+        if uids.is_empty() {
+            return Ok(());
+        }
+        self.command(&format!("UID MOVE {} {}", uid_set(uids), quote(mailbox))).await?;
+        Ok(())
+    }
+
+    // Waits for the server to report a change, up to `wait`. Returns whether anything changed.
+    pub async fn idle(&mut self, wait: Duration) -> Result<bool> {
+        let tag = self.send("IDLE").await?;
+        let ready = self.read_response().await?;
+        if !ready.text.starts_with('+') {
+            bail!("imap IDLE refused: {}", ready.text);
+        }
+        let changed = match tokio::time::timeout(wait, self.read_response()).await {
+            Ok(update) => update?.text.starts_with("* "),
+            Err(_) => false,
+        };
+        let stream = self.stream.get_mut();
+        stream.write_all(b"DONE\r\n").await?;
+        stream.flush().await?;
+        loop {
+            let response = self.read_response().await?;
+            if let Some(status) = response.text.strip_prefix(&format!("{tag} ")) {
+                if !status.starts_with("OK") {
+                    bail!("imap IDLE: {}", status);
+                }
+                return Ok(changed);
+            }
+        }
+    }
+
+    pub async fn logout(mut self) {
+        let _ = self.command("LOGOUT").await;
+    }
+}
+
+// An IMAP mailbox as an `EmailSource`. Each call opens its own session, so it never
+// competes with the sync worker's IDLE connection.
+pub struct ImapSource {
+    pub config: ImapConfig,
+}
+
+impl ImapSource {
+    pub fn new(config: ImapConfig) -> Self {
+        Self { config }
+    }
+
+    // Logged in, with the mailbox selected
+    pub async fn open(&self) -> Result<(ImapClient, MailboxState)> {
+        let mut client = ImapClient::connect(&self.config).await?;
+        let mailbox = client.select(&self.config.mailbox).await?;
+        Ok((client, mailbox))
+    }
+
+    // Messages matching a UID SEARCH, oldest first
+    pub async fn search(&self, criteria: &str) -> Result<Vec<EmailMessage>> {
+        let (mut client, mailbox) = self.open().await?;
+        let uids = client.uid_search(criteria).await?;
+        let messages = match uids.is_empty() {
+            true => vec![],
+            false => Self::normalize_all(&mailbox, client.uid_fetch(&uid_set(&uids)).await?),
+        };
+        client.logout().await;
+        Ok(messages)
+    }
+
+    pub async fn fetch(&self, set: &str) -> Result<Vec<EmailMessage>> {
+        let (mut client, mailbox) = self.open().await?;
+        let fetched = client.uid_fetch(set).await?;
+        client.logout().await;
+        Ok(Self::normalize_all(&mailbox, fetched))
+    }
+
+    fn normalize_all(mailbox: &MailboxState, mut fetched: Vec<FetchedMessage>) -> Vec<EmailMessage> {
+        fetched.sort_by_key(|m| m.uid);
+        fetched.iter()
+            .filter_map(|m| normalize(message_id(mailbox.uid_validity, m.uid), &m.raw, &m.flags))
+            .collect()
+    }
+}
+
+fn display(address: Option<&Address>) -> String {
+    let Some(addr) = address.and_then(|a| a.first()) else { return String::new() };
+    match (addr.name(), addr.address()) {
+        (Some(name), Some(email)) => format!("{name} <{email}>"),
+        (None, Some(email)) => email.to_string(),
+        (Some(name), None) => name.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+fn ids(value: &HeaderValue) -> Vec<String> {
+    match (value.as_text_list(), value.as_text()) {
+        (Some(list), _) => list.iter().map(|id| id.to_string()).collect(),
+        (None, Some(id)) => vec![id.to_string()],
+        _ => vec![],
+    }
+}
+
+// Normalizes a raw RFC 822 message. Replies are threaded by the first message of their
+// References chain, which is what Gmail's thread ids amount to.
+pub fn normalize(id: String, raw: &[u8], flags: &[String]) -> Option<EmailMessage> {
+    let parsed = MessageParser::default().parse(raw)?;
+    let text = match (parsed.body_text(0), parsed.body_html(0)) {
+        (Some(text), _) => text.replace("\r\n", "\n"),
+        (None, Some(html)) => html_to_text(&html),
+        _ => String::new(),
+    };
+    let body = strip_quoted_reply(&text);
+    let snippet: String = body.split_whitespace().collect::<Vec<_>>().join(" ").chars().take(SNIPPET_CHARS).collect();
+
+    let message_id = parsed.message_id().map(str::to_string);
+    let references = ids(parsed.references());
+    let root = references.first().cloned()
+        .or_else(|| ids(parsed.in_reply_to()).into_iter().next())
+        .or_else(|| message_id.clone())
+        .unwrap_or_else(|| id.clone());
+
+    // Only the synced mailbox is read, so nothing here is archived
+    let state = match flags.iter().any(|f| f.eq_ignore_ascii_case("\\Seen")) {
+        true => MailState::Read,
+        false => MailState::Unread,
+    };
+    let raw_header = |name: &'static str| parsed.header_raw(name).map(str::trim);
+
+    Some(EmailMessage {
+        thread_id: format!("{ID_PREFIX}{root}"),
+        snippet,
+        sender: display(parsed.from()),
+        subject: parsed.subject().unwrap_or_default().to_string(),
+        date: parsed.date().map(|d| d.to_rfc822()).unwrap_or_default(),
+        body,
+        attachments: parsed.attachments()
+            .map(|part| GmailAttachment {
+                filename: part.attachment_name().unwrap_or_default().to_string(),
+                mime_type: part.content_type()
+                    .map(|ct| match ct.subtype() {
+                        Some(sub) => format!("{}/{}", ct.ctype(), sub),
+                        None => ct.ctype().to_string(),
+                    })
+                    .unwrap_or_default(),
+                size: part.len() as u64,
+                attachment_id: None,
+            })
+            .collect(),
+        list_unsubscribe: raw_header("List-Unsubscribe")
+            .and_then(|v| parse_list_unsubscribe(v, raw_header("List-Unsubscribe-Post"))),
+        rfc822_message_id: message_id.map(|m| format!("<{m}>")).unwrap_or_default(),
+        references: references.iter().map(|r| format!("<{r}>")).collect::<Vec<_>>().join(" "),
+        reply_to: display(parsed.reply_to()),
+        state,
+        id,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = "From: Ana Ruiz <ana@example.com>\r\n\
+        To: me@example.com\r\n\
+        Subject: Re: Q3 plan\r\n\
+        Date: Mon, 12 Oct 2026 09:00:00 +0000\r\n\
+        Message-ID: <m2@example.com>\r\n\
+        In-Reply-To: <m1@example.com>\r\n\
+        References: <m0@example.com> <m1@example.com>\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        \r\n\
+        Can you sign off by Friday?\r\n\
+        \r\n\
+        On Sun, Oct 11, 2026 at 8:00 AM Bo <bo@example.com> wrote:\r\n\
+        > earlier\r\n";
+
+    #[test]
+    fn test_parse_fetch_and_normalize() {
+        let response = Response {
+            text: format!("* 3 FETCH (UID 17 FLAGS (\\Flagged) BODY[] {{{}}})", RAW.len()),
+            literals: vec![RAW.as_bytes().to_vec()],
+        };
+        assert_eq!(literal_len("* 3 FETCH (UID 17 BODY[] {342}"), Some(342));
+        let fetched = parse_fetch(&response).unwrap();
+        assert_eq!((fetched.uid, fetched.flags.clone()), (17, vec!["\\Flagged".to_string()]));
+
+        let message = normalize(message_id(7, fetched.uid), &fetched.raw, &fetched.flags).unwrap();
+        assert_eq!(message.id, "imap:7:17");
+        assert_eq!(parse_message_id(&message.id), Some((7, 17)));
+        assert_eq!(message.thread_id, "imap:m0@example.com");
+        assert_eq!(message.sender, "Ana Ruiz <ana@example.com>");
+        assert_eq!(message.body, "Can you sign off by Friday?");
+        assert_eq!(message.rfc822_message_id, "<m2@example.com>");
+        assert_eq!(message.references, "<m0@example.com> <m1@example.com>");
+        assert_eq!(message.state, MailState::Unread);
+
+        let select = parse_select(&[
+            Response { text: "* 4 EXISTS".into(), ..Default::default() },
+            Response { text: "* OK [UIDVALIDITY 7] UIDs valid".into(), ..Default::default() },
+            Response { text: "* OK [UIDNEXT 18] Predicted next UID".into(), ..Default::default() },
+        ]);
+        assert_eq!(select, MailboxState { uid_validity: 7, uid_next: 18, exists: 4 });
+        assert_eq!(parse_search(&[Response { text: "* SEARCH 3 17".into(), ..Default::default() }]), vec![3, 17]);
+    }
+
+    // Against a local GreenMail (IMAP 3143, SMTP 3025):
+    //   docker run -p 3025:3025 -p 3143:3143 greenmail/standalone
+    //   IMAP_TEST_HOST=localhost cargo test imap -- --ignored
+    #[tokio::test]
+    #[ignore]
+    async fn test_greenmail_round_trip() {
+        use crate::connectors::email::{EmailSource, MailChange};
+        use crate::connectors::smtp::{SmtpConfig, SmtpSender, SmtpTls};
+        let host = std::env::var("IMAP_TEST_HOST").unwrap_or_else(|_| "localhost".to_string());
+        let smtp = SmtpSender::new(&SmtpConfig {
+            host: host.clone(),
+            port: 3025,
+            tls: SmtpTls::None,
+            username: String::new(),
+            password: String::new(),
+            from: "efl@localhost".to_string(),
+        }).unwrap();
+        smtp.send_reply("me@localhost", "Re: ping", "<root@localhost>", "", "Can you take a look?").await.unwrap();
+
+        let config = ImapConfig {
+            host,
+            port: 3143,
+            tls: false,
+            username: "me@localhost".to_string(),
+            password: "me@localhost".to_string(),
+            mailbox: "INBOX".to_string(),
+            archive_mailbox: None,
+        };
+        let mut client = ImapClient::connect(&config).await.unwrap();
+        let state = client.select(&config.mailbox).await.unwrap();
+        let unseen = client.uid_search("UNSEEN").await.unwrap();
+        let fetched = client.uid_fetch(&uid_set(&unseen)).await.unwrap();
+        let last = fetched.last().unwrap();
+        let message = normalize(message_id(state.uid_validity, last.uid), &last.raw, &last.flags).unwrap();
+        assert_eq!(message.thread_id, "imap:root@localhost");
+        client.logout().await;
+
+        let mut source = ImapSource::new(config);
+        assert!(source.list_new(10).await.unwrap().contains(&message.id));
+        assert!(source.thread(&message.thread_id).await.unwrap().iter().any(|m| m.id == message.id));
+        source.mutate(std::slice::from_ref(&message.id), MailChange::MarkRead).await.unwrap();
+        assert_eq!(source.get_message(&message.id).await.unwrap().state, MailState::Read);
+        assert!(!source.list_new(10).await.unwrap().contains(&message.id));
+    }
+}
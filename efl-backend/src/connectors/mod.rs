@@ -1,3 +1,7 @@
 // MCP connectors for external integrations
 pub mod slack;
 pub mod gmail;
+pub mod gmail_mime;
+pub mod imap;
+pub mod smtp;
+pub mod email;
@@ -1,53 +1,158 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
-use reqwest::Client;
+use serde_json::Value;
+use reqwest::{Client, StatusCode};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use tokio::sync::{OnceCell, RwLock};
+use uuid::Uuid;
+use sqlx::SqlitePool;
+use crate::sqlite::repo::slack_users::SlackUserRepo;
 use hmac::{Hmac, Mac};
 use sha2::Sha256;
 use hex;
 
+const API: &str = "https://slack.com/api";
+// Attempts at a rate-limited call before giving up
+const MAX_RETRIES: u32 = 3;
+// For a 429 that comes without Retry-After
+const DEFAULT_RETRY_SECS: u64 = 1;
+// Calls run inside HTTP requests, so a 429 is only waited out when it's short. Past
+// this much waiting in total the caller gets RateLimited and retries on its own.
+const MAX_RETRY_WAIT_SECS: u64 = 5;
+const PAGE_LIMIT: &str = "200";
+// search.messages pages read per mentions request
+const MAX_SEARCH_PAGES: u32 = 5;
+// How far back reads go when there's no cursor yet
+const DEFAULT_LOOKBACK_HOURS: i64 = 24;
+// Stored display names are looked up again after this long
+const USER_NAME_MAX_AGE_HOURS: i64 = 24;
+
+#[derive(thiserror::Error, Debug)]
+pub enum SlackError {
+    #[error("Slack {method} failed: {error}")]
+    Api { method: String, error: String },
+    // Still throttled after retrying
+    #[error("Slack rate-limited {0}; retry after {1}s")]
+    RateLimited(String, u64),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SlackMessage {
+    // "<channel>:<ts>"
     pub id: String,
     pub channel: String,
     pub user: String,
     pub text: String,
     pub timestamp: String,
+    // Display names, filled in by name resolution
+    #[serde(default)]
+    pub user_name: String,
+    #[serde(default)]
+    pub channel_name: String,
+    // Set on thread replies
+    #[serde(default)]
+    pub thread_ts: Option<String>,
 }
 
-#[async_trait]
-pub trait SlackConnector: Send + Sync {
-    async fn read_dms(&self) -> Result<Vec<SlackMessage>>;
-    async fn read_channel(&self, channel_id: &str) -> Result<Vec<SlackMessage>>;
-    async fn draft_message(&self, channel_id: &str, text: &str) -> Result<String>;
+impl SlackMessage {
+    // Joins, topic changes and other system messages come back as None. Events API message
+    // events have the same shape.
+    pub fn from_api(channel: &str, message: &Value) -> Option<Self> {
+        let subtype = message.get("subtype").and_then(Value::as_str);
+        if subtype.is_some_and(|s| s != "thread_broadcast") {
+            return None;
+        }
+        let field = |name: &str| message.get(name).and_then(Value::as_str).map(str::to_string);
+        let ts = field("ts")?;
+        Some(Self {
+            id: format!("{channel}:{ts}"),
+            channel: channel.to_string(),
+            user: field("user").unwrap_or_default(),
+            text: field("text").unwrap_or_default(),
+            thread_ts: field("thread_ts").filter(|t| *t != ts),
+            timestamp: ts,
+            user_name: String::new(),
+            channel_name: String::new(),
+        })
+    }
 }
 
-pub struct MCPSlackConnector {
-    // MCP client configuration
+// Messages oldest first, and the cursor to pass as `since` next time
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlackPage {
+    pub messages: Vec<SlackMessage>,
+    pub cursor: Option<String>,
 }
 
-impl MCPSlackConnector {
-    pub fn new() -> Self {
-        Self {}
+impl SlackPage {
+    fn new(mut messages: Vec<SlackMessage>, since: Option<&str>) -> Self {
+        messages.sort_by_key(|m| ts_key(&m.timestamp));
+        let cursor = messages.last().map(|m| m.timestamp.clone()).or_else(|| since.map(str::to_string));
+        Self { messages, cursor }
     }
 }
 
+// A reply waiting for approval. The id is derived from the content, so an approval can't
+// be carried over to text that changed after the user saw it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlackDraft {
+    pub draft_id: String,
+    pub channel: String,
+    pub thread_ts: Option<String>,
+    pub text: String,
+}
+
+fn draft_id(channel: &str, thread_ts: Option<&str>, text: &str) -> String {
+    let key = format!("slack-draft:{}:{}:{}", channel, thread_ts.unwrap_or(""), text);
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, key.as_bytes()).to_string()
+}
+
+// Slack message timestamps are "<epoch seconds>.<sequence>"
+pub fn ts_to_datetime(ts: &str) -> Option<chrono::DateTime<chrono::Utc>> {
+    let secs = ts.split('.').next()?.parse::<i64>().ok()?;
+    chrono::DateTime::from_timestamp(secs, 0)
+}
+
+// "1712345678.000200" -> (1712345678, 200), which orders correctly
+fn ts_key(ts: &str) -> (i64, i64) {
+    let (secs, seq) = ts.split_once('.').unwrap_or((ts, "0"));
+    (secs.parse().unwrap_or(0), seq.parse().unwrap_or(0))
+}
+
+fn retry_after(headers: &HeaderMap) -> u64 {
+    headers.get(RETRY_AFTER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_RETRY_SECS)
+}
+
+// Whether to wait `wait` seconds and try again, having already waited `waited`
+fn retry_inline(attempt: u32, waited: u64, wait: u64) -> bool {
+    attempt <= MAX_RETRIES && waited.saturating_add(wait) <= MAX_RETRY_WAIT_SECS
+}
+
+fn next_cursor(body: &Value) -> String {
+    body.pointer("/response_metadata/next_cursor").and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
 #[async_trait]
-impl SlackConnector for MCPSlackConnector {
-    async fn read_dms(&self) -> Result<Vec<SlackMessage>> {
-        // Implement MCP protocol for reading DMs
-        Ok(vec![])
-    }
-    
-    async fn read_channel(&self, _channel_id: &str) -> Result<Vec<SlackMessage>> {
-        // Implement MCP protocol for reading channel
-        Ok(vec![])
-    }
-    
-    async fn draft_message(&self, _channel_id: &str, text: &str) -> Result<String> {
-        // Return draft without sending
-        Ok(format!("Draft: {}", text))
-    }
+pub trait SlackConnector: Send + Sync {
+    // New messages in every DM since `since` (a message ts)
+    async fn read_dms(&self, since: Option<&str>) -> Result<SlackPage>;
+    // Messages that mention the token's user; needs a user token
+    async fn read_mentions(&self, since: Option<&str>) -> Result<SlackPage>;
+    async fn read_channel(&self, channel_id: &str, since: Option<&str>) -> Result<SlackPage>;
+    // A reply for review; nothing is posted
+    async fn draft_message(&self, channel_id: &str, thread_ts: Option<&str>, text: &str) -> Result<SlackDraft>;
+    // Posts the draft once the user approved exactly this one; returns the new message's ts
+    async fn post_approved(&self, draft: &SlackDraft, approved_draft_id: &str) -> Result<String>;
 }
 
 // Simple Web API client for Slack
@@ -58,6 +163,12 @@ impl SlackConnector for MCPSlackConnector {
 pub struct SlackClient {
     pub client: Client,
     pub token: String,
+    // Display names by user or channel id, looked up once per process
+    names: Arc<RwLock<HashMap<String, String>>>,
+    // The token's own user id, for mentions
+    me: Arc<OnceCell<String>>,
+    // Display names kept across restarts
+    users: Option<SlackUserRepo>,
 }
 
 impl SlackClient {
@@ -66,33 +177,253 @@ impl SlackClient {
         let token = std::env::var("SLACK_USER_TOKEN")
             .or_else(|_| std::env::var("SLACK_BOT_TOKEN"))
             .ok()?;
-        Some(Self {
+        Some(Self::new(token))
+    }
+
+    pub fn new(token: String) -> Self {
+        Self {
             client: Client::new(),
             token,
-        })
+            names: Arc::new(RwLock::new(HashMap::new())),
+            me: Arc::new(OnceCell::new()),
+            users: None,
+        }
+    }
+
+    pub fn with_user_cache(mut self, pool: Option<SqlitePool>) -> Self {
+        self.users = pool.map(SlackUserRepo::new);
+        self
+    }
+
+    // One Web API call. Short 429s wait out Retry-After; `ok: false` becomes SlackError::Api.
+    async fn call(&self, method: &str, params: &[(&str, &str)]) -> Result<Value> {
+        let (mut attempt, mut waited) = (0, 0);
+        loop {
+            let resp = self.client
+                .post(format!("{API}/{method}"))
+                .bearer_auth(&self.token)
+                .form(params)
+                .send()
+                .await
+                .map_err(|e| anyhow!("slack {method} http: {e}"))?;
+            if resp.status() == StatusCode::TOO_MANY_REQUESTS {
+                let wait = retry_after(resp.headers());
+                attempt += 1;
+                if !retry_inline(attempt, waited, wait) {
+                    return Err(SlackError::RateLimited(method.to_string(), wait).into());
+                }
+                tracing::debug!("slack {} rate-limited, retrying in {}s", method, wait);
+                tokio::time::sleep(Duration::from_secs(wait)).await;
+                waited += wait;
+                continue;
+            }
+            let body: Value = resp.json().await.map_err(|e| anyhow!("slack {method} parse: {e}"))?;
+            if body.get("ok").and_then(Value::as_bool) != Some(true) {
+                let error = body.get("error").and_then(Value::as_str).unwrap_or("unknown_error");
+                return Err(SlackError::Api { method: method.to_string(), error: error.to_string() }.into());
+            }
+            return Ok(body);
+        }
     }
 
-    pub async fn fetch_dm(&self, user_id: &str) -> anyhow::Result<serde_json::Value> {
-        let url = format!("https://slack.com/api/conversations.open");
-        let resp = self.client
-            .post(&url)
-            .bearer_auth(&self.token)
-            .form(&serde_json::json!({"users": user_id}))
-            .send()
-            .await?;
-        let json = resp.json().await?;
-        Ok(json)
+    // Follows next_cursor through every page, collecting the `key` array
+    async fn paged(&self, method: &str, params: &[(&str, &str)], key: &str) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        let mut cursor = String::new();
+        loop {
+            let body = {
+                let mut page = params.to_vec();
+                page.push(("limit", PAGE_LIMIT));
+                if !cursor.is_empty() {
+                    page.push(("cursor", &cursor));
+                }
+                self.call(method, &page).await?
+            };
+            items.extend(body.get(key).and_then(Value::as_array).cloned().unwrap_or_default());
+            cursor = next_cursor(&body);
+            if cursor.is_empty() {
+                return Ok(items);
+            }
+        }
     }
 
-    pub async fn fetch_thread(&self, channel: &str, ts: &str) -> anyhow::Result<serde_json::Value> {
-        let url = format!("https://slack.com/api/conversations.replies?channel={}&ts={}", channel, ts);
-        let resp = self.client
-            .get(&url)
-            .bearer_auth(&self.token)
-            .send()
-            .await?;
-        let json = resp.json().await?;
-        Ok(json)
+    fn default_since() -> String {
+        (chrono::Utc::now() - chrono::Duration::hours(DEFAULT_LOOKBACK_HOURS)).timestamp().to_string()
+    }
+
+    // Top-level messages after `since`
+    async fn history(&self, channel: &str, since: Option<&str>) -> Result<Vec<SlackMessage>> {
+        let oldest = since.map_or_else(Self::default_since, str::to_string);
+        let messages = self.paged("conversations.history", &[("channel", channel), ("oldest", &oldest)], "messages").await?;
+        Ok(messages.iter().filter_map(|m| SlackMessage::from_api(channel, m)).collect())
+    }
+
+    // A thread's parent and replies
+    pub async fn fetch_thread(&self, channel: &str, ts: &str) -> Result<SlackPage> {
+        let messages = self.paged("conversations.replies", &[("channel", channel), ("ts", ts)], "messages").await?;
+        let mut messages = messages.iter().filter_map(|m| SlackMessage::from_api(channel, m)).collect::<Vec<_>>();
+        self.resolve(&mut messages).await;
+        Ok(SlackPage::new(messages, None))
+    }
+
+    pub async fn my_user_id(&self) -> Result<&str> {
+        let id = self.me.get_or_try_init(|| async {
+            let body = self.call("auth.test", &[]).await?;
+            body.get("user_id").and_then(Value::as_str).map(str::to_string)
+                .ok_or_else(|| anyhow!("slack auth.test returned no user_id"))
+        }).await?;
+        Ok(id)
+    }
+
+    async fn cached_name(&self, id: &str) -> Option<String> {
+        self.names.read().await.get(id).cloned()
+    }
+
+    // Display name, else real name, else handle; the id itself when the lookup fails
+    pub async fn user_name(&self, user_id: &str) -> String {
+        if user_id.is_empty() {
+            return String::new();
+        }
+        if let Some(name) = self.cached_name(user_id).await {
+            return name;
+        }
+        let stored = match &self.users {
+            Some(users) => users.display_name(user_id, USER_NAME_MAX_AGE_HOURS).await.unwrap_or_else(|e| {
+                tracing::warn!("slack user cache read failed: {}", e);
+                None
+            }),
+            None => None,
+        };
+        let name = match stored {
+            Some(name) => name,
+            None => match self.call("users.info", &[("user", user_id)]).await {
+                Ok(body) => {
+                    let name = ["/user/profile/display_name", "/user/real_name", "/user/name"].iter()
+                        .filter_map(|path| body.pointer(path).and_then(Value::as_str))
+                        .find(|name| !name.is_empty())
+                        .unwrap_or(user_id)
+                        .to_string();
+                    if let Some(users) = &self.users {
+                        if let Err(e) = users.upsert(user_id, &name).await {
+                            tracing::warn!("slack user cache write failed: {}", e);
+                        }
+                    }
+                    name
+                }
+                Err(e) => {
+                    tracing::debug!("slack users.info {} failed: {}", user_id, e);
+                    return user_id.to_string();
+                }
+            },
+        };
+        self.names.write().await.insert(user_id.to_string(), name.clone());
+        name
+    }
+
+    // "#general", or "@Ana" for a DM
+    pub async fn channel_name(&self, channel_id: &str) -> String {
+        if let Some(name) = self.cached_name(channel_id).await {
+            return name;
+        }
+        let channel = match self.call("conversations.info", &[("channel", channel_id)]).await {
+            Ok(body) => body.get("channel").cloned().unwrap_or_default(),
+            Err(e) => {
+                tracing::debug!("slack conversations.info {} failed: {}", channel_id, e);
+                return channel_id.to_string();
+            }
+        };
+        let name = match (channel.get("is_im").and_then(Value::as_bool), channel.get("user").and_then(Value::as_str)) {
+            (Some(true), Some(user)) => format!("@{}", self.user_name(user).await),
+            _ => format!("#{}", channel.get("name").and_then(Value::as_str).unwrap_or(channel_id)),
+        };
+        self.names.write().await.insert(channel_id.to_string(), name.clone());
+        name
+    }
+
+    async fn resolve(&self, messages: &mut [SlackMessage]) {
+        for message in messages {
+            message.user_name = self.user_name(&message.user).await;
+            if message.channel_name.is_empty() {
+                message.channel_name = self.channel_name(&message.channel).await;
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl SlackConnector for SlackClient {
+    async fn read_dms(&self, since: Option<&str>) -> Result<SlackPage> {
+        let ims = self.paged("conversations.list", &[("types", "im"), ("exclude_archived", "true")], "channels").await?;
+        let mut messages = Vec::new();
+        for im in &ims {
+            let Some(channel) = im.get("id").and_then(Value::as_str) else { continue };
+            messages.extend(self.history(channel, since).await?);
+        }
+        self.resolve(&mut messages).await;
+        Ok(SlackPage::new(messages, since))
+    }
+
+    async fn read_mentions(&self, since: Option<&str>) -> Result<SlackPage> {
+        let me = self.my_user_id().await?.to_string();
+        let query = format!("<@{me}>");
+        let oldest = ts_key(&since.map_or_else(Self::default_since, str::to_string));
+        let mut messages = Vec::new();
+        // Newest first, so stop at the first page that reaches back past `since`
+        for number in 1..=MAX_SEARCH_PAGES {
+            let page = number.to_string();
+            let body = self.call("search.messages", &[("query", &query), ("sort", "timestamp"), ("sort_dir", "desc"), ("count", "100"), ("page", &page)]).await?;
+            let matches = body.pointer("/messages/matches").and_then(Value::as_array).cloned().unwrap_or_default();
+            let mut reached_since = matches.is_empty();
+            for found in &matches {
+                let Some(channel) = found.pointer("/channel/id").and_then(Value::as_str) else { continue };
+                let Some(mut message) = SlackMessage::from_api(channel, found) else { continue };
+                if ts_key(&message.timestamp) <= oldest {
+                    reached_since = true;
+                    continue;
+                }
+                if let Some(name) = found.pointer("/channel/name").and_then(Value::as_str) {
+                    message.channel_name = format!("#{name}");
+                }
+                messages.push(message);
+            }
+            let pages = body.pointer("/messages/paging/pages").and_then(Value::as_u64).unwrap_or(1);
+            if reached_since || u64::from(number) >= pages {
+                break;
+            }
+        }
+        self.resolve(&mut messages).await;
+        Ok(SlackPage::new(messages, since))
+    }
+
+    async fn read_channel(&self, channel_id: &str, since: Option<&str>) -> Result<SlackPage> {
+        let mut messages = self.history(channel_id, since).await?;
+        self.resolve(&mut messages).await;
+        Ok(SlackPage::new(messages, since))
+    }
+
+    async fn draft_message(&self, channel_id: &str, thread_ts: Option<&str>, text: &str) -> Result<SlackDraft> {
+        if channel_id.is_empty() || text.trim().is_empty() {
+            bail!("a Slack reply needs a channel and text");
+        }
+        Ok(SlackDraft {
+            draft_id: draft_id(channel_id, thread_ts, text),
+            channel: channel_id.to_string(),
+            thread_ts: thread_ts.map(str::to_string),
+            text: text.to_string(),
+        })
+    }
+
+    async fn post_approved(&self, draft: &SlackDraft, approved_draft_id: &str) -> Result<String> {
+        if draft_id(&draft.channel, draft.thread_ts.as_deref(), &draft.text) != approved_draft_id {
+            bail!("draft {} was changed after approval; review it again before sending", approved_draft_id);
+        }
+        let mut params = vec![("channel", draft.channel.as_str()), ("text", draft.text.as_str())];
+        if let Some(thread_ts) = &draft.thread_ts {
+            params.push(("thread_ts", thread_ts));
+        }
+        let body = self.call("chat.postMessage", &params).await?;
+        body.get("ts").and_then(Value::as_str).map(str::to_string)
+            .ok_or_else(|| anyhow!("slack chat.postMessage returned no ts"))
     }
 }
 
@@ -116,3 +447,44 @@ fn subtle_eq(a: &str, b: &str) -> bool {
     }
     diff == 0
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_long_retry_after_is_not_waited_out() {
+        assert!(retry_inline(1, 0, 1));
+        assert!(retry_inline(2, 1, 4));
+        assert!(!retry_inline(1, 0, 60));
+        assert!(!retry_inline(3, 4, 2));
+        assert!(!retry_inline(MAX_RETRIES + 1, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_history_parsing_and_draft_approval() {
+        let history = serde_json::json!([
+            { "type": "message", "user": "U2", "text": "second", "ts": "1712345678.000200" },
+            { "type": "message", "subtype": "channel_join", "user": "U3", "text": "joined", "ts": "1712345677.000100" },
+            { "type": "message", "user": "U1", "text": "reply", "ts": "1712345600.000900", "thread_ts": "1712345000.000100" },
+        ]);
+        let messages = history.as_array().unwrap().iter().filter_map(|m| SlackMessage::from_api("D1", m)).collect();
+        let page = SlackPage::new(messages, Some("1712340000.000000"));
+        assert_eq!(page.messages.iter().map(|m| m.text.as_str()).collect::<Vec<_>>(), vec!["reply", "second"]);
+        assert_eq!(page.messages[0].thread_ts.as_deref(), Some("1712345000.000100"));
+        assert_eq!(page.cursor.as_deref(), Some("1712345678.000200"));
+        assert_eq!(SlackPage::new(vec![], Some("1.2")).cursor.as_deref(), Some("1.2"));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), DEFAULT_RETRY_SECS);
+        headers.insert(RETRY_AFTER, "30".parse().unwrap());
+        assert_eq!(retry_after(&headers), 30);
+
+        // Approval is checked before anything goes to Slack
+        let slack = SlackClient::new("xoxp-test".to_string());
+        let draft = slack.draft_message("D1", Some("1712345000.000100"), "On it").await.unwrap();
+        let edited = slack.draft_message("D1", Some("1712345000.000100"), "On it!").await.unwrap();
+        assert_ne!(draft.draft_id, edited.draft_id);
+        assert!(slack.post_approved(&edited, &draft.draft_id).await.is_err());
+    }
+}
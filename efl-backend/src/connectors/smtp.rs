@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use lettre::address::Envelope;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use uuid::Uuid;
+use crate::connectors::gmail_mime::{header_value, reply_message};
+
+// Sends replies for mail synced over IMAP, where there is no Gmail draft to send
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SmtpTls {
+    // Implicit TLS, usually port 465
+    Tls,
+    // Upgrade on 587
+    StartTls,
+    // Local test servers only
+    None,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub tls: SmtpTls,
+    pub username: String,
+    pub password: String,
+    // Our own address, e.g. "Sam <sam@example.com>"
+    pub from: String,
+}
+
+impl SmtpConfig {
+    // None unless SMTP_HOST is set
+    pub fn from_env() -> Option<Self> {
+        let host = std::env::var("SMTP_HOST").ok().filter(|h| !h.is_empty())?;
+        let tls = match std::env::var("SMTP_TLS").unwrap_or_default().to_lowercase().as_str() {
+            "starttls" => SmtpTls::StartTls,
+            "none" => SmtpTls::None,
+            _ => SmtpTls::Tls,
+        };
+        let default_port = match tls {
+            SmtpTls::Tls => 465,
+            SmtpTls::StartTls => 587,
+            SmtpTls::None => 25,
+        };
+        let username = std::env::var("SMTP_USERNAME")
+            .or_else(|_| std::env::var("IMAP_USERNAME"))
+            .unwrap_or_default();
+        Some(Self {
+            host,
+            port: std::env::var("SMTP_PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(default_port),
+            tls,
+            from: std::env::var("SMTP_FROM").unwrap_or_else(|_| username.clone()),
+            password: std::env::var("SMTP_PASSWORD")
+                .or_else(|_| std::env::var("IMAP_PASSWORD"))
+                .unwrap_or_default(),
+            username,
+        })
+    }
+}
+
+pub struct SmtpSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+fn mailbox(value: &str) -> Result<Mailbox> {
+    header_value(value).parse().map_err(|e| anyhow!("bad address {:?}: {}", value, e))
+}
+
+// The whole message as sent. Every header value from the incoming mail goes through
+// `reply_message`, which keeps each on its own line.
+fn raw_reply(from: &Mailbox, to: &Mailbox, message_id: &str, subject: &str, in_reply_to: &str, references: &str, body: &str) -> String {
+    format!(
+        "From: {}\r\nDate: {}\r\nMessage-ID: {}\r\n{}",
+        from,
+        Utc::now().to_rfc2822(),
+        message_id,
+        reply_message(&to.to_string(), subject, in_reply_to, references, body),
+    )
+}
+
+impl SmtpSender {
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        let builder = match config.tls {
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+            SmtpTls::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+        };
+        let mut builder = builder.port(config.port);
+        if !config.username.is_empty() {
+            builder = builder.credentials(Credentials::new(config.username.clone(), config.password.clone()));
+        }
+        Ok(Self { transport: builder.build(), from: mailbox(&config.from)? })
+    }
+
+    // Sends the same reply Gmail drafts get, with From/Date/Message-ID added. Returns the new
+    // Message-ID.
+    pub async fn send_reply(&self, to: &str, subject: &str, in_reply_to: &str, references: &str, body: &str) -> Result<String> {
+        let to = mailbox(to)?;
+        let domain = self.from.email.domain().to_string();
+        let message_id = format!("<{}@{}>", Uuid::new_v4(), domain);
+        let raw = raw_reply(&self.from, &to, &message_id, subject, in_reply_to, references, body);
+        let envelope = Envelope::new(Some(self.from.email.clone()), vec![to.email])?;
+        self.transport.send_raw(&envelope, raw.as_bytes()).await?;
+        Ok(message_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_keeps_imap_headers_on_one_line() {
+        let (from, to) = (mailbox("Me <me@example.com>").unwrap(), mailbox("ana@example.com\r\n").unwrap());
+        // An IMAP subject decoded from an encoded-word with CRLF in it
+        let raw = raw_reply(&from, &to, "<r@example.com>", "Re: hi\r\nBcc: evil@example.com", "<m@example.com>", "", "ok");
+        let (headers, body) = raw.split_once("\r\n\r\n").unwrap();
+        assert!(headers.lines().all(|line| !line.starts_with("Bcc:")));
+        assert!(headers.contains("Subject: Re: hi  Bcc: evil@example.com\r\n"));
+        assert_eq!(body, "ok");
+    }
+}
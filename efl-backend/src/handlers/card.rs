@@ -8,19 +8,40 @@ use axum::{
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
-use crate::{AppState, models::{Card, CardAction, ParkedItem}};
+use crate::{AppState, models::{Altitude, Card, CardAction, CardPatch, CardState, CardType, ParkedItem, WakeCondition}};
+use crate::connectors::gmail::GmailError;
+use crate::models::DraftKind;
+use crate::services::gmail_actions::is_gmail_action;
+use crate::services::gmail_drafts::{is_draft_action, GmailDraftService};
+use crate::services::gmail_threads::latest_message_id;
+use crate::services::card::CardService;
+use crate::services::card_fsm::FsmError;
+use crate::services::parking::SnoozeError;
+use crate::services::undo::UndoError;
+use crate::sqlite::repo::cards::CardFilter;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/", axum::routing::post(create_card))
+        .route("/", axum::routing::get(list_cards))
         .route("/:id", axum::routing::get(get_card))
+        .route("/:id", axum::routing::patch(update_card))
+        .route("/:id", axum::routing::delete(delete_card))
         .route("/:id/action", axum::routing::post(perform_action))
+        .route("/:id/history", axum::routing::get(get_card_history))
         .route("/:id/park", axum::routing::post(park_card))
         .route("/:id/unpark", axum::routing::post(unpark_card))
         .route("/:id/snooze", axum::routing::post(snooze_card))
         .route("/parked", axum::routing::get(get_parked_items))
 }
 
+fn card_service(state: &AppState) -> CardService {
+    CardService::new_with_sqlite(
+        state.db_pool.clone(),
+        state.sqlite_db.as_ref().map(|db| db.pool.clone())
+    ).with_undo(state.undo_service.clone())
+}
+
 #[derive(Deserialize)]
 struct CreateCardRequest {
     card_type: String,
@@ -32,7 +53,7 @@ async fn create_card(
     State(state): State<AppState>,
     Json(req): Json<CreateCardRequest>,
 ) -> impl IntoResponse {
-    let service = crate::services::card::CardService::new(state.db_pool.clone());
+    let service = card_service(&state);
     
     match service.create_card(req.card_type, req.intent_id, req.content).await {
         Ok(card) => (StatusCode::CREATED, Json(card)).into_response(),
@@ -47,7 +68,7 @@ async fn get_card(
     State(state): State<AppState>,
     Path(id): Path<Uuid>,
 ) -> impl IntoResponse {
-    let service = crate::services::card::CardService::new(state.db_pool.clone());
+    let service = card_service(&state);
     
     match service.get_card(id).await {
         Ok(Some(card)) => (StatusCode::OK, Json(card)).into_response(),
@@ -59,6 +80,87 @@ async fn get_card(
     }
 }
 
+#[derive(Deserialize)]
+struct ListCardsQuery {
+    state: Option<CardState>,
+    kind: Option<CardType>,
+    altitude: Option<Altitude>,
+    limit: Option<i64>,
+}
+
+async fn list_cards(
+    State(state): State<AppState>,
+    Query(params): Query<ListCardsQuery>,
+) -> impl IntoResponse {
+    let service = card_service(&state);
+    let filter = CardFilter {
+        state: params.state,
+        card_type: params.kind,
+        altitude: params.altitude,
+        limit: params.limit,
+    };
+
+    match service.list_cards(filter).await {
+        Ok(cards) => (StatusCode::OK, Json(cards)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
+async fn update_card(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    Json(patch): Json<CardPatch>,
+) -> impl IntoResponse {
+    let service = card_service(&state);
+
+    match service.update_card(id, patch).await {
+        Ok(Some(card)) => (StatusCode::OK, Json(card)).into_response(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
+async fn delete_card(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let service = card_service(&state);
+
+    match service.delete_card(id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
+async fn get_card_history(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+) -> impl IntoResponse {
+    let service = card_service(&state);
+
+    match service.history(id).await {
+        Ok((events, _)) if events.is_empty() => StatusCode::NOT_FOUND.into_response(),
+        Ok((events, projection)) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "card_id": id, "events": events, "projection": projection }))
+        ).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
 #[derive(Deserialize)]
 struct ActionRequest {
     action: CardAction,
@@ -70,21 +172,171 @@ async fn perform_action(
     Path(id): Path<Uuid>,
     Json(req): Json<ActionRequest>,
 ) -> impl IntoResponse {
-    let service = crate::services::card::CardService::new(state.db_pool.clone());
+    let service = card_service(&state);
+    if is_gmail_action(&req.action) {
+        return gmail_action(&state, &service, id, req).await;
+    }
+    if is_draft_action(&req.action) {
+        if let Some(response) = draft_action(&state, &service, id, &req).await {
+            return response;
+        }
+    }
     
+    let answered = matches!(req.action, CardAction::RespondNow);
     match service.perform_action(id, req.action, req.payload).await {
-        Ok(result) => (StatusCode::OK, Json(result)).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() }))
-        ).into_response(),
+        Ok(result) => {
+            let to = result.get("state").cloned().and_then(|s| serde_json::from_value::<CardState>(s).ok());
+            if answered || matches!(to, Some(CardState::Committed | CardState::Dismissed)) {
+                settle(&state, id).await;
+            }
+            (StatusCode::OK, Json(result)).into_response()
+        }
+        Err(e) => (action_error_status(&e), Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+// A card that's been committed, dismissed, answered or parked leaves the woken set and
+// the break-in inbox, so it doesn't linger at the top of the feed
+async fn settle(state: &AppState, id: Uuid) {
+    state.parking_service.forget_woken(id).await;
+    state.breakin_inbox.remove(id).await;
+}
+
+// Archive/unsubscribe/block change the user's mailbox, so they take two calls: without a
+// `confirmationToken` the response is the plan to confirm; with it, the plan runs.
+async fn gmail_action(state: &AppState, service: &CardService, id: Uuid, req: ActionRequest) -> axum::response::Response {
+    let payload = req.payload.unwrap_or_default();
+    if let Some(token) = payload.get("confirmationToken").and_then(|t| t.as_str()) {
+        let Ok(token) = Uuid::parse_str(token) else {
+            return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": "invalid confirmationToken" }))).into_response();
+        };
+        return match state.gmail_actions.confirm(id, token).await {
+            Ok(result) => {
+                settle(state, id).await;
+                (StatusCode::OK, Json(result)).into_response()
+            }
+            Err(e) => gmail_error(StatusCode::BAD_GATEWAY, &e),
+        };
+    }
+
+    let Some(card) = resolve_card(service, id, payload.get("card")).await else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "card not found; send it as payload.card" }))).into_response();
+    };
+    match state.gmail_actions.plan(&card, req.action).await {
+        Ok(plan) => (StatusCode::ACCEPTED, Json(serde_json::json!({
+            "status": "confirmation_required",
+            "plan": plan,
+        }))).into_response(),
+        Err(e) => gmail_error(StatusCode::UNPROCESSABLE_ENTITY, &e),
+    }
+}
+
+// Replies are written as Gmail drafts and only leave on an explicit approval: SendDraft
+// without `{"approved": true, "draftId": ...}` returns the draft to review instead.
+// Returns None for cards that aren't Gmail messages, which keep the plain action handling.
+async fn draft_action(state: &AppState, service: &CardService, id: Uuid, req: &ActionRequest) -> Option<axum::response::Response> {
+    let payload = req.payload.clone().unwrap_or_default();
+    let drafts = GmailDraftService::new(state.sqlite_db.as_ref().map(|db| db.pool.clone()));
+    let kind = match req.action {
+        CardAction::GenerateDraft => DraftKind::Reply,
+        CardAction::DeclineRespectfully => DraftKind::Decline,
+        CardAction::SendDraft => {
+            let approved = payload.get("approved").and_then(|a| a.as_bool()).unwrap_or(false);
+            let draft_id = payload.get("draftId").and_then(|d| d.as_str());
+            let response = match (approved, draft_id) {
+                (true, Some(draft_id)) => match drafts.send(id, draft_id).await {
+                    Ok(draft) => {
+                        settle(state, id).await;
+                        (StatusCode::OK, Json(serde_json::json!({ "status": "sent", "draft": draft }))).into_response()
+                    }
+                    Err(e) => gmail_error(StatusCode::CONFLICT, &e),
+                },
+                _ => match drafts.get(id).await {
+                    Ok(Some(draft)) => (StatusCode::ACCEPTED, Json(serde_json::json!({
+                        "status": "approval_required",
+                        "draft": draft,
+                    }))).into_response(),
+                    Ok(None) => (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "card has no draft" }))).into_response(),
+                    Err(e) => gmail_error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+                },
+            };
+            return Some(response);
+        }
+        CardAction::DiscardDraft => {
+            return Some(match drafts.discard(id).await {
+                Ok(draft) => (StatusCode::OK, Json(serde_json::json!({ "status": "discarded", "draft": draft }))).into_response(),
+                Err(e) => gmail_error(StatusCode::CONFLICT, &e),
+            });
+        }
+        _ => return None,
+    };
+
+    let card = resolve_card(service, id, payload.get("card")).await.filter(|c| latest_message_id(c).is_some())?;
+    // The client may send back an edited body to redraft with
+    let body = payload.get("body").and_then(|b| b.as_str()).map(str::to_string);
+    Some(match drafts.draft(&card, kind, body).await {
+        Ok(draft) => (StatusCode::OK, Json(serde_json::json!({ "status": "drafted", "draft": draft }))).into_response(),
+        Err(e) => gmail_error(StatusCode::BAD_GATEWAY, &e),
+    })
+}
+
+// Gmail cards aren't persisted, so the client sends the card along as `payload.card`
+async fn resolve_card(service: &CardService, id: Uuid, sent: Option<&serde_json::Value>) -> Option<Card> {
+    match service.get_card(id).await {
+        Ok(Some(card)) => Some(card),
+        _ => match sent.cloned().map(serde_json::from_value::<Card>) {
+            Some(Ok(card)) if card.id == id => Some(card),
+            _ => None,
+        },
+    }
+}
+
+// A missing Gmail scope sends the client back through OAuth
+fn gmail_error(status: StatusCode, e: &anyhow::Error) -> axum::response::Response {
+    match e.downcast_ref::<GmailError>() {
+        Some(GmailError::ScopeRequired(_)) => (StatusCode::FORBIDDEN, Json(serde_json::json!({
+            "error": e.to_string(),
+            "authorizeUrl": "/api/v1/auth/google/authorize",
+        }))).into_response(),
+        _ => (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+fn action_error_status(e: &anyhow::Error) -> StatusCode {
+    match e.downcast_ref::<FsmError>() {
+        Some(FsmError::NotFound(_)) => StatusCode::NOT_FOUND,
+        Some(FsmError::IllegalTransition { .. } | FsmError::Conflict(_)) => StatusCode::CONFLICT,
+        Some(FsmError::Unavailable) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => match e.downcast_ref::<UndoError>() {
+            // The document moved on or there's nothing left to undo/redo
+            Some(UndoError::Memory(_)) | None => StatusCode::INTERNAL_SERVER_ERROR,
+            // A client-sent diff that doesn't fit the document
+            Some(UndoError::InvalidOperation(_)) => StatusCode::BAD_REQUEST,
+            Some(_) => StatusCode::CONFLICT,
+        },
     }
 }
 
 #[derive(Deserialize)]
 struct ParkRequest {
-    wake_time: DateTime<Utc>,
+    wake_time: Option<DateTime<Utc>>,
+    // Natural-language alternative to `wake_time`, e.g. "tomorrow 9am"
+    when: Option<String>,
     reason: Option<String>,
+    // Extra conditions that wake the card before `wake_time`
+    #[serde(default)]
+    wake_on: Vec<WakeCondition>,
+    // Gmail cards aren't persisted, so the client sends the card along
+    card: Option<serde_json::Value>,
+}
+
+// Picks the absolute time if given, otherwise resolves the phrase in the user's timezone
+fn resolve_wake_time(state: &AppState, wake_time: Option<DateTime<Utc>>, when: Option<&str>) -> Result<DateTime<Utc>, String> {
+    match (wake_time, when) {
+        (Some(at), _) => Ok(at),
+        (None, Some(phrase)) => state.parking_service.resolve_wake_time(phrase).map_err(|e| e.to_string()),
+        (None, None) => Err("a wake time is required".to_string()),
+    }
 }
 
 async fn park_card(
@@ -92,45 +344,29 @@ async fn park_card(
     Path(id): Path<Uuid>,
     Json(req): Json<ParkRequest>,
 ) -> impl IntoResponse {
-    // Get the actual card to park
-    // For now, since we don't have real card persistence, create a minimal card
-    // In production, we'd fetch the real card from the database
-    let card = Card {
-        id,
-        card_type: crate::models::CardType::DoNow,
-        altitude: crate::models::Altitude::Do,
-        title: format!("Card {}", id),
-        content: crate::models::CardContent::DoNow {
-            intent: crate::models::Intent {
-                id: Uuid::new_v4(),
-                name: "Parked task".to_string(),
-                description: "Task that was parked".to_string(),
-                intent_type: crate::models::IntentType::Operate,
-                rationale: "Parked for later".to_string(),
-                preconditions: vec![],
-                estimated_tokens: 0,
-                created_at: chrono::Utc::now(),
-            },
-            preview: "Parked card".to_string(),
-            diff: None,
-        },
-        actions: vec![],
-        origin_object: None,
-        created_at: chrono::Utc::now(),
-        status: crate::models::CardStatus::Active,
-        metadata: None,
+    let wake_time = match resolve_wake_time(&state, req.wake_time, req.when.as_deref()) {
+        Ok(at) => at,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+    };
+    let Some(card) = resolve_card(&card_service(&state), id, req.card.as_ref()).await else {
+        return (StatusCode::NOT_FOUND, Json(serde_json::json!({ "error": "card not found; send it as card" }))).into_response();
     };
     
     let reason = req.reason.unwrap_or_else(|| "Parked for later".to_string());
-    match state.parking_service.park_card(card, req.wake_time, reason).await {
-        Ok(card_id) => (
-            StatusCode::OK, 
-            Json(serde_json::json!({ "card_id": card_id, "wake_time": req.wake_time }))
-        ).into_response(),
-        Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(serde_json::json!({ "error": e.to_string() }))
-        ).into_response(),
+    match state.parking_service.park_card_with_conditions(card, wake_time, req.wake_on, reason).await {
+        Ok(card_id) => {
+            state.breakin_inbox.remove(card_id).await;
+            (
+                StatusCode::OK,
+                Json(serde_json::json!({
+                    "card_id": card_id,
+                    "wake_time": wake_time,
+                    "when": req.when,
+                    "timezone": state.parking_service.timezone(),
+                }))
+            ).into_response()
+        }
+        Err(e) => (action_error_status(&e), Json(serde_json::json!({ "error": e.to_string() }))).into_response(),
     }
 }
 
@@ -150,7 +386,17 @@ async fn unpark_card(
 
 #[derive(Deserialize)]
 struct SnoozeRequest {
-    minutes: i64,
+    minutes: Option<i64>,
+    // Natural-language new wake time, e.g. "after lunch"
+    until: Option<String>,
+}
+
+fn snooze_error_status(e: &anyhow::Error) -> StatusCode {
+    match e.downcast_ref::<SnoozeError>() {
+        Some(SnoozeError::NotParked(_)) => StatusCode::NOT_FOUND,
+        Some(SnoozeError::OutOfRange(_)) => StatusCode::BAD_REQUEST,
+        None => StatusCode::INTERNAL_SERVER_ERROR,
+    }
 }
 
 async fn snooze_card(
@@ -158,10 +404,20 @@ async fn snooze_card(
     Path(id): Path<Uuid>,
     Json(req): Json<SnoozeRequest>,
 ) -> impl IntoResponse {
-    match state.parking_service.snooze_card(id, req.minutes).await {
-        Ok(_) => StatusCode::OK.into_response(),
+    let result = match (req.minutes, req.until.as_deref()) {
+        (Some(minutes), _) => state.parking_service.snooze_card(id, minutes).await,
+        (None, until) => match resolve_wake_time(&state, None, until) {
+            Ok(at) => state.parking_service.reschedule(id, at).await,
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+        },
+    };
+    match result {
+        Ok(wake_time) => (
+            StatusCode::OK,
+            Json(serde_json::json!({ "card_id": id, "wake_time": wake_time, "timezone": state.parking_service.timezone() }))
+        ).into_response(),
         Err(e) => (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            snooze_error_status(&e),
             Json(serde_json::json!({ "error": e.to_string() }))
         ).into_response(),
     }
@@ -1,19 +1,25 @@
 use axum::{
     Router,
     extract::{State, Query},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     response::IntoResponse,
     Json,
 };
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use uuid::Uuid;
-use crate::{AppState, models::{altitude::Altitude, card::Card}};
+use crate::{AppState, models::{altitude::Altitude, card::Card, ParkedItem, WakeCondition}};
+use crate::services::altimeter::AltimeterService;
+use crate::services::altitude::DEFAULT_USER;
+use crate::services::altitude_policy::{AltitudePolicy, Held};
+use crate::services::feed::{FeedCursor, FeedService};
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/", axum::routing::get(get_feed))
         .route("/altitude", axum::routing::get(get_altitude))
         .route("/altitude", axum::routing::put(set_altitude))
+        .route("/altitude/state", axum::routing::get(get_altitude_state))
+        .route("/altitude/policy/dry-run", axum::routing::post(dry_run_policy))
         .route("/demo", axum::routing::get(get_demo_feed))
 }
 
@@ -21,26 +27,55 @@ pub fn routes() -> Router<AppState> {
 struct FeedQuery {
     limit: Option<usize>,
     altitude: Option<Altitude>,
+    // `next_cursor` from the previous page
+    cursor: Option<String>,
 }
 
-#[derive(Serialize)]
-struct FeedResponse {
-    cards: Vec<Card>,
-    current_altitude: Altitude,
-    parked_count: usize,
+// Callers identify themselves with X-User-Id until there is real auth
+fn user_id(headers: &HeaderMap) -> String {
+    headers.get("x-user-id")
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.trim().is_empty())
+        .unwrap_or(DEFAULT_USER)
+        .to_string()
+}
+
+fn feed_service(state: &AppState) -> FeedService {
+    FeedService::new_with_sqlite(
+        state.db_pool.clone(),
+        state.sqlite_db.as_ref().map(|db| db.pool.clone())
+    ).with_sources(state.parking_service.clone(), state.breakin_inbox.clone())
 }
 
 async fn get_feed(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Query(params): Query<FeedQuery>,
 ) -> impl IntoResponse {
-    let service = crate::services::feed::FeedService::new_with_sqlite(
-        state.db_pool.clone(),
-        state.sqlite_db.as_ref().map(|db| db.pool.clone())
-    );
+    let service = feed_service(&state);
     let limit = params.limit.unwrap_or(10);
+    let cursor = match params.cursor.as_deref().map(FeedCursor::decode) {
+        Some(None) => return (
+            StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({ "error": "invalid cursor" }))
+        ).into_response(),
+        Some(cursor) => cursor,
+        None => None,
+    };
+    
+    // The feed ranks for the user's current altitude unless asked for another
+    let altitude = match params.altitude {
+        Some(altitude) => altitude,
+        None => match state.altitude_service.current(&user_id(&headers)).await {
+            Ok(altitude) => altitude,
+            Err(e) => return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({ "error": e.to_string() }))
+            ).into_response(),
+        },
+    };
     
-    match service.get_feed(params.altitude, limit).await {
+    match service.get_feed(altitude, cursor.as_ref(), limit).await {
         Ok(response) => (StatusCode::OK, Json(response)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -51,10 +86,9 @@ async fn get_feed(
 
 async fn get_altitude(
     State(state): State<AppState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
-    let service = crate::services::feed::FeedService::new(state.db_pool.clone());
-    
-    match service.get_current_altitude().await {
+    match state.altitude_service.current(&user_id(&headers)).await {
         Ok(altitude) => (StatusCode::OK, Json(altitude)).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -68,14 +102,29 @@ struct SetAltitudeRequest {
     altitude: Altitude,
 }
 
+// Current altitude, previous altitude, and today's gates
+async fn get_altitude_state(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    match state.altitude_service.state(&user_id(&headers)).await {
+        Ok(altitude_state) => (StatusCode::OK, Json(altitude_state)).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    }
+}
+
 async fn set_altitude(
     State(state): State<AppState>,
+    headers: HeaderMap,
     Json(req): Json<SetAltitudeRequest>,
 ) -> impl IntoResponse {
-    let service = crate::services::feed::FeedService::new(state.db_pool.clone());
+    let cards = feed_service(&state).active_cards().await;
     
-    match service.set_altitude(req.altitude).await {
-        Ok(_) => StatusCode::OK.into_response(),
+    match state.altitude_service.transition(&user_id(&headers), req.altitude, &cards).await {
+        Ok(gate) => (StatusCode::OK, Json(serde_json::json!({ "altitude": req.altitude, "gate": gate }))).into_response(),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(serde_json::json!({ "error": e.to_string() }))
@@ -83,28 +132,62 @@ async fn set_altitude(
     }
 }
 
-async fn get_demo_feed(
+#[derive(Deserialize)]
+struct DryRunRequest {
+    // Defaults to the policy the altimeter is running
+    policy: Option<AltitudePolicy>,
+    // Defaults to a snapshot of the active cards
+    cards: Option<Vec<Card>>,
+    // Altitude to treat as already recommended, so the hysteresis margin applies
+    current: Option<Altitude>,
+}
+
+// Evaluates a policy without touching the live altimeter
+async fn dry_run_policy(
     State(state): State<AppState>,
+    Json(req): Json<DryRunRequest>,
 ) -> impl IntoResponse {
+    let policy = req.policy.unwrap_or_else(|| state.altimeter.policy().clone());
+    if let Err(e) = policy.validate() {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e.to_string() }))).into_response();
+    }
+    let cards = match req.cards {
+        Some(cards) => cards,
+        None => feed_service(&state).active_cards().await,
+    };
+    let progress = AltimeterService::progress_of(&cards);
+    let now = chrono::Utc::now();
+    // Held long enough ago that only the margin matters
+    let held = req.current.map(|altitude| Held { altitude, since: now - chrono::Duration::days(1) });
+    let recommendation = policy.recommend(&progress, held, now);
+    (StatusCode::OK, Json(serde_json::json!({
+        "cardCount": cards.len(),
+        "progress": progress,
+        "recommendation": recommendation,
+    }))).into_response()
+}
+
+async fn get_demo_feed() -> impl IntoResponse {
     use crate::services::mock_data::*;
     use chrono::Utc;
-    
-    // Add some cards to parking for demo
-    let parked_card1 = generate_mock_do_now_card();
-    let parked_card2 = generate_mock_ship_card();
-    
-    let _ = state.parking_service.park_card(
-        parked_card1.clone(),
-        Utc::now() + chrono::Duration::minutes(15),
-        "Waiting for API review".to_string()
-    ).await;
-    
-    let _ = state.parking_service.park_card(
-        parked_card2.clone(),
-        Utc::now() - chrono::Duration::minutes(5), // Overdue
-        "Ready to continue after meeting".to_string()
-    ).await;
-    
+
+    // Demo parked items are built here rather than parked, so nothing reaches the store
+    let parked_items: Vec<ParkedItem> = [
+        (generate_mock_do_now_card(), 15, "Waiting for API review"),
+        (generate_mock_ship_card(), -5, "Ready to continue after meeting"), // Overdue
+    ].into_iter().map(|(card, minutes, reason)| {
+        let wake_time = Utc::now() + chrono::Duration::minutes(minutes);
+        ParkedItem {
+            id: card.id,
+            title: card.title,
+            wake_time,
+            altitude: card.altitude,
+            origin_card_id: card.id,
+            context: Some(reason.to_string()),
+            wake_conditions: vec![WakeCondition::Time(wake_time)],
+        }
+    }).collect();
+
     let cards = vec![
         generate_mock_do_now_card(),
         generate_mock_ship_card(),
@@ -113,8 +196,6 @@ async fn get_demo_feed(
         generate_mock_breakin_card(),
     ];
     
-    let parked_items = state.parking_service.get_parked_items().await;
-    
     let response = serde_json::json!({
         "cards": cards,
         "current_altitude": Altitude::Do,
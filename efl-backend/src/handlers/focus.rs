@@ -0,0 +1,54 @@
+use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use uuid::Uuid;
+use crate::AppState;
+
+pub fn routes() -> Router<AppState> {
+    Router::new()
+        .route("/", axum::routing::get(get_focus))
+        .route("/start", axum::routing::post(start_focus))
+        .route("/stop", axum::routing::post(stop_focus))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StartFocusRequest {
+    card_id: Option<Uuid>,
+    // Absolute end time, or a phrase like "in 45m" or "after lunch"
+    until: Option<DateTime<Utc>>,
+    until_phrase: Option<String>,
+    // Triage score a break-in needs to get through; defaults to FOCUS_THRESHOLD / TRIAGE_THRESHOLD
+    threshold: Option<f64>,
+}
+
+async fn get_focus(State(state): State<AppState>) -> impl IntoResponse {
+    let session = state.focus_service.current().await;
+    (StatusCode::OK, Json(json!({ "active": session.is_some(), "session": session })))
+}
+
+async fn start_focus(
+    State(state): State<AppState>,
+    Json(req): Json<StartFocusRequest>,
+) -> impl IntoResponse {
+    let ends_at = match (req.until, req.until_phrase.as_deref()) {
+        (Some(at), _) => Some(at),
+        (None, Some(phrase)) => match state.parking_service.resolve_wake_time(phrase) {
+            Ok(at) => Some(at),
+            Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+        },
+        (None, None) => None,
+    };
+    match state.focus_service.start(req.card_id, ends_at, req.threshold).await {
+        Ok(session) => (StatusCode::OK, Json(json!({ "session": session }))).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(json!({ "error": e.to_string() }))).into_response(),
+    }
+}
+
+async fn stop_focus(State(state): State<AppState>) -> impl IntoResponse {
+    match state.focus_service.stop().await {
+        Some(summary) => (StatusCode::OK, Json(json!(summary))).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(json!({ "error": "no focus session is active" }))).into_response(),
+    }
+}
@@ -1,10 +1,61 @@
 use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, Json};
-use crate::{AppState, connectors::gmail::GmailClient, services::gmail_cards::GmailCardService};
+use crate::{AppState, connectors::gmail::{GmailClient, GmailError}, services::gmail_cards::GmailCardService};
+use crate::connectors::email::{sources_for, MailChange};
+use crate::services::wake_registry::gmail_thread_reply;
+use crate::sqlite::repo::gmail_cache::GmailCacheRepo;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/list", axum::routing::get(list_unread))
         .route("/cards", axum::routing::get(get_gmail_cards))
+        .route("/messages/read", axum::routing::post(mark_read))
+        .route("/sync", axum::routing::get(sync_status))
+        .route("/sync", axum::routing::post(sync_now))
+}
+
+async fn sync_status(State(state): State<AppState>) -> impl IntoResponse {
+    Json(state.gmail_sync.status().await)
+}
+
+// Runs a sync right away instead of waiting for the worker's next tick
+async fn sync_now(State(state): State<AppState>) -> impl IntoResponse {
+    match state.gmail_sync.sync().await {
+        Ok(report) => (StatusCode::OK, Json(serde_json::json!(report))).into_response(),
+        Err(e) => (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct MarkReadRequest {
+    ids: Vec<String>,
+}
+
+// Reversible and expected when a card is opened, so unlike archive/unsubscribe/block
+// this skips the confirmation step
+async fn mark_read(State(state): State<AppState>, Json(req): Json<MarkReadRequest>) -> impl IntoResponse {
+    let result = async {
+        for (mut source, ids) in sources_for(&req.ids, state.sqlite_db.as_ref().map(|db| db.pool.clone())).await? {
+            source.mutate(&ids, MailChange::MarkRead).await?;
+        }
+        anyhow::Ok(())
+    };
+    match result.await {
+        Ok(()) => {
+            if let Some(db) = &state.sqlite_db {
+                if let Err(e) = GmailCacheRepo::new(db.pool.clone()).mark_read(&req.ids).await {
+                    tracing::warn!("gmail cache mark-read failed: {}", e);
+                }
+            }
+            (StatusCode::OK, Json(serde_json::json!({"marked_read": req.ids.len()}))).into_response()
+        }
+        Err(e) => match e.downcast_ref::<GmailError>() {
+            Some(GmailError::ScopeRequired(_)) => (StatusCode::FORBIDDEN, Json(serde_json::json!({
+                "error": e.to_string(),
+                "authorizeUrl": "/api/v1/auth/google/authorize",
+            }))).into_response(),
+            _ => (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
+        },
+    }
 }
 
 async fn list_unread(State(state): State<AppState>) -> impl IntoResponse {
@@ -12,18 +63,41 @@ async fn list_unread(State(state): State<AppState>) -> impl IntoResponse {
         state.sqlite_db.as_ref().map(|db| db.pool.clone())
     ).await;
     match client.list_unread(5).await {
-        Ok(list) => (StatusCode::OK, Json(list)).into_response(),
+        Ok(list) => {
+            for msg in &list {
+                publish_thread_reply(&state, &msg.thread_id, &msg.date).await;
+            }
+            (StatusCode::OK, Json(list)).into_response()
+        }
         Err(e) => (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
     }
 }
 
 async fn get_gmail_cards(State(state): State<AppState>) -> impl IntoResponse {
-    let mut service = GmailCardService::new(
-        state.sqlite_db.as_ref().map(|db| db.pool.clone())
-    ).await;
-    match service.fetch_gmail_cards(10).await {
-        Ok(cards) => (StatusCode::OK, Json(cards)).into_response(),
+    let pool = state.sqlite_db.as_ref().map(|db| db.pool.clone());
+    let mut client = GmailClient::from_env_with_db(pool.clone()).await;
+    let service = GmailCardService::new(pool).await;
+    match service.cards_from(&mut client, 10).await {
+        Ok(cards) => {
+            for card in &cards {
+                let thread_id = card.origin_object.as_ref().and_then(|o| o.block_id.as_deref());
+                let date = card.metadata.as_ref().and_then(|m| m.email_date.as_deref());
+                if let (Some(thread_id), Some(date)) = (thread_id, date) {
+                    publish_thread_reply(&state, thread_id, date).await;
+                }
+            }
+            (StatusCode::OK, Json(cards)).into_response()
+        }
         Err(e) => (StatusCode::BAD_GATEWAY, Json(serde_json::json!({"error": e.to_string()}))).into_response(),
     }
 }
 
+// Wakes cards parked on the thread. Messages without a parseable Date header are skipped,
+// since we can't tell whether they arrived after the card was parked.
+async fn publish_thread_reply(state: &AppState, thread_id: &str, date: &str) {
+    let Ok(sent_at) = chrono::DateTime::parse_from_rfc2822(date) else { return };
+    let event = gmail_thread_reply(thread_id);
+    if let Err(e) = state.parking_service.publish(&event, Some(sent_at.with_timezone(&chrono::Utc))).await {
+        tracing::warn!("gmail thread wake failed for {}: {}", thread_id, e);
+    }
+}
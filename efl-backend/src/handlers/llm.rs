@@ -1,7 +1,8 @@
 use axum::{Router, extract::State, http::StatusCode, response::IntoResponse, Json};
 use serde::{Deserialize, Serialize};
 
-use crate::{AppState, llm::{self, LlmProvider}};
+use crate::{AppState, llm::{self, LlmProvider}, models::Diff};
+use crate::services::diff::{compute_diff, DiffGranularity};
 
 pub fn routes() -> Router<AppState> {
     Router::new()
@@ -42,10 +43,20 @@ pub async fn post_intents(State(_app): State<AppState>, Json(body): Json<Intents
 }
 
 #[derive(Deserialize)]
-pub struct SimplifyReq { pub snippet: String }
+pub struct SimplifyReq {
+    pub snippet: String,
+    #[serde(default)]
+    pub granularity: DiffGranularity,
+}
 
 #[derive(Serialize, Deserialize)]
-struct SimplifyResp { rewrite: String, reasoning: String }
+struct SimplifyResp {
+    rewrite: String,
+    reasoning: String,
+    // Filled in server-side from snippet → rewrite
+    #[serde(default)]
+    diff: Option<Diff>,
+}
 
 pub async fn post_transform_simplify(State(_app): State<AppState>, Json(body): Json<SimplifyReq>) -> impl IntoResponse {
     let system = include_str!("../llm/prompts/transform_simplify.md");
@@ -59,7 +70,10 @@ pub async fn post_transform_simplify(State(_app): State<AppState>, Json(body): J
         provider.json(system, &user).await
     };
     match out {
-        Ok(resp) => (StatusCode::OK, Json(resp)).into_response(),
+        Ok(mut resp) => {
+            resp.diff = Some(compute_diff(&body.snippet, &resp.rewrite, body.granularity));
+            (StatusCode::OK, Json(resp)).into_response()
+        }
         Err(_) => StatusCode::BAD_GATEWAY.into_response(),
     }
 }
@@ -7,13 +7,15 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
-use crate::{AppState, models::WorkingSet};
+use crate::{AppState, models::{Summary, WorkingSet}};
+use crate::services::wake_registry::summary_updated;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/working-set", axum::routing::get(get_working_set))
         .route("/working-set", axum::routing::put(update_working_set))
         .route("/summaries/:key", axum::routing::get(get_summary))
+        .route("/summaries/:key", axum::routing::put(put_summary))
 }
 
 async fn get_working_set(
@@ -75,4 +77,31 @@ async fn get_summary(
             Json(serde_json::json!({ "error": e.to_string() }))
         ).into_response(),
     }
-}
\ No newline at end of file
+}
+
+async fn put_summary(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Json(summary): Json<Summary>,
+) -> impl IntoResponse {
+    let service = crate::services::memory::MemoryService::new(
+        state.db_pool.clone(),
+        state.memory_cache.clone()
+    );
+    
+    let summary = match service.put_summary(&key, summary).await {
+        Ok(summary) => summary,
+        Err(e) => return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({ "error": e.to_string() }))
+        ).into_response(),
+    };
+    // Cards parked on this summary wake now
+    let woken = state.parking_service.publish(&summary_updated(&key), None).await
+        .map(|cards| cards.len())
+        .unwrap_or_else(|e| {
+            tracing::warn!("summary wake failed for {}: {}", key, e);
+            0
+        });
+    (StatusCode::OK, Json(serde_json::json!({ "summary": summary, "woken": woken }))).into_response()
+}
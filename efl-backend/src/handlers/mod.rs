@@ -11,3 +11,4 @@ pub mod slack;
 pub mod gmail;
 pub mod oauth;
 pub mod slack_map;
+pub mod focus;
@@ -7,6 +7,7 @@ use axum::{
 };
 use serde::{Deserialize, Serialize};
 use crate::AppState;
+use crate::connectors::gmail::OAUTH_SCOPES;
 use crate::sqlite::oauth::OAuthToken;
 use chrono::{Duration, Utc};
 
@@ -22,16 +23,18 @@ async fn google_authorize() -> impl IntoResponse {
         .expect("GMAIL_CLIENT_ID environment variable not set");
     
     let redirect_uri = "http://localhost:3000/api/v1/auth/google/callback";
-    let scope = "https://www.googleapis.com/auth/gmail.readonly";
+    // Write scopes for archive/unsubscribe/block; re-running this upgrades a read-only grant
+    let scope = OAUTH_SCOPES.join(" ");
     
     let auth_url = format!(
-        "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&response_type=code&scope={}&access_type=offline&prompt=consent",
+        "https://accounts.google.com/o/oauth2/v2/auth?client_id={}&redirect_uri={}&response_type=code&scope={}&access_type=offline&prompt=consent&include_granted_scopes=true",
         client_id,
         urlencoding::encode(redirect_uri),
-        urlencoding::encode(scope)
+        urlencoding::encode(&scope)
     );
     
-    Redirect::permanent(&auth_url)
+    // Temporary, so browsers don't cache the URL and its scopes
+    Redirect::temporary(&auth_url)
 }
 
 #[derive(Deserialize)]
@@ -56,6 +59,8 @@ struct TokenResponse {
     refresh_token: Option<String>,
     expires_in: i64,
     token_type: String,
+    // Space-separated scopes the user actually granted
+    scope: Option<String>,
 }
 
 // Handle OAuth callback from Google
@@ -106,7 +111,7 @@ async fn google_callback(
                                 access_token: Some(tokens.access_token.clone()),
                                 refresh_token: tokens.refresh_token.clone(),
                                 expires_at: Some(expires_at),
-                                scopes: Some("https://www.googleapis.com/auth/gmail.readonly".to_string()),
+                                scopes: Some(tokens.scope.clone().unwrap_or_else(|| OAUTH_SCOPES.join(" "))),
                                 created_at: None,
                                 updated_at: None,
                             };
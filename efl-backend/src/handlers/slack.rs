@@ -1,12 +1,101 @@
-use axum::{Router, extract::State, http::{StatusCode, HeaderMap, Request}, response::IntoResponse, Json, body};
+use axum::{Router, extract::{Path, Query, State}, http::{StatusCode, HeaderMap, Request}, response::{IntoResponse, Response}, Json, body};
+use serde::Deserialize;
 use serde_json::json;
-use crate::{AppState, connectors::slack::{verify_signature}, sse::SseEvent};
-use crate::sqlite::db::SqliteDb;
-use crate::sqlite::repo::slack_map::SlackMapRepo;
+use crate::{AppState, connectors::slack::{verify_signature, SlackConnector, SlackError}, sse::SseEvent};
+use crate::connectors::slack::{ts_to_datetime, SlackMessage};
+use crate::models::{Card, CardContent, CardState};
+use crate::services::focus::Admission;
+use crate::services::slack_breakins::SlackBreakInService;
+use crate::services::triage::TriageContext;
+use crate::services::wake_registry::slack_thread_reply;
+use crate::sqlite::repo::cards::kind_label;
 
 pub fn routes() -> Router<AppState> {
     Router::new()
         .route("/events", axum::routing::post(events))
+        .route("/dms", axum::routing::get(list_dms))
+        .route("/mentions", axum::routing::get(list_mentions))
+        .route("/channels/:channel/history", axum::routing::get(channel_history))
+        .route("/threads/:channel/:ts", axum::routing::get(thread))
+        .route("/reply", axum::routing::post(reply))
+}
+
+#[derive(Deserialize)]
+struct SinceQuery {
+    // The `cursor` of the previous page
+    since: Option<String>,
+}
+
+fn slack_unavailable() -> Response {
+    (StatusCode::SERVICE_UNAVAILABLE, Json(json!({
+        "error": "SLACK_USER_TOKEN or SLACK_BOT_TOKEN not set",
+    }))).into_response()
+}
+
+fn slack_error(e: &anyhow::Error) -> Response {
+    match e.downcast_ref::<SlackError>() {
+        Some(SlackError::RateLimited(_, retry_after)) => (StatusCode::TOO_MANY_REQUESTS, Json(json!({
+            "error": e.to_string(),
+            "retryAfter": retry_after,
+        }))).into_response(),
+        _ => (StatusCode::BAD_GATEWAY, Json(json!({"error": e.to_string()}))).into_response(),
+    }
+}
+
+fn page_response(result: anyhow::Result<crate::connectors::slack::SlackPage>) -> Response {
+    match result {
+        Ok(page) => (StatusCode::OK, Json(page)).into_response(),
+        Err(e) => slack_error(&e),
+    }
+}
+
+async fn list_dms(State(state): State<AppState>, Query(q): Query<SinceQuery>) -> Response {
+    let Some(slack) = &state.slack else { return slack_unavailable() };
+    page_response(slack.read_dms(q.since.as_deref()).await)
+}
+
+async fn list_mentions(State(state): State<AppState>, Query(q): Query<SinceQuery>) -> Response {
+    let Some(slack) = &state.slack else { return slack_unavailable() };
+    page_response(slack.read_mentions(q.since.as_deref()).await)
+}
+
+async fn channel_history(State(state): State<AppState>, Path(channel): Path<String>, Query(q): Query<SinceQuery>) -> Response {
+    let Some(slack) = &state.slack else { return slack_unavailable() };
+    page_response(slack.read_channel(&channel, q.since.as_deref()).await)
+}
+
+async fn thread(State(state): State<AppState>, Path((channel, ts)): Path<(String, String)>) -> Response {
+    let Some(slack) = &state.slack else { return slack_unavailable() };
+    page_response(slack.fetch_thread(&channel, &ts).await)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReplyRequest {
+    channel: String,
+    thread_ts: Option<String>,
+    text: String,
+    #[serde(default)]
+    approved: bool,
+    // The draftId from the approval_required response the user reviewed
+    draft_id: Option<String>,
+}
+
+// Nothing is posted until the request comes back approved with the reviewed draft's id
+async fn reply(State(state): State<AppState>, Json(req): Json<ReplyRequest>) -> Response {
+    let Some(slack) = &state.slack else { return slack_unavailable() };
+    let draft = match slack.draft_message(&req.channel, req.thread_ts.as_deref(), &req.text).await {
+        Ok(draft) => draft,
+        Err(e) => return (StatusCode::BAD_REQUEST, Json(json!({"error": e.to_string()}))).into_response(),
+    };
+    match (req.approved, req.draft_id.as_deref()) {
+        (true, Some(approved)) => match slack.post_approved(&draft, approved).await {
+            Ok(ts) => (StatusCode::OK, Json(json!({ "status": "sent", "ts": ts, "draft": draft }))).into_response(),
+            Err(e) if e.downcast_ref::<SlackError>().is_some() => slack_error(&e),
+            Err(e) => (StatusCode::CONFLICT, Json(json!({"error": e.to_string()}))).into_response(),
+        },
+        _ => (StatusCode::ACCEPTED, Json(json!({ "status": "approval_required", "draft": draft }))).into_response(),
+    }
 }
 
 // Slack Events API endpoint: verifies signature, handles URL verification, basic message events.
@@ -60,54 +149,33 @@ async fn events(
                 let channel = event.get("channel").and_then(|v| v.as_str());
                 let thread_ts = event.get("thread_ts").and_then(|v| v.as_str()).or_else(|| event.get("ts").and_then(|v| v.as_str()));
                 if let (Some(ch), Some(ts)) = (channel, thread_ts) {
-                    // Look up mapping in SQLite if configured
-                    if let Some(database_url) = std::env::var("SQLITE_URL").ok().or_else(|| {
-                        let db = std::env::var("DATABASE_URL").unwrap_or_default();
-                        if db.starts_with("sqlite://") { Some(db) } else { None }
-                    }) {
-                        if let Ok(sqlite) = SqliteDb::connect(&database_url).await {
-                            let repo = SlackMapRepo::new(sqlite.pool);
-                            if let Ok(Some(card_id)) = repo.find_card_by_thread(ch, ts).await {
-                                let _ = _state.sse_tx.send(SseEvent{ event: "wake.fire".into(), data: serde_json::json!({"id": card_id}).to_string() });
-                            }
-                        }
+                    // Wakes any card parked on this thread, including cards mapped via /slack/map
+                    let occurred_at = event.get("ts").and_then(|v| v.as_str()).and_then(ts_to_datetime);
+                    if let Err(e) = _state.parking_service.publish(&slack_thread_reply(ch, ts), occurred_at).await {
+                        tracing::warn!("slack thread wake failed: {}", e);
                     }
                 }
-                // Break-in for urgent DMs (simple heuristic)
+                // Every DM is an interrupt; triage decides whether it preempts the queue
                 if event.get("channel_type").and_then(|v| v.as_str()) == Some("im") {
-                    if let Some(text) = event.get("text").and_then(|v| v.as_str()) {
-                        let urgent = text.to_lowercase().contains("urgent") || text.to_lowercase().contains("now");
-                        let sender = event.get("user").and_then(|v| v.as_str()).unwrap_or("unknown");
-                        let breakin = serde_json::json!({
-                            "card": {
-                                "id": format!("slack-{}", event.get("ts").and_then(|v| v.as_str()).unwrap_or("ts")),
-                                "type": "Active",
-                                "kind": "BreakIn",
-                                "data": {
-                                    "id": format!("slack-{}", event.get("ts").and_then(|v| v.as_str()).unwrap_or("ts")),
-                                    "cardType": "break_in",
-                                    "altitude": "do",
-                                    "title": "New Slack DM",
-                                    "content": {
-                                        "type": "break_in",
-                                        "source": ch_or("slack:dm", channel),
-                                        "message": text,
-                                        "sender": sender,
-                                        "urgency": if urgent { "high" } else { "medium" }
-                                    },
-                                    "actions": ["respond_now","respond_at_break"],
-                                    "createdAt": chrono::Utc::now().to_rfc3339(),
-                                    "status": "active"
-                                }
+                    if let Some(message) = channel.and_then(|ch| SlackMessage::from_api(ch, event)) {
+                        if !is_own_message(&_state, event, &message).await {
+                            let ctx = TriageContext::from_env().with_summaries(&_state.memory_cache);
+                            let service = SlackBreakInService::new(_state.sqlite_db.as_ref().map(|db| db.pool.clone()), _state.slack.clone());
+                            match service.ingest(&message, &ctx).await {
+                                // A reply to a parked card waits for its wake
+                                Ok(ingested) if ingested.breaks_in() => deliver_breakin(&_state, ingested.card).await,
+                                Ok(_) => {}
+                                Err(e) => tracing::warn!("slack break-in for {} failed: {}", message.id, e),
                             }
-                        });
-                        let _ = _state.sse_tx.send(SseEvent{ event: "breakin.arrive".into(), data: breakin.to_string() });
+                        }
                     }
                 }
                 // If the text contains a UUID after "card:", emit a wake.fire
                 if let Some(text) = event.get("text").and_then(|v| v.as_str()) {
-                    if let Some(id) = extract_uuid_from_text(text) {
-                        let _ = _state.sse_tx.send(SseEvent{ event: "wake.fire".into(), data: serde_json::json!({"id": id}).to_string() });
+                    if let Some(id) = extract_uuid_from_text(text).and_then(|id| uuid::Uuid::parse_str(&id).ok()) {
+                        if let Err(e) = _state.parking_service.wake_card(id, "slack.mention").await {
+                            tracing::warn!("slack mention wake failed: {}", e);
+                        }
                     }
                 }
             }
@@ -123,6 +191,49 @@ fn extract_uuid_from_text(text: &str) -> Option<String> {
     re.find(text).map(|m| m.as_str().to_string())
 }
 
-fn ch_or<'a>(fallback: &'a str, ch: Option<&'a str>) -> &'a str {
-    ch.unwrap_or(fallback)
+// Bots and our own side of the conversation aren't interrupts
+async fn is_own_message(state: &AppState, event: &serde_json::Value, message: &SlackMessage) -> bool {
+    if event.get("bot_id").is_some() {
+        return true;
+    }
+    match &state.slack {
+        Some(slack) => slack.my_user_id().await.is_ok_and(|me| me == message.user),
+        None => false,
+    }
+}
+
+// The stored card in the shape the client's `Card` type reads: the same fields, with the
+// snake_case ones renamed
+fn client_card(card: &Card) -> serde_json::Value {
+    let mut data = json!(card);
+    if let Some(fields) = data.as_object_mut() {
+        for (from, to) in [("card_type", "cardType"), ("origin_object", "originObject"), ("created_at", "createdAt")] {
+            if let Some(value) = fields.remove(from) {
+                fields.insert(to.to_string(), value);
+            }
+        }
+    }
+    data
+}
+
+// Into the feed and out over SSE, unless a focus session holds it for the batch
+async fn deliver_breakin(state: &AppState, card: Card) {
+    let Some(triage) = card.metadata.as_ref().and_then(|m| m.triage.clone()) else { return };
+    let CardContent::BreakIn { sender, .. } = &card.content else { return };
+    tracing::info!("slack.breakin sender={} {}", sender, triage.rationale);
+    let breakin = json!({
+        "card": {
+            "id": card.id,
+            "type": CardState::from_status(&card.status).as_str(),
+            "kind": kind_label(&card.card_type),
+            "data": client_card(&card),
+        },
+        "recommendation": triage.recommendation,
+        "preempt": triage.preempt
+    });
+    // During a focus session, anything below its threshold waits for the batch
+    if state.focus_service.admit(&card).await == Admission::Deliver {
+        state.breakin_inbox.push(card).await;
+        let _ = state.sse_tx.send(SseEvent{ event: "breakin.arrive".into(), data: breakin.to_string() });
+    }
 }
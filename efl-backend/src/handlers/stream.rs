@@ -1,45 +1,87 @@
-use std::time::Duration;
-use axum::{Router, extract::State, response::sse::{Event, Sse}};
+use axum::{Router, extract::State, http::HeaderMap, response::sse::{Event, KeepAlive, Sse}};
 use futures::{Stream, StreamExt};
-use tokio_stream::wrappers::{IntervalStream, BroadcastStream};
+use tokio_stream::wrappers::BroadcastStream;
 
 use crate::{AppState, sse::SseEvent};
+use crate::models::{Altitude, CardState};
+use crate::services::altitude::DEFAULT_USER;
+use crate::services::feed::FeedService;
+use crate::sqlite::repo::cards::kind_label;
 
 pub fn routes() -> Router<AppState> {
     Router::new().route("/cards", axum::routing::get(stream_cards))
 }
 
-async fn stream_cards(State(state): State<AppState>) -> Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>> {
-    // Subscribe to broadcast channel
-    let rx = state.sse_tx.subscribe();
-    let bus = BroadcastStream::new(rx).filter_map(|msg| async move {
-        match msg {
-            Ok(SseEvent { event, data }) => {
-                let evt = Event::default().event(event).data(data);
-                Some(Ok(evt))
+// Cards sent in `queue.hydrate`; the rest are counted in `afterCount`
+const HYDRATE_SIZE: usize = 10;
+
+// Every event carries an id. A client reconnecting with Last-Event-ID gets just the
+// events it missed; one we can't resume (new client, restart, too far behind) gets a
+// fresh `queue.hydrate`, which replaces its queue rather than adding to it.
+async fn stream_cards(State(state): State<AppState>, headers: HeaderMap) -> Sse<impl Stream<Item = Result<Event, std::convert::Infallible>>> {
+    // Subscribe before reading history so nothing falls in between
+    let live = state.sse_journal.subscribe();
+    let last_event_id = headers.get("last-event-id").and_then(|v| v.to_str().ok());
+    let missed = match last_event_id {
+        Some(id) => state.sse_journal.since(id).await,
+        None => None,
+    };
+
+    let journal = state.sse_journal.clone();
+    let (initial, high_water): (Vec<Event>, u64) = match missed {
+        Some(events) => {
+            let high_water = events.last().map(|(seq, _)| *seq)
+                .or_else(|| last_event_id.and_then(|id| id.rsplit('-').next()?.parse().ok()))
+                .unwrap_or(0);
+            let replay = events.into_iter()
+                .map(|(seq, SseEvent { event, data })| Event::default().id(journal.event_id(seq)).event(event).data(data))
+                .collect();
+            (replay, high_water)
+        }
+        None => {
+            let head = journal.head().await;
+            let mut events = vec![hydrate_event(&state).await.id(journal.event_id(head))];
+            if let Some(altimeter) = state.altimeter.latest().await {
+                let data = serde_json::to_string(&altimeter).unwrap_or_default();
+                events.push(Event::default().id(journal.event_id(head)).event("altimeter.update").data(data));
             }
-            Err(_) => None,
+            (events, head)
         }
-    });
+    };
 
-    // Initial hydrate on connect
-    let initial = futures::stream::once(async move {
-        let payload = serde_json::json!({ "cards": [], "afterCount": 0 });
-        Ok(Event::default().event("queue.hydrate").data(payload.to_string()))
+    let bus = BroadcastStream::new(live).filter_map(move |msg| {
+        let journal = journal.clone();
+        async move {
+            match msg {
+                Ok((seq, SseEvent { event, data })) if seq > high_water => {
+                    Some(Ok(Event::default().id(journal.event_id(seq)).event(event).data(data)))
+                }
+                _ => None,
+            }
+        }
     });
+    let initial = futures::stream::iter(initial.into_iter().map(Ok));
 
-    // Heartbeat altimeter every 5s
-    let interval = tokio::time::interval(Duration::from_secs(5));
-    let heartbeat = IntervalStream::new(interval).map(move |_| {
-        let payload = serde_json::json!({
-            "kind": "altimeter.update",
-            "systemAltitude": "Do",
-            "progress": {"doCount":0,"shipGreen":0,"shipTotal":0,"amplifyDone":0,"amplifyTotal":0,"orientOk":true},
-            "rationale": null
-        });
-        Ok(Event::default().event("altimeter.update").data(payload.to_string()))
-    });
+    // Keep-alive comments hold the connection open; altimeter.update arrives on the bus when it changes
+    let stream = initial.chain(bus);
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
 
-    let stream = initial.chain(bus).chain(heartbeat);
-    Sse::new(stream)
+// The head of the ranked queue, in the shape the client store hydrates from
+async fn hydrate_event(state: &AppState) -> Event {
+    let service = FeedService::new_with_sqlite(
+        state.db_pool.clone(),
+        state.sqlite_db.as_ref().map(|db| db.pool.clone()),
+    ).with_sources(state.parking_service.clone(), state.breakin_inbox.clone());
+    let altitude = state.altitude_service.current(DEFAULT_USER).await.unwrap_or(Altitude::Do);
+    let page = service.page(altitude, None, HYDRATE_SIZE).await;
+    let cards: Vec<serde_json::Value> = page.cards.iter().map(|card| serde_json::json!({
+        "id": card.id,
+        // The client store's own state names
+        "type": CardState::from_status(&card.status).as_str(),
+        "kind": kind_label(&card.card_type),
+        "data": card,
+    })).collect();
+    let payload = serde_json::json!({ "kind": "queue.hydrate", "cards": cards, "afterCount": page.after_count });
+    Event::default().event("queue.hydrate").data(payload.to_string())
 }
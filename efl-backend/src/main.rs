@@ -25,8 +25,18 @@ pub struct AppState {
     pub sqlite_db: Option<sqlite::db::SqliteDb>,
     pub memory_cache: memory::MemoryCache,
     pub parking_service: services::parking::ParkingService,
+    pub undo_service: services::undo::UndoService,
+    pub breakin_inbox: services::breakin_inbox::BreakInInbox,
+    pub altitude_service: services::altitude::AltitudeService,
+    pub altimeter: services::altimeter::AltimeterMonitor,
+    pub focus_service: services::focus::FocusService,
+    pub gmail_actions: services::gmail_actions::GmailActionService,
+    pub gmail_sync: services::gmail_sync::GmailSyncWorker,
+    // None without SLACK_USER_TOKEN / SLACK_BOT_TOKEN
+    pub slack: Option<connectors::slack::SlackClient>,
     pub telemetry_service: services::telemetry::TelemetryService,
     pub sse_tx: broadcast::Sender<crate::sse::SseEvent>,
+    pub sse_journal: sse::SseJournal,
 }
 
 #[tokio::main]
@@ -75,10 +85,57 @@ async fn main() -> anyhow::Result<()> {
         }
     };
     
+    // Admin command: `efl_backend rebuild-cards` replays queue_events into the cards table
+    if std::env::args().nth(1).as_deref() == Some("rebuild-cards") {
+        let sqlite = sqlite_db.ok_or_else(|| anyhow::anyhow!("rebuild-cards requires SQLite"))?;
+        let summary = services::card_projector::CardProjector::new(sqlite.pool).rebuild_all().await?;
+        tracing::info!(
+            "Rebuilt cards from event log: {} rebuilt, {} deleted, {} skipped",
+            summary.rebuilt, summary.deleted, summary.skipped.len()
+        );
+        for card_id in &summary.skipped {
+            tracing::warn!("No snapshot in history for card {}", card_id);
+        }
+        return Ok(());
+    }
+    
     let memory_cache = memory::MemoryCache::new();
-    let parking_service = services::parking::ParkingService::new();
-    let telemetry_service = services::telemetry::TelemetryService::new();
     let (sse_tx, _sse_rx) = broadcast::channel(100);
+    let sse_journal = sse::SseJournal::spawn(&sse_tx);
+    let parking_service = services::parking::ParkingService::new_with_sqlite(
+        sqlite_db.as_ref().map(|db| db.pool.clone()),
+        sse_tx.clone(),
+    );
+    let telemetry_service = services::telemetry::TelemetryService::new();
+    let undo_service = services::undo::UndoService::new(memory_cache.clone(), sse_tx.clone());
+    let altitude_service = services::altitude::AltitudeService::new(sqlite_db.as_ref().map(|db| db.pool.clone()));
+    let breakin_inbox = services::breakin_inbox::BreakInInbox::new();
+    let altimeter = services::altimeter::AltimeterMonitor::spawn(
+        services::altimeter::CardSources {
+            db_pool: db_pool.clone(),
+            sqlite_pool: sqlite_db.as_ref().map(|db| db.pool.clone()),
+            parking: parking_service.clone(),
+            breakins: breakin_inbox.clone(),
+        },
+        services::altitude_policy::AltitudePolicy::from_env(),
+        sse_tx.clone(),
+    );
+    
+    let focus_service = services::focus::FocusService::new(breakin_inbox.clone(), telemetry_service.clone(), sse_tx.clone());
+    let gmail_actions = services::gmail_actions::GmailActionService::new(sqlite_db.as_ref().map(|db| db.pool.clone()));
+    let gmail_sync = services::gmail_sync::GmailSyncWorker::spawn(
+        sqlite_db.as_ref().map(|db| db.pool.clone()),
+        parking_service.clone(),
+        sse_tx.clone(),
+    );
+    services::imap_sync::ImapSyncWorker::spawn(
+        sqlite_db.as_ref().map(|db| db.pool.clone()),
+        parking_service.clone(),
+        sse_tx.clone(),
+    );
+    
+    let slack = connectors::slack::SlackClient::from_env()
+        .map(|client| client.with_user_cache(sqlite_db.as_ref().map(|db| db.pool.clone())));
     
     // Generate demo telemetry
     let telemetry_clone = telemetry_service.clone();
@@ -91,8 +148,17 @@ async fn main() -> anyhow::Result<()> {
         sqlite_db,
         memory_cache,
         parking_service,
+        undo_service,
+        breakin_inbox,
+        altitude_service,
+        altimeter,
+        focus_service,
+        gmail_actions,
+        gmail_sync,
+        slack,
         telemetry_service,
         sse_tx,
+        sse_journal,
     };
     
     let app = create_router(app_state);
@@ -126,6 +192,7 @@ fn api_routes() -> Router<AppState> {
         .nest("/memory", handlers::memory::routes())
         .nest("/trace", handlers::trace::routes())
         .nest("/telemetry", handlers::telemetry::routes())
+        .nest("/focus", handlers::focus::routes())
         .nest("/llm", handlers::llm::routes())
         .nest("/health", handlers::health_db::routes())
         .route("/health", axum::routing::get(health_check))
@@ -49,6 +49,11 @@ impl MemoryCache {
         self.summaries.insert(key, summary).await;
     }
     
+    // Everything currently cached; triage checks these to see if an interrupt can be answered from memory
+    pub fn summaries(&self) -> Vec<Summary> {
+        self.summaries.iter().map(|(_, summary)| summary).collect()
+    }
+    
     pub async fn get_parked_item(&self, id: &Uuid) -> Option<ParkedItem> {
         self.parked_items.get(id).await
     }
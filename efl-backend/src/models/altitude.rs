@@ -45,7 +45,7 @@ pub struct AltimeterEvent {
     pub rationale: Option<String>, // "DoD 3/3 green; suggest Ship"
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AltimeterProgress {
     pub do_count: u8,
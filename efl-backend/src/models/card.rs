@@ -41,6 +41,68 @@ pub enum CardStatus {
     Cancelled,
 }
 
+// Lifecycle state from the card FSM (milestone A.1 §2.1); persisted in `cards.state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CardState {
+    Idle,
+    Active,
+    Preview,
+    Committed,
+    Parked,
+    Dismissed,
+}
+
+impl CardState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CardState::Idle => "Idle",
+            CardState::Active => "Active",
+            CardState::Preview => "Preview",
+            CardState::Committed => "Committed",
+            CardState::Parked => "Parked",
+            CardState::Dismissed => "Dismissed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Idle" => Some(CardState::Idle),
+            "Active" => Some(CardState::Active),
+            "Preview" => Some(CardState::Preview),
+            "Committed" => Some(CardState::Committed),
+            "Parked" => Some(CardState::Parked),
+            "Dismissed" => Some(CardState::Dismissed),
+            // Rows written before the FSM stored the flat CardStatus
+            "Pending" => Some(CardState::Idle),
+            "Completed" => Some(CardState::Committed),
+            "Cancelled" => Some(CardState::Dismissed),
+            _ => None,
+        }
+    }
+
+    pub fn from_status(status: &CardStatus) -> Self {
+        match status {
+            CardStatus::Active => CardState::Active,
+            CardStatus::Pending => CardState::Idle,
+            CardStatus::Completed => CardState::Committed,
+            CardStatus::Parked => CardState::Parked,
+            CardStatus::Cancelled => CardState::Dismissed,
+        }
+    }
+
+    // Coarse status mirrored into the card payload for clients that only read `status`
+    pub fn status(&self) -> CardStatus {
+        match self {
+            CardState::Idle => CardStatus::Pending,
+            CardState::Active | CardState::Preview => CardStatus::Active,
+            CardState::Committed => CardStatus::Completed,
+            CardState::Parked => CardStatus::Parked,
+            CardState::Dismissed => CardStatus::Cancelled,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum CardContent {
@@ -160,7 +222,7 @@ pub struct OriginObject {
     pub block_id: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum CardAction {
     Commit,
@@ -178,6 +240,10 @@ pub enum CardAction {
     ArchiveAll,
     UnsubscribeAll,
     BlockSender,
+    Dismiss,
+    Redo,
+    SendDraft,
+    DiscardDraft,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -188,7 +254,7 @@ pub enum BreakInUrgency {
     Low,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CardMetadata {
     pub email_sender: Option<String>,
@@ -196,4 +262,138 @@ pub struct CardMetadata {
     pub email_date: Option<String>,
     pub reply_templates: Option<Vec<String>>,
     pub email_category: Option<String>,
+    // Set by the feed ranker; backs the "why this, why now" frame
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub score: Option<ScoreBreakdown>,
+    // Set when the card arrived as an interrupt; decides whether it preempts the queue
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub triage: Option<Triage>,
+    // Gmail draft written for this card, attached when the card is built
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub draft: Option<CardDraft>,
+    // The Gmail thread a card stands for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email_thread: Option<EmailThread>,
+}
+
+// The unread messages of one Gmail thread, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailThread {
+    pub thread_id: String,
+    pub messages: Vec<EmailThreadMessage>,
+    // Display names in order of first appearance
+    pub participants: Vec<String>,
+    pub summary: String,
+    // The most recent question or request in the thread
+    pub latest_ask: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailThreadMessage {
+    pub id: String,
+    // Raw From header
+    pub from: String,
+    pub sender: String,
+    pub date: String,
+    pub snippet: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftKind {
+    Reply,
+    Decline,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftState {
+    Drafted,
+    Sent,
+    Discarded,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CardDraft {
+    pub draft_id: String,
+    pub kind: DraftKind,
+    pub state: DraftState,
+    // The Gmail message being answered and its thread
+    pub message_id: String,
+    pub thread_id: String,
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreBreakdown {
+    pub total: f64,
+    pub signals: Vec<SignalScore>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignalScore {
+    pub signal: String,
+    // Normalized to 0..=1 before weighting
+    pub value: f64,
+    pub weight: f64,
+    pub contribution: f64,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriageRecommendation {
+    Now,
+    AtBreak,
+    Park,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TriageFactor {
+    pub value: f64,
+    pub reason: String,
+}
+
+// Micro-triage of an interrupt: score = urgency × impact × readiness, compared to τ
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Triage {
+    pub urgency: TriageFactor,
+    pub impact: TriageFactor,
+    pub readiness: TriageFactor,
+    pub score: f64,
+    pub threshold: f64,
+    pub recommendation: TriageRecommendation,
+    // Only true at or above the threshold; the client enters `Interrupted` on this
+    pub preempt: bool,
+    pub rationale: String,
+}
+
+// Partial update for a persisted card; only the fields that are set get applied.
+// State changes go through the card FSM instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CardPatch {
+    pub title: Option<String>,
+    pub altitude: Option<Altitude>,
+    pub content: Option<CardContent>,
+    pub actions: Option<Vec<CardAction>>,
+    pub metadata: Option<CardMetadata>,
+}
+
+impl CardPatch {
+    pub fn apply(&self, card: &mut Card) {
+        if let Some(title) = &self.title { card.title = title.clone(); }
+        if let Some(altitude) = self.altitude { card.altitude = altitude; }
+        if let Some(content) = &self.content { card.content = content.clone(); }
+        if let Some(actions) = &self.actions { card.actions = actions.clone(); }
+        if let Some(metadata) = &self.metadata { card.metadata = Some(metadata.clone()); }
+    }
 }
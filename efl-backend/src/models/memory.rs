@@ -20,6 +20,9 @@ pub struct DocumentContext {
     pub content: String,
     pub focused_section: Option<String>,
     pub last_blocks: Vec<String>,
+    // Bumped on every server-side change (commit/undo/redo) to detect stale edits
+    #[serde(default)]
+    pub version: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3,39 +3,69 @@ use crate::models::{
     card::{Card, CardContent, CardType, ChipStatus},
 };
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::Duration;
+use chrono::Utc;
+use sqlx::{PgPool, SqlitePool};
+use tokio::sync::{broadcast, RwLock};
+use tokio::time::Instant;
+use crate::services::altitude_policy::{AltitudePolicy, Held};
+use crate::services::breakin_inbox::BreakInInbox;
+use crate::services::feed::FeedService;
+use crate::services::parking::ParkingService;
+use crate::sse::SseEvent;
+
+const ALTIMETER_EVENT: &str = "altimeter.update";
+// Card changes settle for this long before the altimeter recomputes
+const DEBOUNCE: Duration = Duration::from_secs(2);
+// Recompute anyway so changes that don't go through the bus (new mail) show up
+const REFRESH_EVERY: Duration = Duration::from_secs(30);
 
 pub struct AltimeterService {
     cards: Arc<RwLock<Vec<Card>>>,
+    policy: AltitudePolicy,
+    // The current recommendation, for hysteresis
+    held: RwLock<Option<Held>>,
 }
 
 impl AltimeterService {
     pub fn new(cards: Arc<RwLock<Vec<Card>>>) -> Self {
-        Self { cards }
+        Self { cards, policy: AltitudePolicy::default(), held: RwLock::new(None) }
+    }
+
+    pub fn with_policy(mut self, policy: AltitudePolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    pub fn policy(&self) -> &AltitudePolicy {
+        &self.policy
     }
 
     pub async fn calculate_progress(&self) -> AltimeterProgress {
-        let cards = self.cards.read().await;
+        Self::progress_of(&self.cards.read().await)
+    }
+
+    // Totals across all cards of each kind
+    pub fn progress_of(cards: &[Card]) -> AltimeterProgress {
         let mut progress = AltimeterProgress::new();
+        let count = |n: usize| u8::try_from(n).unwrap_or(u8::MAX);
 
         for card in cards.iter() {
             match card.card_type {
                 CardType::DoNow => {
-                    progress.do_count = (progress.do_count + 1).min(255);
+                    progress.do_count = progress.do_count.saturating_add(1);
                 }
                 CardType::Ship => {
                     if let CardContent::Ship { dod_chips, .. } = &card.content {
-                        progress.ship_total = dod_chips.len() as u8;
-                        progress.ship_green = dod_chips
-                            .iter()
-                            .filter(|chip| chip.status == ChipStatus::Green)
-                            .count() as u8;
+                        let green = dod_chips.iter().filter(|chip| chip.status == ChipStatus::Green).count();
+                        progress.ship_total = progress.ship_total.saturating_add(count(dod_chips.len()));
+                        progress.ship_green = progress.ship_green.saturating_add(count(green));
                     }
                 }
                 CardType::Amplify => {
                     if let CardContent::Amplify { suggestions, drafts } = &card.content {
-                        progress.amplify_total = suggestions.len() as u8;
-                        progress.amplify_done = drafts.len() as u8;
+                        progress.amplify_total = progress.amplify_total.saturating_add(count(suggestions.len()));
+                        progress.amplify_done = progress.amplify_done.saturating_add(count(drafts.len()));
                     }
                 }
                 CardType::Orient => {
@@ -43,7 +73,7 @@ impl AltimeterService {
                         let has_high_priority = next_tasks
                             .iter()
                             .any(|task| task.urgency_score > 0.7 || task.impact_score > 0.7);
-                        progress.orient_ok = !has_high_priority;
+                        progress.orient_ok &= !has_high_priority;
                     }
                 }
                 _ => {}
@@ -53,28 +83,15 @@ impl AltimeterService {
         progress
     }
 
+    // Applies the policy, holding the previous recommendation per its hysteresis
     pub async fn recommend_altitude(&self, progress: &AltimeterProgress) -> (Altitude, Option<String>) {
-        let mut rationale = None;
-        
-        // Calculate recommended altitude based on progress
-        let altitude = if progress.do_count >= 3 {
-            rationale = Some(format!("{} focused edits available", progress.do_count));
-            Altitude::Do
-        } else if progress.ship_total > 0 && progress.ship_green == progress.ship_total {
-            rationale = Some(format!("Ready to ship: {}/{} checks green", progress.ship_green, progress.ship_total));
-            Altitude::Ship
-        } else if progress.amplify_total > progress.amplify_done {
-            let pending = progress.amplify_total - progress.amplify_done;
-            rationale = Some(format!("{} audiences need updates", pending));
-            Altitude::Amplify
-        } else if !progress.orient_ok {
-            rationale = Some("Queue conflicts detected; review priorities".to_string());
-            Altitude::Orient
-        } else {
-            Altitude::Do // Default
-        };
-
-        (altitude, rationale)
+        let now = Utc::now();
+        let mut held = self.held.write().await;
+        let rec = self.policy.recommend(progress, *held, now);
+        if held.is_none_or(|h| h.altitude != rec.altitude) {
+            *held = Some(Held { altitude: rec.altitude, since: now });
+        }
+        (rec.altitude, Some(rec.rationale))
     }
 
     pub async fn create_altimeter_event(&self) -> AltimeterEvent {
@@ -82,10 +99,139 @@ impl AltimeterService {
         let (altitude, rationale) = self.recommend_altitude(&progress).await;
 
         AltimeterEvent {
-            kind: "altimeter.update".to_string(),
+            kind: ALTIMETER_EVENT.to_string(),
             system_altitude: format!("{:?}", altitude),
             progress,
             rationale,
         }
     }
 }
+
+// Where the monitor gets the active cards from
+#[derive(Clone)]
+pub struct CardSources {
+    pub db_pool: Option<PgPool>,
+    pub sqlite_pool: Option<SqlitePool>,
+    pub parking: ParkingService,
+    pub breakins: BreakInInbox,
+}
+
+// Keeps the altimeter in step with the active cards and pushes `altimeter.update`
+// when the progress or the recommended altitude changes.
+#[derive(Clone)]
+pub struct AltimeterMonitor {
+    service: Arc<AltimeterService>,
+    cards: Arc<RwLock<Vec<Card>>>,
+    last: Arc<RwLock<Option<AltimeterEvent>>>,
+    sse_tx: broadcast::Sender<SseEvent>,
+}
+
+impl AltimeterMonitor {
+    pub fn new(policy: AltitudePolicy, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        let cards = Arc::new(RwLock::new(Vec::new()));
+        Self {
+            service: Arc::new(AltimeterService::new(cards.clone()).with_policy(policy)),
+            cards,
+            last: Arc::new(RwLock::new(None)),
+            sse_tx,
+        }
+    }
+
+    pub fn spawn(sources: CardSources, policy: AltitudePolicy, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        let monitor = Self::new(policy, sse_tx);
+        let runner = monitor.clone();
+        tokio::spawn(async move { runner.run(sources).await });
+        monitor
+    }
+
+    pub fn policy(&self) -> &AltitudePolicy {
+        self.service.policy()
+    }
+
+    // The last event pushed, for clients that just connected
+    pub async fn latest(&self) -> Option<AltimeterEvent> {
+        self.last.read().await.clone()
+    }
+
+    async fn run(&self, sources: CardSources) {
+        let mut rx = self.sse_tx.subscribe();
+        let mut ticker = tokio::time::interval(REFRESH_EVERY);
+        let mut pending: Option<Instant> = None;
+        loop {
+            let settle = async {
+                match pending {
+                    Some(at) => tokio::time::sleep_until(at).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = settle => {}
+                msg = rx.recv() => {
+                    match msg {
+                        Ok(event) if event.event != ALTIMETER_EVENT => {
+                            pending.get_or_insert_with(|| Instant::now() + DEBOUNCE);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                        _ => {}
+                    }
+                    continue;
+                }
+            }
+            pending = None;
+            let cards = FeedService::new_with_sqlite(sources.db_pool.clone(), sources.sqlite_pool.clone())
+                .with_sources(sources.parking.clone(), sources.breakins.clone())
+                .active_cards()
+                .await;
+            self.update(cards).await;
+        }
+    }
+
+    // Recomputes from `cards`; returns the event if it was pushed
+    pub async fn update(&self, cards: Vec<Card>) -> Option<AltimeterEvent> {
+        *self.cards.write().await = cards;
+        let event = self.service.create_altimeter_event().await;
+        let mut last = self.last.write().await;
+        let unchanged = last.as_ref().is_some_and(|prev| {
+            prev.progress == event.progress && prev.system_altitude == event.system_altitude
+        });
+        if unchanged {
+            return None;
+        }
+        *last = Some(event.clone());
+        let data = serde_json::to_string(&event).unwrap_or_default();
+        let _ = self.sse_tx.send(SseEvent { event: ALTIMETER_EVENT.into(), data });
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::altitude_policy::Hysteresis;
+    use crate::services::mock_data::*;
+
+    #[tokio::test]
+    async fn test_pushes_only_on_change() {
+        let (tx, mut rx) = broadcast::channel(8);
+        let hysteresis = Hysteresis { margin: 0.0, min_dwell_secs: 0 };
+        let monitor = AltimeterMonitor::new(AltitudePolicy { hysteresis, ..Default::default() }, tx);
+        let do_cards: Vec<Card> = (0..3).map(|_| generate_mock_do_now_card()).collect();
+
+        let first = monitor.update(do_cards.clone()).await.unwrap();
+        assert_eq!(first.system_altitude, "Do");
+        assert!(first.rationale.unwrap().starts_with("Do 1.00/1.00 (do_count 3)"));
+        assert!(monitor.update(do_cards.clone()).await.is_none());
+
+        let second = monitor.update(vec![do_cards[0].clone(), generate_mock_amplify_card()]).await.unwrap();
+        assert_eq!(second.system_altitude, "Amplify");
+        // The rationale also names the signals that lost
+        let rationale = second.rationale.unwrap();
+        assert!(rationale.starts_with("Amplify 1.00/1.00 (amplify_pending 1)"), "{}", rationale);
+        assert!(rationale.contains("also Do 0.33/1.00 (do_count 1)"), "{}", rationale);
+
+        assert_eq!(rx.recv().await.unwrap().event, ALTIMETER_EVENT);
+        assert_eq!(rx.recv().await.unwrap().event, ALTIMETER_EVENT);
+        assert!(rx.try_recv().is_err());
+    }
+}
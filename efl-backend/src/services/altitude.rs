@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use anyhow::Result;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::SqlitePool;
+use tokio::sync::RwLock;
+use crate::models::{Altitude, AltitudeState, Card, CardContent, ChipStatus, Gate, GateType};
+use crate::services::park_time::WorkingHours;
+use crate::sqlite::repo::altitude::AltitudeRepo;
+
+// There is no login yet; requests without X-User-Id act as this user
+pub const DEFAULT_USER: &str = "default";
+
+// Each altitude is entered through one gate, whichever altitude the user comes from
+pub fn gate_type(to: Altitude) -> GateType {
+    match to {
+        Altitude::Ship => GateType::DoToShip,
+        Altitude::Amplify => GateType::ShipToAmplify,
+        Altitude::Orient => GateType::AmplifyToOrient,
+        Altitude::Do => GateType::OrientToDo,
+    }
+}
+
+// Whether the active cards say it's time to go through `gate`:
+// - DoToShip: there is something to ship, and either the Do queue is empty or a ship candidate is all green
+// - ShipToAmplify: no ship checks are red, and there is amplify work waiting
+// - AmplifyToOrient: every amplify suggestion has a draft
+// - OrientToDo: there is Do work to pick up
+pub fn conditions_met(gate: &GateType, cards: &[Card]) -> bool {
+    let do_count = cards.iter().filter(|c| matches!(c.content, CardContent::DoNow { .. })).count();
+    let ship_chips: Vec<Vec<&ChipStatus>> = cards.iter()
+        .filter_map(|c| match &c.content {
+            CardContent::Ship { dod_chips, .. } => Some(dod_chips.iter().map(|chip| &chip.status).collect()),
+            _ => None,
+        })
+        .collect();
+    let (suggestions, drafts) = cards.iter().fold((0, 0), |(s, d), c| match &c.content {
+        CardContent::Amplify { suggestions, drafts } => (s + suggestions.len(), d + drafts.len()),
+        _ => (s, d),
+    });
+
+    match gate {
+        GateType::DoToShip => {
+            let all_green = ship_chips.iter().any(|chips| chips.iter().all(|s| **s == ChipStatus::Green));
+            !ship_chips.is_empty() && (do_count == 0 || all_green)
+        }
+        GateType::ShipToAmplify => {
+            ship_chips.iter().flatten().all(|s| **s == ChipStatus::Green) && suggestions > 0
+        }
+        GateType::AmplifyToOrient => drafts >= suggestions,
+        GateType::OrientToDo => do_count > 0,
+    }
+}
+
+pub fn evaluate_gate(from: Altitude, to: Altitude, cards: &[Card], at: DateTime<Utc>) -> Gate {
+    let gate_type = gate_type(to);
+    Gate {
+        from_altitude: from,
+        to_altitude: to,
+        conditions_met: conditions_met(&gate_type, cards),
+        gate_type,
+        timestamp: at,
+    }
+}
+
+#[derive(Clone)]
+pub struct AltitudeService {
+    repo: Option<AltitudeRepo>,
+    // Without SQLite, state lives here for the life of the process
+    memory: Arc<RwLock<HashMap<String, AltitudeState>>>,
+    hours: WorkingHours,
+}
+
+impl AltitudeService {
+    pub fn new(sqlite_pool: Option<SqlitePool>) -> Self {
+        Self {
+            repo: sqlite_pool.map(AltitudeRepo::new),
+            memory: Arc::new(RwLock::new(HashMap::new())),
+            hours: WorkingHours::from_env(),
+        }
+    }
+
+    // Local midnight in the user's timezone; gates_passed covers today only
+    fn start_of_day(&self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let date = now.with_timezone(&self.hours.timezone).date_naive();
+        self.hours.timezone.from_local_datetime(&date.and_time(chrono::NaiveTime::MIN))
+            .earliest()
+            .map_or(now, |d| d.with_timezone(&Utc))
+    }
+
+    pub async fn state(&self, user_id: &str) -> Result<AltitudeState> {
+        let today = self.start_of_day(Utc::now());
+        if let Some(repo) = &self.repo {
+            let (current, previous) = repo.get(user_id).await?.unwrap_or((Altitude::Do, None));
+            return Ok(AltitudeState { current, previous, gates_passed: repo.gates_since(user_id, today).await? });
+        }
+        let memory = self.memory.read().await;
+        let mut state = memory.get(user_id).cloned().unwrap_or(AltitudeState {
+            current: Altitude::Do,
+            previous: None,
+            gates_passed: vec![],
+        });
+        state.gates_passed.retain(|g| g.timestamp >= today);
+        Ok(state)
+    }
+
+    pub async fn current(&self, user_id: &str) -> Result<Altitude> {
+        Ok(self.state(user_id).await?.current)
+    }
+
+    // Moves the user to `to`, evaluating the gate against the active cards. The user
+    // always gets to move; `conditions_met` records whether the cards agreed.
+    // Returns None when already at `to`.
+    pub async fn transition(&self, user_id: &str, to: Altitude, cards: &[Card]) -> Result<Option<Gate>> {
+        let from = self.current(user_id).await?;
+        if from == to {
+            return Ok(None);
+        }
+        let gate = evaluate_gate(from, to, cards, Utc::now());
+        if let Some(repo) = &self.repo {
+            repo.record_transition(user_id, &gate).await?;
+        } else {
+            let mut memory = self.memory.write().await;
+            let state = memory.entry(user_id.to_string()).or_insert(AltitudeState {
+                current: Altitude::Do,
+                previous: None,
+                gates_passed: vec![],
+            });
+            state.previous = Some(from);
+            state.current = to;
+            state.gates_passed.push(gate.clone());
+        }
+        tracing::info!("altitude {:?} -> {:?} for {} (conditions met: {})", from, to, user_id, gate.conditions_met);
+        Ok(Some(gate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock_data::*;
+    use crate::sqlite::db::SqliteDb;
+
+    #[test]
+    fn test_gate_conditions_follow_cards() {
+        let do_now = generate_mock_do_now_card();
+        // The mock ship card has red chips
+        let ship = generate_mock_ship_card();
+        let amplify = generate_mock_amplify_card();
+
+        assert!(!conditions_met(&GateType::DoToShip, &[do_now.clone(), ship.clone()]));
+        assert!(conditions_met(&GateType::DoToShip, std::slice::from_ref(&ship)));
+        assert!(!conditions_met(&GateType::ShipToAmplify, &[ship.clone(), amplify.clone()]));
+        assert!(conditions_met(&GateType::OrientToDo, std::slice::from_ref(&do_now)));
+        assert!(!conditions_met(&GateType::OrientToDo, &[ship]));
+    }
+
+    #[tokio::test]
+    async fn test_transitions_persist() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        let service = AltitudeService::new(Some(db.pool.clone()));
+        let cards = vec![generate_mock_ship_card()];
+
+        assert!(service.transition("ana", Altitude::Do, &cards).await.unwrap().is_none());
+        let gate = service.transition("ana", Altitude::Ship, &cards).await.unwrap().unwrap();
+        assert!(matches!(gate.gate_type, GateType::DoToShip));
+        assert!(gate.conditions_met);
+        service.transition("ana", Altitude::Amplify, &cards).await.unwrap();
+
+        // A fresh service over the same database sees the same state
+        let state = AltitudeService::new(Some(db.pool)).state("ana").await.unwrap();
+        assert_eq!(state.current, Altitude::Amplify);
+        assert_eq!(state.previous, Some(Altitude::Ship));
+        assert_eq!(state.gates_passed.len(), 2);
+        assert!(!state.gates_passed[1].conditions_met);
+        assert_eq!(service.current("someone-else").await.unwrap(), Altitude::Do);
+    }
+}
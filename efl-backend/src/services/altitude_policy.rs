@@ -0,0 +1,324 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use crate::models::{Altitude, AltimeterProgress};
+
+// Declarative altitude recommendation: each altitude scores the weighted sum of its
+// signals, the best score at or over its threshold wins, and hysteresis keeps the
+// recommendation from flapping. Loaded from the JSON file at ALTITUDE_POLICY.
+
+#[derive(thiserror::Error, Debug)]
+pub enum PolicyError {
+    #[error("failed to read policy {0}: {1}")]
+    Read(String, std::io::Error),
+    #[error("invalid policy JSON: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("invalid policy: {0}")]
+    Invalid(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Signal {
+    // Number of DoNow cards
+    DoCount,
+    // Share of DoD chips that are green, 0 with no Ship cards
+    ShipReady,
+    // Number of red DoD chips
+    ShipRed,
+    // Amplify suggestions without a draft
+    AmplifyPending,
+    // 1 when high-urgency or high-impact Orient tasks compete for the queue
+    OrientConflict,
+}
+
+impl Signal {
+    pub fn raw(&self, p: &AltimeterProgress) -> f64 {
+        match self {
+            Signal::DoCount => p.do_count as f64,
+            Signal::ShipReady if p.ship_total == 0 => 0.0,
+            Signal::ShipReady => p.ship_green as f64 / p.ship_total as f64,
+            Signal::ShipRed => p.ship_total.saturating_sub(p.ship_green) as f64,
+            Signal::AmplifyPending => p.amplify_total.saturating_sub(p.amplify_done) as f64,
+            Signal::OrientConflict => if p.orient_ok { 0.0 } else { 1.0 },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WeightedSignal {
+    pub signal: Signal,
+    #[serde(default = "one")]
+    pub weight: f64,
+    // Raw value that counts as a full 1.0; counts above it don't add more
+    #[serde(default = "one")]
+    pub saturate_at: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AltitudeRule {
+    pub altitude: Altitude,
+    pub signals: Vec<WeightedSignal>,
+    // Minimum score for this altitude to be recommended at all
+    pub threshold: f64,
+}
+
+// Fields left out of the policy file keep their defaults
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Hysteresis {
+    // A challenger must beat the held altitude's score by this much
+    pub margin: f64,
+    // Hold a recommendation at least this long before switching
+    pub min_dwell_secs: i64,
+}
+
+impl Default for Hysteresis {
+    fn default() -> Self {
+        Self { margin: 0.25, min_dwell_secs: 60 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AltitudePolicy {
+    // Earlier rules win ties
+    pub rules: Vec<AltitudeRule>,
+    #[serde(default)]
+    pub hysteresis: Hysteresis,
+    // Recommended when no rule reaches its threshold
+    #[serde(default = "default_altitude")]
+    pub fallback: Altitude,
+}
+
+fn one() -> f64 { 1.0 }
+fn default_altitude() -> Altitude { Altitude::Do }
+
+fn signal(signal: Signal, saturate_at: f64) -> WeightedSignal {
+    WeightedSignal { signal, weight: 1.0, saturate_at }
+}
+
+// Matches the original fixed chain: 3+ edits, all chips green, pending amplify, orient conflict
+impl Default for AltitudePolicy {
+    fn default() -> Self {
+        let rule = |altitude, signals, threshold| AltitudeRule { altitude, signals, threshold };
+        Self {
+            rules: vec![
+                rule(Altitude::Do, vec![signal(Signal::DoCount, 3.0)], 1.0),
+                rule(Altitude::Ship, vec![signal(Signal::ShipReady, 1.0)], 1.0),
+                rule(Altitude::Amplify, vec![signal(Signal::AmplifyPending, 1.0)], 1.0),
+                rule(Altitude::Orient, vec![signal(Signal::OrientConflict, 1.0)], 1.0),
+            ],
+            hysteresis: Hysteresis::default(),
+            fallback: Altitude::Do,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignalValue {
+    pub signal: Signal,
+    pub raw: f64,
+    pub value: f64,
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AltitudeScore {
+    pub altitude: Altitude,
+    pub score: f64,
+    pub threshold: f64,
+    pub signals: Vec<SignalValue>,
+}
+
+impl AltitudeScore {
+    pub fn qualifies(&self) -> bool {
+        self.score >= self.threshold
+    }
+
+    fn describe(&self) -> String {
+        let parts: Vec<String> = self.signals.iter()
+            .map(|s| format!("{} {}", signal_name(s.signal), (s.raw * 100.0).round() / 100.0))
+            .collect();
+        format!("{:?} {:.2}/{:.2} ({})", self.altitude, self.score, self.threshold, parts.join(", "))
+    }
+}
+
+fn signal_name(signal: Signal) -> &'static str {
+    match signal {
+        Signal::DoCount => "do_count",
+        Signal::ShipReady => "ship_ready",
+        Signal::ShipRed => "ship_red",
+        Signal::AmplifyPending => "amplify_pending",
+        Signal::OrientConflict => "orient_conflict",
+    }
+}
+
+// An altitude recommendation and the altitude that currently holds it
+#[derive(Debug, Clone, Copy)]
+pub struct Held {
+    pub altitude: Altitude,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Recommendation {
+    pub altitude: Altitude,
+    pub rationale: String,
+    // Whether hysteresis kept the previous altitude over a better-scoring one
+    pub held: bool,
+    pub scores: Vec<AltitudeScore>,
+}
+
+impl AltitudePolicy {
+    // ALTITUDE_POLICY names a JSON policy file; without it the default policy applies
+    pub fn from_env() -> Self {
+        let Ok(path) = std::env::var("ALTITUDE_POLICY") else { return Self::default() };
+        match Self::load(&path) {
+            Ok(policy) => policy,
+            Err(e) => {
+                tracing::warn!("Using default altitude policy: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn load(path: &str) -> Result<Self, PolicyError> {
+        let text = std::fs::read_to_string(path).map_err(|e| PolicyError::Read(path.to_string(), e))?;
+        let policy: Self = serde_json::from_str(&text)?;
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self) -> Result<(), PolicyError> {
+        let invalid = |msg: String| Err(PolicyError::Invalid(msg));
+        for (i, rule) in self.rules.iter().enumerate() {
+            if self.rules[..i].iter().any(|r| r.altitude == rule.altitude) {
+                return invalid(format!("{:?} has more than one rule", rule.altitude));
+            }
+            if !rule.threshold.is_finite() || rule.threshold < 0.0 {
+                return invalid(format!("{:?} threshold must be a non-negative number", rule.altitude));
+            }
+            for s in &rule.signals {
+                let valid = s.weight.is_finite() && s.saturate_at.is_finite() && s.saturate_at > 0.0;
+                if !valid {
+                    return invalid(format!("{:?} {} needs a finite weight and a positive saturate_at", rule.altitude, signal_name(s.signal)));
+                }
+            }
+        }
+        if self.hysteresis.margin < 0.0 || self.hysteresis.min_dwell_secs < 0 {
+            return invalid("hysteresis margin and min_dwell_secs can't be negative".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn score(&self, progress: &AltimeterProgress) -> Vec<AltitudeScore> {
+        self.rules.iter().map(|rule| {
+            let signals: Vec<SignalValue> = rule.signals.iter().map(|s| {
+                let raw = s.signal.raw(progress);
+                SignalValue { signal: s.signal, raw, value: (raw / s.saturate_at).min(1.0), weight: s.weight }
+            }).collect();
+            AltitudeScore {
+                altitude: rule.altitude,
+                score: signals.iter().map(|s| s.value * s.weight).sum(),
+                threshold: rule.threshold,
+                signals,
+            }
+        }).collect()
+    }
+
+    // Picks the best qualifying altitude, unless `held` should be kept: it was
+    // recommended less than min_dwell ago, or it still qualifies and the challenger
+    // doesn't beat it by the margin.
+    pub fn recommend(&self, progress: &AltimeterProgress, held: Option<Held>, now: DateTime<Utc>) -> Recommendation {
+        let scores = self.score(progress);
+        // max_by keeps the last of equal elements, so walk in reverse to let earlier rules win ties
+        let best = scores.iter().rev()
+            .filter(|s| s.qualifies())
+            .max_by(|a, b| a.score.total_cmp(&b.score));
+        let (candidate, mut rationale) = match best {
+            Some(s) => (s.altitude, s.describe()),
+            None => (self.fallback, format!("{:?}: no altitude reached its threshold", self.fallback)),
+        };
+
+        let mut altitude = candidate;
+        let mut was_held = false;
+        if let Some(held) = held.filter(|h| h.altitude != candidate) {
+            let held_score = scores.iter().find(|s| s.altitude == held.altitude);
+            let dwelling = (now - held.since).num_seconds() < self.hysteresis.min_dwell_secs;
+            let ahead = match (held_score, best) {
+                (Some(h), Some(b)) if h.qualifies() => b.score >= h.score + self.hysteresis.margin,
+                _ => true,
+            };
+            if dwelling || !ahead {
+                altitude = held.altitude;
+                was_held = true;
+                let why = if dwelling { "recommended too recently to switch" } else { "challenger isn't ahead by the margin" };
+                rationale = format!("{:?} held, {} ({})", held.altitude, why, rationale);
+            }
+        }
+
+        let others: Vec<String> = scores.iter()
+            .filter(|s| s.altitude != altitude && s.signals.iter().any(|v| v.raw > 0.0))
+            .map(|s| s.describe())
+            .collect();
+        if !others.is_empty() {
+            rationale = format!("{}; also {}", rationale, others.join("; "));
+        }
+        Recommendation { altitude, rationale, held: was_held, scores }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(do_count: u8, ship_green: u8, ship_total: u8) -> AltimeterProgress {
+        AltimeterProgress { do_count, ship_green, ship_total, ..AltimeterProgress::new() }
+    }
+
+    #[test]
+    fn test_default_policy_matches_fixed_chain() {
+        let policy = AltitudePolicy::default();
+        let now = Utc::now();
+        assert_eq!(policy.recommend(&progress(3, 0, 2), None, now).altitude, Altitude::Do);
+        assert_eq!(policy.recommend(&progress(1, 2, 2), None, now).altitude, Altitude::Ship);
+        // Ties go to the earlier rule
+        assert_eq!(policy.recommend(&progress(4, 2, 2), None, now).altitude, Altitude::Do);
+        let idle = policy.recommend(&progress(1, 1, 2), None, now);
+        assert_eq!(idle.altitude, Altitude::Do);
+        assert!(idle.rationale.contains("no altitude reached its threshold"));
+    }
+
+    #[test]
+    fn test_hysteresis_holds_then_switches() {
+        let json = r#"{
+            "rules": [
+                {"altitude": "do", "signals": [{"signal": "do_count", "saturate_at": 5}], "threshold": 0.4},
+                {"altitude": "ship", "signals": [{"signal": "ship_ready", "weight": 0.9}], "threshold": 0.5}
+            ],
+            "hysteresis": {"margin": 0.3, "min_dwell_secs": 60}
+        }"#;
+        let policy: AltitudePolicy = serde_json::from_str(json).unwrap();
+        policy.validate().unwrap();
+        let now = Utc::now();
+        let held = Held { altitude: Altitude::Do, since: now - chrono::Duration::minutes(5) };
+
+        // Ship scores 0.9 vs Do's 0.8: better, but not by the margin
+        let rec = policy.recommend(&progress(4, 3, 3), Some(held), now);
+        assert_eq!(rec.altitude, Altitude::Do);
+        assert!(rec.held);
+        // Do drops below its threshold, so Ship takes over
+        assert_eq!(policy.recommend(&progress(1, 3, 3), Some(held), now).altitude, Altitude::Ship);
+        // ...unless Do was only just recommended
+        let fresh = Held { since: now, ..held };
+        assert_eq!(policy.recommend(&progress(1, 3, 3), Some(fresh), now).altitude, Altitude::Do);
+
+        let bad: AltitudePolicy = serde_json::from_str(r#"{"rules": [
+            {"altitude": "do", "signals": [], "threshold": 1},
+            {"altitude": "do", "signals": [], "threshold": 1}
+        ]}"#).unwrap();
+        assert!(matches!(bad.validate(), Err(PolicyError::Invalid(_))));
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::models::Card;
+
+// Most recent break-ins kept for the feed
+const INBOX_CAPACITY: usize = 50;
+
+// Break-in cards from connectors (Slack DMs, alerts) waiting to be ranked into the feed
+#[derive(Clone, Default)]
+pub struct BreakInInbox {
+    cards: Arc<RwLock<VecDeque<Card>>>,
+}
+
+impl BreakInInbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn push(&self, card: Card) {
+        let mut cards = self.cards.write().await;
+        cards.retain(|c| c.id != card.id);
+        cards.push_front(card);
+        cards.truncate(INBOX_CAPACITY);
+    }
+
+    // Drops a card once it's been dealt with
+    pub async fn remove(&self, id: Uuid) {
+        self.cards.write().await.retain(|c| c.id != id);
+    }
+
+    pub async fn list(&self) -> Vec<Card> {
+        self.cards.read().await.iter().cloned().collect()
+    }
+}
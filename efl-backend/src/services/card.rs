@@ -1,19 +1,44 @@
-use sqlx::PgPool;
+use sqlx::{PgPool, SqlitePool};
 use uuid::Uuid;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use chrono::Utc;
 use crate::models::{
-    Card, CardType, CardContent, CardAction, CardStatus,
+    Card, CardType, CardContent, CardAction, CardState, CardStatus, CardPatch,
     Altitude, ParkedItem, WakeCondition
 };
+use crate::services::card_fsm::{CardEvent, CardFsm, FsmError};
+use crate::services::diff::{compute_diff, DiffGranularity};
+use crate::services::card_projector::{project, CardProjection, CardProjector};
+use crate::services::undo::{UndoError, UndoService};
+use crate::sqlite::repo::cards::{CardFilter, CardsRepo};
+use crate::sqlite::repo::queue::{QueueEvent, QueueRepo};
 
+#[derive(Clone)]
 pub struct CardService {
     db_pool: Option<PgPool>,
+    cards: Option<CardsRepo>,
+    fsm: Option<CardFsm>,
+    undo: Option<UndoService>,
 }
 
 impl CardService {
-    pub fn new(db_pool: Option<PgPool>) -> Self {
-        Self { db_pool }
+    pub fn new_with_sqlite(db_pool: Option<PgPool>, sqlite_pool: Option<SqlitePool>) -> Self {
+        Self {
+            db_pool,
+            cards: sqlite_pool.clone().map(CardsRepo::new),
+            fsm: sqlite_pool.map(CardFsm::new),
+            undo: None,
+        }
+    }
+
+    // Lets Commit/Undo/Redo edit the active document
+    pub fn with_undo(mut self, undo: UndoService) -> Self {
+        self.undo = Some(undo);
+        self
+    }
+
+    fn repo(&self) -> Result<&CardsRepo> {
+        self.cards.as_ref().ok_or_else(|| anyhow!("sqlite not configured"))
     }
     
     pub async fn create_card(
@@ -57,34 +82,8 @@ impl CardService {
         };
         
         // v0 persistence: save to SQLite if configured
-        let sqlite_url = std::env::var("SQLITE_URL").ok().or_else(|| {
-            let db = std::env::var("DATABASE_URL").unwrap_or_default();
-            if db.starts_with("sqlite://") { Some(db) } else { None }
-        });
-        if let Some(database_url) = sqlite_url {
-            if database_url.starts_with("sqlite://") {
-                if let Ok(sqlite) = crate::sqlite::db::SqliteDb::connect(&database_url).await {
-                    let repo = crate::sqlite::repo::cards::CardsRepo::new(sqlite.pool);
-                    let payload = serde_json::to_value(&card).unwrap_or(serde_json::json!({}));
-                    let kind = match card.card_type { 
-                        CardType::DoNow => "DoNow",
-                        CardType::Ship => "Ship",
-                        CardType::Amplify => "Amplify",
-                        CardType::Orient => "Orient",
-                        CardType::Parked => "Parked",
-                        CardType::BreakIn => "BreakIn",
-                        CardType::BatchReview => "BatchReview",
-                    };
-                    let state = match card.status {
-                        CardStatus::Active => "Active",
-                        CardStatus::Pending => "Pending",
-                        CardStatus::Completed => "Completed",
-                        CardStatus::Parked => "Parked",
-                        CardStatus::Cancelled => "Cancelled",
-                    };
-                    let _ = repo.upsert(&card.id.to_string(), kind, state, &payload).await;
-                }
-            }
+        if self.cards.is_some() {
+            self.save_card(&card, "user").await?;
         }
 
         // In a real implementation, also save to Postgres
@@ -92,8 +91,50 @@ impl CardService {
     }
     
     pub async fn get_card(&self, id: Uuid) -> Result<Option<Card>> {
-        // In a real implementation, fetch from database
-        Ok(None)
+        let Some(repo) = &self.cards else { return Ok(None) };
+        Ok(repo.get_card(&id.to_string()).await?)
+    }
+
+    pub async fn list_cards(&self, filter: CardFilter) -> Result<Vec<Card>> {
+        let Some(repo) = &self.cards else { return Ok(vec![]) };
+        Ok(repo.list_cards(&filter).await?)
+    }
+
+    // Upserts the card and appends a snapshot to its history so it can be replayed
+    pub async fn save_card(&self, card: &Card, actor: &str) -> Result<()> {
+        let repo = self.repo()?;
+        let id = card.id.to_string();
+        let event = if repo.get(&id).await?.is_some() { "update" } else { "append" };
+        repo.save_card(card).await?;
+        QueueRepo::new(repo.pool.clone())
+            .append_event(&id, event, actor, Some(&serde_json::json!({ "card": card })))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_card(&self, id: Uuid, patch: CardPatch) -> Result<Option<Card>> {
+        let Some(mut card) = self.get_card(id).await? else { return Ok(None) };
+        patch.apply(&mut card);
+        self.save_card(&card, "user").await?;
+        Ok(Some(card))
+    }
+
+    pub async fn delete_card(&self, id: Uuid) -> Result<bool> {
+        let repo = self.repo()?;
+        let deleted = repo.soft_delete(&id.to_string()).await?;
+        if deleted {
+            QueueRepo::new(repo.pool.clone())
+                .append_event(&id.to_string(), "delete", "user", None)
+                .await?;
+        }
+        Ok(deleted)
+    }
+
+    pub async fn history(&self, id: Uuid) -> Result<(Vec<QueueEvent>, Option<CardProjection>)> {
+        let projector = CardProjector::new(self.repo()?.pool.clone());
+        let events = projector.history(&id.to_string()).await?;
+        let projection = project(&events);
+        Ok((events, projection))
     }
     
     pub async fn perform_action(
@@ -102,23 +143,101 @@ impl CardService {
         action: CardAction,
         payload: Option<serde_json::Value>,
     ) -> Result<serde_json::Value> {
-        match action {
+        let fsm = self.fsm.as_ref().ok_or(FsmError::Unavailable)?;
+        let event = CardEvent::from_action(&action);
+        let granularity: DiffGranularity = payload.as_ref()
+            .and_then(|p| p.get("granularity"))
+            .and_then(|g| serde_json::from_value(g.clone()).ok())
+            .unwrap_or_default();
+
+        // Document changes are made inside the transition's transaction: a rejected diff
+        // leaves the card where it was, and a failed transition reverts the document
+        let mut change = None;
+        let transition = match event {
+            Some(event) => {
+                let documented = match (&self.undo, event) {
+                    (Some(_), CardEvent::Undo) => self.committed_document(card_id).await?,
+                    _ => false,
+                };
+                let mut pending = fsm.begin(card_id, event).await?;
+                if let Some(undo) = &self.undo {
+                    change = match event {
+                        CardEvent::Commit => undo.record_commit(&pending.card).await?,
+                        CardEvent::Undo if pending.from == CardState::Committed => match undo.undo(card_id).await? {
+                            // The commit changed a document but its undo history is gone (e.g. a restart)
+                            None if documented => return Err(UndoError::HistoryLost(card_id).into()),
+                            undone => undone,
+                        },
+                        CardEvent::Redo => Some(undo.redo(card_id).await?),
+                        _ => None,
+                    };
+                }
+                pending.document = change.as_ref().map(|c| serde_json::json!({ "doc_id": c.doc_id, "version": c.version_after }));
+                match pending.commit("user", payload).await {
+                    Ok(t) => Some(t),
+                    Err(e) => {
+                        if let (Some(undo), Some(_)) = (&self.undo, &change) {
+                            let reverted = match event {
+                                CardEvent::Undo => undo.redo(card_id).await.map(|_| ()),
+                                _ => undo.undo(card_id).await.map(|_| ()),
+                            };
+                            if let Err(revert) = reverted {
+                                tracing::warn!("card {}: document not reverted after failed {}: {}", card_id, event.as_str(), revert);
+                            }
+                        }
+                        return Err(e.into());
+                    }
+                }
+            }
+            None => {
+                fsm.check_action(card_id, &action).await?;
+                None
+            }
+        };
+
+        let mut result = match action {
             CardAction::Commit => {
                 // Process commit action
-                Ok(serde_json::json!({ "status": "committed" }))
+                serde_json::json!({ "status": "committed" })
             },
             CardAction::Undo => {
                 // Process undo action
-                Ok(serde_json::json!({ "status": "undone" }))
+                serde_json::json!({ "status": "undone" })
             },
+            CardAction::Redo => serde_json::json!({ "status": "redone" }),
             CardAction::ShowDiff => {
-                // Generate and return diff
-                Ok(serde_json::json!({ "diff": "..." }))
+                // Recompute operations from the card's before/after text
+                let diff = transition.as_ref().and_then(|t| match &t.card.content {
+                    CardContent::DoNow { diff: Some(d), .. } => Some(compute_diff(&d.before, &d.after, granularity)),
+                    _ => None,
+                });
+                serde_json::json!({ "diff": diff })
             },
-            _ => Ok(serde_json::json!({ "status": "processed" }))
+            _ => serde_json::json!({ "status": "processed" })
+        };
+        if let Some(t) = transition {
+            result["state"] = serde_json::json!(t.to);
+            result["previous_state"] = serde_json::json!(t.from);
+            result["event_id"] = serde_json::json!(t.event_id);
+            result["card"] = serde_json::json!(t.card);
+            if let (Some(undo), CardAction::Undo | CardAction::Redo) = (&self.undo, &action) {
+                undo.publish(card_id, t.to, change.as_ref());
+            }
         }
+        if let Some(c) = &change {
+            result["document"] = serde_json::json!({ "doc_id": c.doc_id, "version": c.version_after });
+        }
+        Ok(result)
     }
     
+    // Whether the card's last commit or redo changed a document
+    async fn committed_document(&self, card_id: Uuid) -> Result<bool> {
+        let events = QueueRepo::new(self.repo()?.pool.clone()).list_for_card(&card_id.to_string()).await?;
+        Ok(events.iter().rev()
+            .find(|e| e.event == CardEvent::Commit.as_str() || e.event == CardEvent::Redo.as_str())
+            .is_some_and(|e| e.meta.as_ref().is_some_and(|m| m.get("document").is_some())))
+    }
+
     pub async fn park_card(
         &self,
         card_id: Uuid,
@@ -139,3 +258,38 @@ impl CardService {
         Ok(parked_item)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryCache;
+    use crate::services::memory::MemoryService;
+    use crate::sqlite::db::SqliteDb;
+
+    #[tokio::test]
+    async fn test_undo_without_history_is_refused() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        let cache = MemoryCache::new();
+        let (sse_tx, _) = tokio::sync::broadcast::channel(8);
+        let card = crate::services::mock_data::generate_mock_do_now_card();
+        let CardContent::DoNow { diff: Some(diff), .. } = &card.content else { unreachable!() };
+        MemoryService::new(None, cache.clone())
+            .update_working_set(Some("doc-1".into()), Some(format!("{} More text.", diff.before)), None)
+            .await
+            .unwrap();
+        let service = |undo| CardService::new_with_sqlite(None, Some(db.pool.clone())).with_undo(undo);
+        let before_restart = service(UndoService::new(cache.clone(), sse_tx.clone()));
+        before_restart.save_card(&card, "user").await.unwrap();
+        before_restart.perform_action(card.id, CardAction::Commit, None).await.unwrap();
+
+        // A fresh UndoService has lost the commit, so Undo must not report success
+        let after_restart = service(UndoService::new(cache.clone(), sse_tx));
+        let err = after_restart.perform_action(card.id, CardAction::Undo, None).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<UndoError>(), Some(UndoError::HistoryLost(_))));
+        let state = CardsRepo::new(db.pool.clone()).get_state(&card.id.to_string()).await.unwrap();
+        assert_eq!(state, Some(CardState::Committed));
+
+        let undone = before_restart.perform_action(card.id, CardAction::Undo, None).await.unwrap();
+        assert_eq!(undone["state"], "active");
+    }
+}
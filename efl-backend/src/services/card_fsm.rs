@@ -0,0 +1,238 @@
+use serde_json::Value;
+use sqlx::{Sqlite, SqlitePool, Transaction};
+use uuid::Uuid;
+use crate::models::{Card, CardAction, CardState, CardType};
+use crate::sqlite::repo::cards::{decode_card, fetch, kind_label, update_if_state, CardsRepo};
+use crate::sqlite::repo::queue::insert_event;
+
+// Card FSM (milestone A.1 §2): Idle → Active → Preview → Committed/Parked/Dismissed
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardEvent {
+    Activate,
+    PreviewReady,
+    Commit,
+    Undo,
+    Redo,
+    Park,
+    Wake,
+    Dismiss,
+}
+
+impl CardEvent {
+    // Actions that move a card through its lifecycle; the rest don't change state
+    pub fn from_action(action: &CardAction) -> Option<Self> {
+        match action {
+            CardAction::Open => Some(CardEvent::Activate),
+            CardAction::ShowDiff => Some(CardEvent::PreviewReady),
+            CardAction::Commit => Some(CardEvent::Commit),
+            CardAction::Undo => Some(CardEvent::Undo),
+            CardAction::Redo => Some(CardEvent::Redo),
+            CardAction::Park => Some(CardEvent::Park),
+            CardAction::Resume => Some(CardEvent::Wake),
+            CardAction::Dismiss => Some(CardEvent::Dismiss),
+            _ => None,
+        }
+    }
+
+    // Name recorded in `queue_events.event`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CardEvent::Activate => "activate",
+            CardEvent::PreviewReady => "preview",
+            CardEvent::Commit => "commit",
+            CardEvent::Undo => "undo",
+            CardEvent::Redo => "redo",
+            CardEvent::Park => "park",
+            CardEvent::Wake => "wake",
+            CardEvent::Dismiss => "dismiss",
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FsmError {
+    #[error("card {0} not found")]
+    NotFound(Uuid),
+    #[error("cannot {action} a {kind} card in state {}", .from.as_str())]
+    IllegalTransition { from: CardState, action: String, kind: String },
+    #[error("card {0} changed while the action was being applied")]
+    Conflict(Uuid),
+    #[error("sqlite not configured")]
+    Unavailable,
+    #[error("db {0}")]
+    Db(#[from] sqlx::Error),
+}
+
+// Returns the state `event` leads to from `from`, or None if the transition is illegal
+pub fn next_state(kind: &CardType, from: CardState, event: CardEvent) -> Option<CardState> {
+    use CardState::*;
+    let previewable = matches!(kind, CardType::DoNow | CardType::Ship | CardType::Amplify);
+    let committable = matches!(kind, CardType::DoNow | CardType::Ship);
+    match (event, from) {
+        (CardEvent::Activate, Idle | Active) => Some(Active),
+        (CardEvent::PreviewReady, Active | Preview) if previewable => Some(Preview),
+        (CardEvent::Commit, Active | Preview) if committable => Some(Committed),
+        (CardEvent::Undo, Preview | Committed) => Some(Active),
+        (CardEvent::Redo, Active) if committable => Some(Committed),
+        (CardEvent::Park, Idle | Active | Preview) => Some(Parked),
+        (CardEvent::Wake, Parked) => Some(Active),
+        (CardEvent::Dismiss, Idle | Active | Preview | Parked) => Some(Dismissed),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub card: Card,
+    pub from: CardState,
+    pub to: CardState,
+    pub event_id: i64,
+}
+
+pub struct PendingTransition {
+    tx: Transaction<'static, Sqlite>,
+    pub card: Card,
+    pub from: CardState,
+    pub to: CardState,
+    event: CardEvent,
+    // Raw `cards.state` the transition was validated against
+    stored: String,
+    // The document change made with this transition, kept in the queue event
+    pub document: Option<Value>,
+}
+
+impl PendingTransition {
+    pub async fn commit(mut self, actor: &str, meta: Option<Value>) -> Result<Transition, FsmError> {
+        let id = self.card.id;
+        let key = id.to_string();
+        self.card.status = self.to.status();
+        if !update_if_state(&mut *self.tx, &self.card, &self.stored, self.to).await? {
+            return Err(FsmError::Conflict(id));
+        }
+        let mut event_meta = serde_json::json!({ "from": self.from.as_str(), "to": self.to.as_str() });
+        if let Some(extra) = meta {
+            event_meta["payload"] = extra;
+        }
+        if let Some(document) = self.document {
+            event_meta["document"] = document;
+        }
+        let event_id = insert_event(&mut *self.tx, &key, self.event.as_str(), actor, Some(&event_meta)).await?;
+        self.tx.commit().await?;
+
+        Ok(Transition { card: self.card, from: self.from, to: self.to, event_id })
+    }
+}
+
+#[derive(Clone)]
+pub struct CardFsm {
+    cards: CardsRepo,
+}
+
+impl CardFsm {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { cards: CardsRepo::new(pool) }
+    }
+
+    async fn load(&self, id: Uuid) -> Result<(Card, CardState), FsmError> {
+        let key = id.to_string();
+        let card = self.cards.get_card(&key).await?.ok_or(FsmError::NotFound(id))?;
+        let state = self.cards.get_state(&key).await?
+            .unwrap_or_else(|| CardState::from_status(&card.status));
+        Ok((card, state))
+    }
+
+    // Validates and applies `event` in one transaction: the state only changes if it's still
+    // the one validated against, and the queue event is written along with it
+    pub async fn apply(&self, id: Uuid, event: CardEvent, actor: &str, meta: Option<Value>) -> Result<Transition, FsmError> {
+        self.begin(id, event).await?.commit(actor, meta).await
+    }
+
+    // Validates `event` and holds the card's row until `commit`, so other work can land
+    // together with the transition; dropping the result rolls it back
+    pub async fn begin(&self, id: Uuid, event: CardEvent) -> Result<PendingTransition, FsmError> {
+        let mut tx = self.cards.pool.begin_with("BEGIN IMMEDIATE").await?;
+        let (_, stored, payload) = fetch(&mut *tx, &id.to_string()).await?.ok_or(FsmError::NotFound(id))?;
+        let card = decode_card(payload)?;
+        let from = CardState::parse(&stored).unwrap_or_else(|| CardState::from_status(&card.status));
+        let to = target(&card, from, event)?;
+        Ok(PendingTransition { tx, card, from, to, event, stored, document: None })
+    }
+
+    // Actions outside the lifecycle are allowed on any card that hasn't been dismissed
+    pub async fn check_action(&self, id: Uuid, action: &CardAction) -> Result<(Card, CardState), FsmError> {
+        let (card, state) = self.load(id).await?;
+        if state == CardState::Dismissed {
+            return Err(FsmError::IllegalTransition {
+                from: state,
+                action: serde_json::to_value(action).ok()
+                    .and_then(|v| v.as_str().map(str::to_string))
+                    .unwrap_or_default(),
+                kind: kind_label(&card.card_type).to_string(),
+            });
+        }
+        Ok((card, state))
+    }
+}
+
+fn target(card: &Card, from: CardState, event: CardEvent) -> Result<CardState, FsmError> {
+    next_state(&card.card_type, from, event).ok_or_else(|| FsmError::IllegalTransition {
+        from,
+        action: event.as_str().to_string(),
+        kind: kind_label(&card.card_type).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transitions() {
+        use CardState::*;
+        let do_now = CardType::DoNow;
+        assert_eq!(next_state(&do_now, Idle, CardEvent::Activate), Some(Active));
+        assert_eq!(next_state(&do_now, Active, CardEvent::PreviewReady), Some(Preview));
+        assert_eq!(next_state(&do_now, Preview, CardEvent::Commit), Some(Committed));
+        assert_eq!(next_state(&do_now, Committed, CardEvent::Undo), Some(Active));
+        assert_eq!(next_state(&do_now, Active, CardEvent::Redo), Some(Committed));
+        assert_eq!(next_state(&do_now, Active, CardEvent::Park), Some(Parked));
+        assert_eq!(next_state(&do_now, Parked, CardEvent::Wake), Some(Active));
+        assert_eq!(next_state(&do_now, Parked, CardEvent::Dismiss), Some(Dismissed));
+
+        // Terminal and out-of-order events are rejected
+        assert_eq!(next_state(&do_now, Dismissed, CardEvent::Activate), None);
+        assert_eq!(next_state(&do_now, Committed, CardEvent::Commit), None);
+        assert_eq!(next_state(&do_now, Idle, CardEvent::Undo), None);
+        assert_eq!(next_state(&do_now, Active, CardEvent::Wake), None);
+
+        // Only DoNow/Ship commit, and Orient/BreakIn have no preview
+        assert_eq!(next_state(&CardType::Amplify, Preview, CardEvent::Commit), None);
+        assert_eq!(next_state(&CardType::Orient, Active, CardEvent::PreviewReady), None);
+        assert_eq!(next_state(&CardType::BreakIn, Active, CardEvent::Park), Some(Parked));
+    }
+
+    #[tokio::test]
+    async fn test_apply_persists_state_and_event() {
+        let db = crate::sqlite::db::SqliteDb::connect_in_memory().await.unwrap();
+        let fsm = CardFsm::new(db.pool.clone());
+        let card = crate::services::mock_data::generate_mock_do_now_card();
+        CardsRepo::new(db.pool.clone()).save_card(&card).await.unwrap();
+
+        let t = fsm.apply(card.id, CardEvent::Commit, "user", None).await.unwrap();
+        assert_eq!((t.from, t.to), (CardState::Active, CardState::Committed));
+
+        let err = fsm.apply(card.id, CardEvent::Commit, "user", None).await.unwrap_err();
+        assert!(matches!(err, FsmError::IllegalTransition { from: CardState::Committed, .. }));
+
+        // A write based on a state that has since moved on is refused
+        assert!(!update_if_state(&db.pool, &t.card, CardState::Active.as_str(), CardState::Committed).await.unwrap());
+
+        let events: i64 = sqlx::query_scalar("select count(*) from queue_events where card_id = ?1")
+            .bind(card.id.to_string())
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+        assert_eq!(events, 1);
+    }
+}
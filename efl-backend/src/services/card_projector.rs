@@ -0,0 +1,123 @@
+use anyhow::Result;
+use serde::Serialize;
+use sqlx::SqlitePool;
+use crate::models::{Card, CardState};
+use crate::sqlite::repo::cards::CardsRepo;
+use crate::sqlite::repo::queue::{QueueEvent, QueueRepo};
+
+// Rebuilds cards from `queue_events`. Snapshot events (append/update) carry the full card
+// under `meta.card`; FSM transitions carry `meta.to`; `delete` soft-deletes.
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CardProjection {
+    pub card: Card,
+    pub state: CardState,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RebuildSummary {
+    pub rebuilt: usize,
+    pub deleted: usize,
+    // Cards whose history has no snapshot to start from
+    pub skipped: Vec<String>,
+}
+
+// Folds a card's events in order; None until the first snapshot
+pub fn project(events: &[QueueEvent]) -> Option<CardProjection> {
+    let mut projection: Option<CardProjection> = None;
+    for ev in events {
+        let meta = ev.meta.as_ref();
+        if let Some(card) = meta.and_then(|m| m.get("card")).and_then(|c| serde_json::from_value::<Card>(c.clone()).ok()) {
+            let state = projection.as_ref()
+                .map(|p| p.state)
+                .unwrap_or_else(|| CardState::from_status(&card.status));
+            projection = Some(CardProjection { card, state, deleted: false });
+        }
+        let Some(p) = projection.as_mut() else { continue };
+        if let Some(to) = meta.and_then(|m| m.get("to")).and_then(|v| v.as_str()).and_then(CardState::parse) {
+            p.state = to;
+            p.card.status = to.status();
+        }
+        if ev.event == "delete" {
+            p.deleted = true;
+        }
+    }
+    projection
+}
+
+#[derive(Clone)]
+pub struct CardProjector {
+    cards: CardsRepo,
+    queue: QueueRepo,
+}
+
+impl CardProjector {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { cards: CardsRepo::new(pool.clone()), queue: QueueRepo::new(pool) }
+    }
+
+    pub async fn history(&self, card_id: &str) -> Result<Vec<QueueEvent>> {
+        Ok(self.queue.list_for_card(card_id).await?)
+    }
+
+    pub async fn replay(&self, card_id: &str) -> Result<Option<CardProjection>> {
+        Ok(project(&self.history(card_id).await?))
+    }
+
+    // Overwrites the stored card with its replayed state; returns None if there's nothing to replay
+    pub async fn rebuild_card(&self, card_id: &str) -> Result<Option<CardProjection>> {
+        let Some(projection) = self.replay(card_id).await? else { return Ok(None) };
+        self.cards.save_card(&projection.card).await?;
+        self.cards.set_state(card_id, projection.state).await?;
+        if projection.deleted {
+            self.cards.soft_delete(card_id).await?;
+        }
+        Ok(Some(projection))
+    }
+
+    // Rebuilds every card that has history. Rows without any events are left untouched.
+    pub async fn rebuild_all(&self) -> Result<RebuildSummary> {
+        let mut summary = RebuildSummary::default();
+        for card_id in self.queue.card_ids().await? {
+            match self.rebuild_card(&card_id).await? {
+                Some(p) if p.deleted => summary.deleted += 1,
+                Some(_) => summary.rebuilt += 1,
+                None => summary.skipped.push(card_id),
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::card_fsm::{CardEvent, CardFsm};
+
+    #[tokio::test]
+    async fn test_rebuild_recovers_corrupted_payload() {
+        let db = crate::sqlite::db::SqliteDb::connect_in_memory().await.unwrap();
+        let card = crate::services::mock_data::generate_mock_do_now_card();
+        let id = card.id.to_string();
+        let queue = QueueRepo::new(db.pool.clone());
+        CardsRepo::new(db.pool.clone()).save_card(&card).await.unwrap();
+        queue.append_event(&id, "append", "user", Some(&serde_json::json!({ "card": card }))).await.unwrap();
+        CardFsm::new(db.pool.clone()).apply(card.id, CardEvent::Park, "user", None).await.unwrap();
+
+        sqlx::query("update cards set payload = '{not json' where id = ?1")
+            .bind(&id)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+
+        let projector = CardProjector::new(db.pool.clone());
+        let summary = projector.rebuild_all().await.unwrap();
+        assert_eq!(summary.rebuilt, 1);
+
+        let repo = CardsRepo::new(db.pool.clone());
+        let restored = repo.get_card(&id).await.unwrap().unwrap();
+        assert_eq!(restored.title, card.title);
+        assert_eq!(repo.get_state(&id).await.unwrap(), Some(CardState::Parked));
+    }
+}
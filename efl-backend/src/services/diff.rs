@@ -0,0 +1,135 @@
+use serde::Deserialize;
+use similar::{capture_diff_slices, Algorithm, DiffOp};
+use crate::models::{Diff, DiffOpType, DiffOperation};
+
+// Text diffing for DoNow previews. Operation ranges are byte offsets into `before`
+// and always fall on UTF-8 char boundaries, since tokens are split on chars.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffGranularity {
+    #[default]
+    Word,
+    Line,
+}
+
+pub fn compute_diff(before: &str, after: &str, granularity: DiffGranularity) -> Diff {
+    Diff {
+        before: before.to_string(),
+        after: after.to_string(),
+        operations: diff_operations(before, after, granularity),
+    }
+}
+
+pub fn diff_operations(before: &str, after: &str, granularity: DiffGranularity) -> Vec<DiffOperation> {
+    let old = tokenize(before, granularity);
+    let new = tokenize(after, granularity);
+    let old_text: Vec<&str> = old.iter().map(|t| t.1).collect();
+    let new_text: Vec<&str> = new.iter().map(|t| t.1).collect();
+
+    // Byte offset where token `i` starts (or end of text)
+    let offset = |tokens: &[(usize, &str)], text: &str, i: usize| tokens.get(i).map(|t| t.0).unwrap_or(text.len());
+    let joined = |i: usize, len: usize| new_text[i..i + len].concat();
+
+    let mut ops = Vec::new();
+    for op in capture_diff_slices(Algorithm::Myers, &old_text, &new_text) {
+        match op {
+            DiffOp::Equal { .. } => {}
+            DiffOp::Delete { old_index, old_len, .. } => ops.push(DiffOperation {
+                op_type: DiffOpType::Remove,
+                range: (offset(&old, before, old_index), offset(&old, before, old_index + old_len)),
+                content: None,
+            }),
+            DiffOp::Insert { old_index, new_index, new_len } => {
+                let at = offset(&old, before, old_index);
+                ops.push(DiffOperation {
+                    op_type: DiffOpType::Add,
+                    range: (at, at),
+                    content: Some(joined(new_index, new_len)),
+                });
+            }
+            DiffOp::Replace { old_index, old_len, new_index, new_len } => ops.push(DiffOperation {
+                op_type: DiffOpType::Replace,
+                range: (offset(&old, before, old_index), offset(&old, before, old_index + old_len)),
+                content: Some(joined(new_index, new_len)),
+            }),
+        }
+    }
+    ops
+}
+
+// Splits text into (byte offset, token) pairs that concatenate back to the input.
+// Words are runs of alphanumerics, whitespace runs are kept as their own tokens,
+// and every other char (punctuation, emoji) stands alone.
+fn tokenize(text: &str, granularity: DiffGranularity) -> Vec<(usize, &str)> {
+    if granularity == DiffGranularity::Line {
+        let mut start = 0;
+        return text.split_inclusive('\n')
+            .map(|line| {
+                let token = (start, line);
+                start += line.len();
+                token
+            })
+            .collect();
+    }
+
+    #[derive(PartialEq)]
+    enum Class { Word, Space, Other }
+    let class = |c: char| if c.is_alphanumeric() || c == '_' || c == '\'' {
+        Class::Word
+    } else if c.is_whitespace() {
+        Class::Space
+    } else {
+        Class::Other
+    };
+
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut current: Option<Class> = None;
+    for (i, c) in text.char_indices() {
+        let cls = class(c);
+        let split = match &current {
+            Some(prev) => *prev != cls || cls == Class::Other,
+            None => false,
+        };
+        if split {
+            tokens.push((start, &text[start..i]));
+            start = i;
+        }
+        current = Some(cls);
+    }
+    if start < text.len() {
+        tokens.push((start, &text[start..]));
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::undo::apply_operations;
+
+    #[test]
+    fn test_word_diff_round_trips_utf8() {
+        let before = "Café owners — naïve about the 🚀 launch, ship Friday.";
+        let after = "Café owners are wary of the 🛸 launch; ship Monday.";
+        let ops = diff_operations(before, after, DiffGranularity::Word);
+        assert!(ops.iter().all(|op| before.is_char_boundary(op.range.0) && before.is_char_boundary(op.range.1)));
+        assert_eq!(apply_operations(before, &ops).unwrap(), after);
+        // Unchanged words are left alone
+        assert!(ops.iter().all(|op| !before[op.range.0..op.range.1].contains("Café")));
+    }
+
+    #[test]
+    fn test_line_diff() {
+        let before = "one\ntwo\nthree\n";
+        let after = "one\n2\nthree\nfour";
+        let ops = diff_operations(before, after, DiffGranularity::Line);
+        assert_eq!(ops.len(), 2);
+        assert!(matches!(ops[0].op_type, DiffOpType::Replace));
+        assert_eq!(ops[0].range, (4, 8));
+        assert!(matches!(ops[1].op_type, DiffOpType::Add));
+        assert_eq!(apply_operations(before, &ops).unwrap(), after);
+        assert!(diff_operations(before, before, DiffGranularity::Line).is_empty());
+    }
+}
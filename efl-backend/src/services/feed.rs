@@ -1,70 +1,204 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 use sqlx::{PgPool, SqlitePool};
 use anyhow::Result;
-use crate::models::{Card, Altitude};
+use crate::models::{Card, Altitude, CardState};
+use crate::services::breakin_inbox::BreakInInbox;
+use crate::connectors::email::MemorySource;
 use crate::services::gmail_cards::GmailCardService;
+use crate::services::parking::ParkingService;
+use crate::services::ranking::{FeedRanker, RankContext, RankKey};
+use crate::sqlite::repo::cards::{CardFilter, CardsRepo};
+
+// Cards pulled from each source before ranking
+const SOURCE_WINDOW: usize = 100;
+
+// Where a feed page ended. Later pages are ranked as of the first page's time and
+// leave out cards that arrived since, so new arrivals never shift or repeat a page;
+// clients pick those up from the stream instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedCursor {
+    pub as_of: DateTime<Utc>,
+    pub after: RankKey,
+}
+
+impl FeedCursor {
+    pub fn encode(&self) -> String {
+        hex::encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        serde_json::from_slice(&hex::decode(cursor).ok()?).ok()
+    }
+}
+
+pub struct FeedPage {
+    pub cards: Vec<Card>,
+    pub next_cursor: Option<FeedCursor>,
+    // Ranked cards after this page
+    pub after_count: usize,
+}
+
+pub fn paginate(ranked: Vec<Card>, cursor: Option<&FeedCursor>, as_of: DateTime<Utc>, limit: usize) -> FeedPage {
+    let mut remaining: Vec<Card> = ranked.into_iter()
+        .filter(|c| c.created_at <= as_of)
+        .filter(|c| cursor.is_none_or(|cur| RankKey::of(c).feed_cmp(&cur.after) == Ordering::Greater))
+        .collect();
+    let rest = remaining.split_off(limit.min(remaining.len()));
+    let next_cursor = match (remaining.last(), rest.is_empty()) {
+        (Some(last), false) => Some(FeedCursor { as_of, after: RankKey::of(last) }),
+        _ => None,
+    };
+    FeedPage { cards: remaining, next_cursor, after_count: rest.len() }
+}
 
 pub struct FeedService {
     db_pool: Option<PgPool>,
     sqlite_pool: Option<SqlitePool>,
+    parking: Option<ParkingService>,
+    breakins: Option<BreakInInbox>,
+    ranker: FeedRanker,
 }
 
 impl FeedService {
-    pub fn new(db_pool: Option<PgPool>) -> Self {
-        Self { db_pool, sqlite_pool: None }
-    }
-    
     pub fn new_with_sqlite(db_pool: Option<PgPool>, sqlite_pool: Option<SqlitePool>) -> Self {
-        Self { db_pool, sqlite_pool }
+        Self { db_pool, sqlite_pool, parking: None, breakins: None, ranker: FeedRanker::default() }
+    }
+
+    pub fn with_sources(mut self, parking: ParkingService, breakins: BreakInInbox) -> Self {
+        self.parking = Some(parking);
+        self.breakins = Some(breakins);
+        self
     }
     
-    pub async fn get_feed(
-        &self,
-        altitude: Option<Altitude>,
-        limit: usize,
-    ) -> Result<serde_json::Value> {
+    // Woken, persisted, break-in and Gmail cards, unranked. Earlier sources win when
+    // the same card shows up twice.
+    pub async fn active_cards(&self) -> Vec<Card> {
         let mut all_cards: Vec<Card> = vec![];
-        
-        // Fetch Gmail cards if available
-        if self.sqlite_pool.is_some() {
-            let mut gmail_service = GmailCardService::new(self.sqlite_pool.clone()).await;
-            if let Ok(gmail_cards) = gmail_service.fetch_gmail_cards(limit as u32).await {
-                all_cards.extend(gmail_cards);
+        let mut parked_docs = HashSet::new();
+        if let Some(parking) = &self.parking {
+            all_cards.extend(parking.woken_cards().await);
+            parked_docs.extend(parking.get_parked_cards().await.into_iter()
+                .filter_map(|(card, _, _)| card.origin_object.map(|o| o.doc_id)));
+        }
+
+        if let Some(pool) = &self.sqlite_pool {
+            let repo = CardsRepo::new(pool.clone());
+            for state in [CardState::Active, CardState::Preview, CardState::Idle] {
+                let filter = CardFilter { state: Some(state), limit: Some(SOURCE_WINDOW as i64), ..Default::default() };
+                match repo.list_cards(&filter).await {
+                    Ok(cards) => all_cards.extend(cards),
+                    Err(e) => tracing::warn!("feed: failed to load {} cards: {}", state.as_str(), e),
+                }
             }
         }
+
+        if let Some(breakins) = &self.breakins {
+            all_cards.extend(breakins.list().await);
+        }
         
-        // Sort by altitude (Do > Ship > Amplify > Orient)
-        all_cards.sort_by(|a, b| {
-            use crate::models::Altitude;
-            let altitude_priority = |alt: &Altitude| -> u8 {
-                match alt {
-                    Altitude::Do => 4,
-                    Altitude::Ship => 3,
-                    Altitude::Amplify => 2,
-                    Altitude::Orient => 1,
-                }
-            };
-            altitude_priority(&b.altitude).cmp(&altitude_priority(&a.altitude))
+        // Gmail cards come from the sync worker's cache, so the feed never waits on Gmail.
+        // An EMAIL_FIXTURE stands in for the mailbox when running offline.
+        let fixture = MemorySource::from_env().unwrap_or_else(|e| {
+            tracing::warn!("feed: ignoring EMAIL_FIXTURE: {:#}", e);
+            None
         });
-        
-        // Apply limit
-        all_cards.truncate(limit);
-        
-        let current_altitude = altitude.unwrap_or(Altitude::Do);
+        if self.sqlite_pool.is_some() || fixture.is_some() {
+            let gmail_service = GmailCardService::new(self.sqlite_pool.clone()).await;
+            let gmail_cards = match fixture {
+                Some(mut fixture) => gmail_service.cards_from(&mut fixture, SOURCE_WINDOW as u32).await,
+                None => gmail_service.cached_cards(SOURCE_WINDOW as u32).await,
+            };
+            if let Ok(gmail_cards) = gmail_cards {
+                // Messages whose card is parked stay out until it wakes
+                all_cards.extend(gmail_cards.into_iter()
+                    .filter(|c| c.origin_object.as_ref().is_none_or(|o| !parked_docs.contains(&o.doc_id))));
+            }
+        }
+
+        let mut seen = HashSet::new();
+        all_cards.retain(|c| seen.insert(c.id));
+        all_cards
+    }
+
+    // Ranks the active cards as of `as_of`; each card carries its score breakdown in `metadata.score`
+    async fn ranked_cards(&self, current_altitude: Altitude, as_of: DateTime<Utc>) -> Vec<Card> {
+        let mut ctx = RankContext::new(current_altitude);
+        ctx.now = as_of;
+        if let Some(parking) = &self.parking {
+            ctx.woken.extend(parking.woken_cards().await.iter().map(|c| c.id));
+        }
+        self.ranker.rank(self.active_cards().await, &ctx)
+    }
+
+    // One page of the ranked feed; a cursor continues the ranking it came from
+    pub async fn page(&self, altitude: Altitude, cursor: Option<&FeedCursor>, limit: usize) -> FeedPage {
+        let as_of = cursor.map_or_else(Utc::now, |c| c.as_of);
+        paginate(self.ranked_cards(altitude, as_of).await, cursor, as_of, limit)
+    }
+
+    pub async fn get_feed(
+        &self,
+        current_altitude: Altitude,
+        cursor: Option<&FeedCursor>,
+        limit: usize,
+    ) -> Result<serde_json::Value> {
+        let page = self.page(current_altitude, cursor, limit).await;
+
+        let parked_count = match &self.parking {
+            Some(parking) => parking.parked_count().await,
+            None => 0,
+        };
         
         Ok(serde_json::json!({
-            "cards": all_cards,
+            "cards": page.cards,
             "current_altitude": current_altitude,
-            "parked_count": 0
+            "parked_count": parked_count,
+            "next_cursor": page.next_cursor.map(|c| c.encode()),
+            "after_count": page.after_count
         }))
     }
-    
-    pub async fn get_current_altitude(&self) -> Result<Altitude> {
-        // In a real implementation, fetch from session/user state
-        Ok(Altitude::Do)
-    }
-    
-    pub async fn set_altitude(&self, altitude: Altitude) -> Result<()> {
-        // In a real implementation, update session/user state
-        Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock_data::*;
+    use crate::services::ranking::FeedRanker;
+
+    #[test]
+    fn test_pages_stay_stable_when_cards_arrive() {
+        let ctx = RankContext::new(Altitude::Do);
+        let as_of = ctx.now;
+        let mut cards: Vec<Card> = (0..5).map(|i| {
+            let mut card = generate_mock_do_now_card();
+            card.created_at = as_of - chrono::Duration::minutes(i);
+            card
+        }).collect();
+        let ranker = FeedRanker::default();
+
+        let first = paginate(ranker.rank(cards.clone(), &ctx), None, as_of, 2);
+        assert_eq!(first.cards.len(), 2);
+        assert_eq!(first.after_count, 3);
+        let cursor = FeedCursor::decode(&first.next_cursor.unwrap().encode()).unwrap();
+
+        // A new, urgent card arrives before the next page is fetched
+        let mut urgent = generate_mock_breakin_card();
+        urgent.created_at = as_of + chrono::Duration::seconds(5);
+        cards.push(urgent);
+
+        let second = paginate(ranker.rank(cards.clone(), &ctx), Some(&cursor), cursor.as_of, 2);
+        let third = paginate(ranker.rank(cards, &ctx), second.next_cursor.as_ref(), as_of, 2);
+        let ids: Vec<_> = [&first.cards, &second.cards, &third.cards].iter()
+            .flat_map(|page| page.iter().map(|c| c.id))
+            .collect();
+        assert_eq!(ids.len(), 5);
+        let unique: std::collections::HashSet<_> = ids.iter().collect();
+        assert_eq!(unique.len(), ids.len());
+        assert_eq!(second.after_count, 1);
+        assert!(third.next_cursor.is_none());
+        assert_eq!(third.after_count, 0);
     }
 }
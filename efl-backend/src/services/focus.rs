@@ -0,0 +1,272 @@
+use std::sync::Arc;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+use crate::models::{Altitude, Card, CardAction, CardContent, CardStatus, CardType, OriginObject};
+use crate::services::breakin_inbox::BreakInInbox;
+use crate::services::telemetry::TelemetryService;
+use crate::services::triage::TriageContext;
+use crate::sse::SseEvent;
+
+// Focus mode: while a session is on, break-ins scoring below the session threshold are
+// held instead of interrupting, and come back as one BatchReview card when it ends.
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSession {
+    pub id: Uuid,
+    // The DoNow card being worked on, if the client said
+    pub card_id: Option<Uuid>,
+    pub started_at: DateTime<Utc>,
+    // Open-ended sessions run until stopped
+    pub ends_at: Option<DateTime<Utc>>,
+    // Break-ins whose triage score reaches this still get through
+    pub threshold: f64,
+    pub held: usize,
+    pub passed_through: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FocusSummary {
+    pub session: FocusSession,
+    pub ended_at: DateTime<Utc>,
+    // The BatchReview card holding everything that was buffered
+    pub released: Option<Card>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Admission {
+    Deliver,
+    Held,
+}
+
+struct ActiveFocus {
+    session: FocusSession,
+    buffer: Vec<Card>,
+}
+
+#[derive(Clone)]
+pub struct FocusService {
+    active: Arc<RwLock<Option<ActiveFocus>>>,
+    inbox: BreakInInbox,
+    telemetry: TelemetryService,
+    sse_tx: broadcast::Sender<SseEvent>,
+}
+
+impl FocusService {
+    pub fn new(inbox: BreakInInbox, telemetry: TelemetryService, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        Self { active: Arc::new(RwLock::new(None)), inbox, telemetry, sse_tx }
+    }
+
+    pub async fn current(&self) -> Option<FocusSession> {
+        self.active.read().await.as_ref().map(|a| a.session.clone())
+    }
+
+    // Starts a session, or retargets the one already running (new end time, card, threshold).
+    // The threshold defaults to FOCUS_THRESHOLD, then to the triage threshold.
+    pub async fn start(&self, card_id: Option<Uuid>, ends_at: Option<DateTime<Utc>>, threshold: Option<f64>) -> Result<FocusSession> {
+        let now = Utc::now();
+        if ends_at.is_some_and(|at| at <= now) {
+            bail!("focus end time is in the past");
+        }
+        if threshold.is_some_and(|t| !(0.0..=1.0).contains(&t)) {
+            bail!("threshold must be between 0 and 1");
+        }
+        let threshold = threshold
+            .or_else(|| std::env::var("FOCUS_THRESHOLD").ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|t| (0.0..=1.0).contains(t)))
+            .unwrap_or_else(|| TriageContext::from_env().threshold);
+
+        let mut active = self.active.write().await;
+        let session = match active.as_mut() {
+            Some(focus) => {
+                focus.session.card_id = card_id.or(focus.session.card_id);
+                focus.session.ends_at = ends_at;
+                focus.session.threshold = threshold;
+                focus.session.clone()
+            }
+            None => {
+                let session = FocusSession {
+                    id: Uuid::new_v4(),
+                    card_id,
+                    started_at: now,
+                    ends_at,
+                    threshold,
+                    held: 0,
+                    passed_through: 0,
+                };
+                *active = Some(ActiveFocus { session: session.clone(), buffer: vec![] });
+                session
+            }
+        };
+        drop(active);
+
+        let _ = self.telemetry.record_action("focus.start".to_string(), json!({
+            "sessionId": session.id,
+            "cardId": session.card_id,
+            "endsAt": session.ends_at,
+            "threshold": session.threshold,
+        })).await;
+        let _ = self.sse_tx.send(SseEvent { event: "focus.start".into(), data: json!(session).to_string() });
+
+        if let Some(at) = ends_at {
+            let service = self.clone();
+            let id = session.id;
+            tokio::spawn(async move {
+                tokio::time::sleep((at - Utc::now()).to_std().unwrap_or_default()).await;
+                service.end(Some(id), "timer").await;
+            });
+        }
+        Ok(session)
+    }
+
+    pub async fn stop(&self) -> Option<FocusSummary> {
+        self.end(None, "stopped").await
+    }
+
+    // Ends the session and releases the buffer. With `expected` set (the end-time timer),
+    // only ends that session, and only if it wasn't extended in the meantime.
+    async fn end(&self, expected: Option<Uuid>, reason: &str) -> Option<FocusSummary> {
+        let now = Utc::now();
+        let mut active = self.active.write().await;
+        if let Some(id) = expected {
+            let due = active.as_ref().is_some_and(|a| a.session.id == id && a.session.ends_at.is_some_and(|at| at <= now));
+            if !due {
+                return None;
+            }
+        }
+        let focus = active.take()?;
+        drop(active);
+
+        let released = (!focus.buffer.is_empty()).then(|| batch_card(&focus.buffer, &focus.session));
+        if let Some(card) = &released {
+            self.inbox.push(card.clone()).await;
+        }
+        let summary = FocusSummary { session: focus.session, ended_at: now, released };
+
+        let _ = self.telemetry.record_action("focus.end".to_string(), json!({
+            "sessionId": summary.session.id,
+            "reason": reason,
+            "durationSecs": (now - summary.session.started_at).num_seconds(),
+            "held": summary.session.held,
+            "passedThrough": summary.session.passed_through,
+            "releasedCardId": summary.released.as_ref().map(|c| c.id),
+        })).await;
+        let _ = self.sse_tx.send(SseEvent { event: "focus.end".into(), data: json!(summary).to_string() });
+        tracing::info!("focus session {} ended ({}): {} held, {} passed through", summary.session.id, reason, summary.session.held, summary.session.passed_through);
+        Some(summary)
+    }
+
+    // Decides whether a triaged break-in reaches the user now. Held cards are buffered
+    // here and should not be published by the caller.
+    pub async fn admit(&self, card: &Card) -> Admission {
+        let mut active = self.active.write().await;
+        let Some(focus) = active.as_mut() else {
+            return Admission::Deliver;
+        };
+        // The timer is about to release this session
+        if focus.session.ends_at.is_some_and(|at| at <= Utc::now()) {
+            return Admission::Deliver;
+        }
+        let score = card.metadata.as_ref().and_then(|m| m.triage.as_ref()).map_or(0.0, |t| t.score);
+        if score >= focus.session.threshold {
+            focus.session.passed_through += 1;
+            return Admission::Deliver;
+        }
+        focus.session.held += 1;
+        focus.buffer.push(card.clone());
+        Admission::Held
+    }
+}
+
+// One card for everything that came in during the session, oldest first
+fn batch_card(held: &[Card], session: &FocusSession) -> Card {
+    let items: Vec<serde_json::Value> = held.iter().map(|card| {
+        let triage = card.metadata.as_ref().and_then(|m| m.triage.as_ref());
+        match &card.content {
+            CardContent::BreakIn { source, message, sender, urgency } => json!({
+                "id": card.id,
+                "source": source,
+                "sender": sender,
+                "message": message,
+                "urgency": urgency,
+                "recommendation": triage.map(|t| t.recommendation),
+                "rationale": triage.map(|t| t.rationale.clone()),
+                "receivedAt": card.created_at,
+            }),
+            _ => json!({ "id": card.id, "title": card.title, "receivedAt": card.created_at }),
+        }
+    }).collect();
+
+    Card {
+        id: Uuid::new_v4(),
+        card_type: CardType::BatchReview,
+        altitude: Altitude::Do,
+        title: format!("Held during focus: {} break-ins", held.len()),
+        content: CardContent::BatchReview {
+            emails: items,
+            suggested_actions: vec!["respond_in_order".to_string(), "park_all".to_string()],
+        },
+        actions: vec![CardAction::ProcessBatch, CardAction::ExpandToFlow, CardAction::Park],
+        origin_object: Some(OriginObject { doc_id: format!("focus_{}", session.id), block_id: None }),
+        created_at: Utc::now(),
+        status: CardStatus::Active,
+        metadata: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{CardMetadata, Triage, TriageFactor, TriageRecommendation};
+    use crate::services::mock_data::generate_mock_breakin_card;
+
+    fn breakin(score: f64) -> Card {
+        let factor = TriageFactor { value: 1.0, reason: String::new() };
+        let mut card = generate_mock_breakin_card();
+        card.id = Uuid::new_v4();
+        card.metadata = Some(CardMetadata {
+            triage: Some(Triage {
+                urgency: factor.clone(),
+                impact: factor.clone(),
+                readiness: factor,
+                score,
+                threshold: 0.48,
+                recommendation: TriageRecommendation::AtBreak,
+                preempt: score >= 0.48,
+                rationale: String::new(),
+            }),
+            ..Default::default()
+        });
+        card
+    }
+
+    #[tokio::test]
+    async fn test_focus_holds_and_releases_batch() {
+        let (sse_tx, _) = broadcast::channel(16);
+        let inbox = BreakInInbox::new();
+        let focus = FocusService::new(inbox.clone(), TelemetryService::new(), sse_tx);
+
+        assert_eq!(focus.admit(&breakin(0.1)).await, Admission::Deliver);
+        // A threshold above 1 would hold everything, critical break-ins included
+        assert!(focus.start(None, None, Some(5.0)).await.is_err());
+        assert!(focus.current().await.is_none());
+        focus.start(None, None, Some(0.7)).await.unwrap();
+        assert_eq!(focus.admit(&breakin(0.3)).await, Admission::Held);
+        assert_eq!(focus.admit(&breakin(0.5)).await, Admission::Held);
+        assert_eq!(focus.admit(&breakin(0.9)).await, Admission::Deliver);
+
+        let summary = focus.stop().await.unwrap();
+        assert_eq!((summary.session.held, summary.session.passed_through), (2, 1));
+        let released = summary.released.unwrap();
+        assert!(matches!(&released.content, CardContent::BatchReview { emails, .. } if emails.len() == 2));
+        assert_eq!(inbox.list().await[0].id, released.id);
+        assert!(focus.current().await.is_none());
+        assert!(focus.stop().await.is_none());
+    }
+}
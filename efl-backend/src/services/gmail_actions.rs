@@ -0,0 +1,295 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::sync::RwLock;
+use uuid::Uuid;
+use crate::connectors::email::{sources_for, MailChange};
+use crate::connectors::gmail::{one_click_unsubscribe, GmailClient};
+use crate::connectors::gmail_mime::ListUnsubscribe;
+use crate::connectors::imap::is_imap_id;
+use crate::models::{Card, CardAction, CardContent};
+use crate::services::gmail_threads::latest_message_id;
+use crate::sqlite::repo::gmail_cache::GmailCacheRepo;
+
+// Gmail write actions behind card actions. Nothing touches the mailbox until the user
+// confirms: the first call returns a plan and a token, a second call with that token
+// carries the plan out.
+
+// How long a plan can wait for confirmation
+const CONFIRM_WINDOW_MINUTES: i64 = 5;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailTarget {
+    pub message_id: String,
+    // Raw From header, e.g. "Acme News <news@acme.com>"
+    pub from: String,
+    pub subject: String,
+    pub unsubscribe: Option<ListUnsubscribe>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailActionPlan {
+    pub confirmation_token: Uuid,
+    pub card_id: Uuid,
+    pub action: CardAction,
+    pub targets: Vec<GmailTarget>,
+    // What will happen, one line per step, for the confirmation prompt
+    pub steps: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub fn is_gmail_action(action: &CardAction) -> bool {
+    matches!(action, CardAction::ArchiveAll | CardAction::UnsubscribeAll | CardAction::BlockSender)
+}
+
+// The messages a card stands for: every email in a Gmail batch, or every unread message
+// in the thread behind an email card
+pub fn targets_from_card(card: &Card) -> Vec<GmailTarget> {
+    if let CardContent::BatchReview { emails, .. } = &card.content {
+        return emails.iter()
+            .flat_map(|email| {
+                let text = |key: &str| email.get(key).and_then(|v| v.as_str()).map(str::to_string);
+                // Batch entries stand for their whole thread
+                let ids: Vec<String> = match email.get("messageIds").and_then(|v| serde_json::from_value(v.clone()).ok()) {
+                    Some(ids) => ids,
+                    None => text("id").into_iter().collect(),
+                };
+                let target = GmailTarget {
+                    message_id: String::new(),
+                    from: text("from").or_else(|| text("sender")).unwrap_or_default(),
+                    subject: text("subject").unwrap_or_default(),
+                    unsubscribe: email.get("unsubscribe").and_then(|u| serde_json::from_value(u.clone()).ok()),
+                };
+                ids.into_iter().map(move |message_id| GmailTarget { message_id, ..target.clone() })
+            })
+            .collect();
+    }
+    let subject = card.metadata.as_ref().and_then(|m| m.email_subject.clone()).unwrap_or_default();
+    if let Some(thread) = card.metadata.as_ref().and_then(|m| m.email_thread.as_ref()) {
+        return thread.messages.iter()
+            .map(|m| GmailTarget { message_id: m.id.clone(), from: m.from.clone(), subject: subject.clone(), unsubscribe: None })
+            .collect();
+    }
+    match latest_message_id(card) {
+        Some(id) => vec![GmailTarget {
+            message_id: id.to_string(),
+            from: card.metadata.as_ref().and_then(|m| m.email_sender.clone()).unwrap_or_default(),
+            subject,
+            unsubscribe: None,
+        }],
+        None => vec![],
+    }
+}
+
+// "Acme News <news@acme.com>" -> "news@acme.com"
+pub fn sender_address(from: &str) -> Option<String> {
+    let address = match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &from[start + 1..end],
+        _ => from,
+    };
+    let address = address.trim().to_lowercase();
+    address.contains('@').then_some(address)
+}
+
+fn unsubscribe_targets(targets: &[GmailTarget]) -> Vec<&ListUnsubscribe> {
+    let mut seen = BTreeSet::new();
+    targets.iter()
+        .filter_map(|t| t.unsubscribe.as_ref())
+        .filter(|u| seen.insert((u.http.clone(), u.mailto.clone())))
+        .collect()
+}
+
+fn blocked_senders(targets: &[GmailTarget]) -> BTreeSet<String> {
+    targets.iter().filter_map(|t| sender_address(&t.from)).collect()
+}
+
+pub fn plan_steps(action: &CardAction, targets: &[GmailTarget]) -> Vec<String> {
+    let archive = format!("Archive and mark read {} message(s)", targets.len());
+    match action {
+        CardAction::ArchiveAll => vec![archive],
+        CardAction::UnsubscribeAll => {
+            let mut steps: Vec<String> = unsubscribe_targets(targets).into_iter()
+                .map(|u| match (&u.http, u.one_click) {
+                    (Some(url), true) => format!("Unsubscribe via one-click POST to {}", url),
+                    _ => format!("Open {} to unsubscribe (no one-click support)", u.http.as_ref().or(u.mailto.as_ref()).cloned().unwrap_or_default()),
+                })
+                .collect();
+            steps.push(archive);
+            steps
+        }
+        CardAction::BlockSender => {
+            let mut steps: Vec<String> = blocked_senders(targets).into_iter()
+                .map(|a| format!("Create a Gmail filter that skips the inbox for mail from {}", a))
+                .collect();
+            steps.push(archive);
+            steps
+        }
+        _ => vec![],
+    }
+}
+
+#[derive(Clone)]
+pub struct GmailActionService {
+    pending: Arc<RwLock<HashMap<Uuid, GmailActionPlan>>>,
+    sqlite_pool: Option<SqlitePool>,
+}
+
+impl GmailActionService {
+    pub fn new(sqlite_pool: Option<SqlitePool>) -> Self {
+        Self { pending: Arc::new(RwLock::new(HashMap::new())), sqlite_pool }
+    }
+
+    async fn client(&self) -> GmailClient {
+        GmailClient::from_env_with_db(self.sqlite_pool.clone()).await
+    }
+
+    // Works out what `action` would do to the card's messages and holds it for confirmation
+    pub async fn plan(&self, card: &Card, action: CardAction) -> Result<GmailActionPlan> {
+        if !is_gmail_action(&action) {
+            bail!("{:?} is not a Gmail action", action);
+        }
+        let mut targets = targets_from_card(card);
+        if targets.is_empty() {
+            bail!("card {} has no Gmail messages", card.id);
+        }
+        // Filters are a Gmail feature; IMAP mail can still be archived and unsubscribed
+        if action == CardAction::BlockSender && targets.iter().any(|t| is_imap_id(&t.message_id)) {
+            bail!("card {} has IMAP mail, which can't be blocked without Gmail filters", card.id);
+        }
+        // Only the message ids are taken from the card, which the client may have sent;
+        // senders and List-Unsubscribe links are read from the mailbox
+        let ids: Vec<String> = targets.iter().map(|t| t.message_id.clone()).collect();
+        let mut messages = HashMap::new();
+        for (source, ids) in sources_for(&ids, self.sqlite_pool.clone()).await? {
+            for id in ids {
+                let message = source.get_message(&id).await?;
+                messages.insert(id, message);
+            }
+        }
+        for target in targets.iter_mut() {
+            let message = messages.get(&target.message_id)
+                .ok_or_else(|| anyhow!("message {} wasn't found", target.message_id))?;
+            target.from = message.sender.clone();
+            target.subject = message.subject.clone();
+            target.unsubscribe = message.list_unsubscribe.clone();
+        }
+        if action == CardAction::UnsubscribeAll && unsubscribe_targets(&targets).is_empty() {
+            bail!("none of these messages have a List-Unsubscribe header");
+        }
+        if action == CardAction::BlockSender && blocked_senders(&targets).is_empty() {
+            bail!("couldn't find a sender address to block");
+        }
+
+        let now = Utc::now();
+        let plan = GmailActionPlan {
+            confirmation_token: Uuid::new_v4(),
+            card_id: card.id,
+            steps: plan_steps(&action, &targets),
+            action,
+            targets,
+            expires_at: now + Duration::minutes(CONFIRM_WINDOW_MINUTES),
+        };
+        let mut pending = self.pending.write().await;
+        pending.retain(|_, p| p.expires_at > now);
+        pending.insert(plan.confirmation_token, plan.clone());
+        Ok(plan)
+    }
+
+    // Carries out a confirmed plan. Each token works once.
+    pub async fn confirm(&self, card_id: Uuid, token: Uuid) -> Result<serde_json::Value> {
+        let plan = self.pending.write().await.remove(&token)
+            .filter(|p| p.card_id == card_id && p.expires_at > Utc::now())
+            .ok_or_else(|| anyhow!("confirmation token is unknown or expired; request the action again"))?;
+        let ids: Vec<String> = plan.targets.iter().map(|t| t.message_id.clone()).collect();
+
+        let mut result = json!({ "status": "completed", "action": plan.action });
+        match plan.action {
+            CardAction::UnsubscribeAll => {
+                let (mut unsubscribed, mut manual) = (vec![], vec![]);
+                for target in unsubscribe_targets(&plan.targets) {
+                    match (&target.http, target.one_click) {
+                        (Some(url), true) => match one_click_unsubscribe(url).await {
+                            Ok(()) => unsubscribed.push(url.clone()),
+                            Err(e) => {
+                                tracing::warn!("one-click unsubscribe failed for {}: {}", url, e);
+                                manual.push(url.clone());
+                            }
+                        },
+                        _ => manual.extend(target.http.clone().or_else(|| target.mailto.clone())),
+                    }
+                }
+                result["unsubscribed"] = json!(unsubscribed);
+                // Lists without one-click need the user to follow the link
+                result["manual"] = json!(manual);
+            }
+            CardAction::BlockSender => {
+                let mut client = self.client().await;
+                let mut filters = vec![];
+                for address in blocked_senders(&plan.targets) {
+                    let filter_id = client.create_block_filter(&address).await?;
+                    filters.push(json!({ "sender": address, "filterId": filter_id }));
+                }
+                result["blocked"] = json!(filters);
+            }
+            _ => {}
+        }
+        for (mut source, ids) in sources_for(&ids, self.sqlite_pool.clone()).await? {
+            source.mutate(&ids, MailChange::Archive).await?;
+        }
+        // Out of the feed now rather than at the next sync
+        if let Some(pool) = &self.sqlite_pool {
+            GmailCacheRepo::new(pool.clone()).mark_read(&ids).await?;
+        }
+        result["archived"] = json!(ids.len());
+        tracing::info!("gmail {:?} on card {}: {}", plan.action, card_id, result);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Altitude, CardStatus, CardType};
+
+    #[test]
+    fn test_batch_targets_and_plan() {
+        let unsub = json!({ "http": "https://news.example.com/u/1", "mailto": null, "oneClick": true });
+        let card = Card {
+            id: Uuid::new_v4(),
+            card_type: CardType::BatchReview,
+            altitude: Altitude::Orient,
+            title: "Batch".to_string(),
+            content: CardContent::BatchReview {
+                emails: vec![
+                    json!({ "id": "m1", "from": "Example News <News@Example.com>", "subject": "Weekly", "unsubscribe": unsub }),
+                    json!({ "id": "m2", "from": "news@example.com", "subject": "Daily", "unsubscribe": unsub, "messageIds": ["m2", "m0"] }),
+                    json!({ "subject": "no id, skipped" }),
+                ],
+                suggested_actions: vec![],
+            },
+            actions: vec![],
+            origin_object: None,
+            created_at: Utc::now(),
+            status: CardStatus::Active,
+            metadata: None,
+        };
+
+        let targets = targets_from_card(&card);
+        assert_eq!(targets.iter().map(|t| t.message_id.as_str()).collect::<Vec<_>>(), vec!["m1", "m2", "m0"]);
+        assert_eq!(blocked_senders(&targets).into_iter().collect::<Vec<_>>(), vec!["news@example.com"]);
+
+        let steps = plan_steps(&CardAction::UnsubscribeAll, &targets);
+        assert_eq!(steps, vec![
+            "Unsubscribe via one-click POST to https://news.example.com/u/1".to_string(),
+            "Archive and mark read 3 message(s)".to_string(),
+        ]);
+        assert_eq!(sender_address("Ana <ana@example.com>").as_deref(), Some("ana@example.com"));
+        assert!(sender_address("Ana").is_none());
+    }
+}
@@ -3,9 +3,16 @@ use uuid::Uuid;
 use chrono::Utc;
 use crate::models::{
     Card, CardContent, CardAction, CardType, CardStatus, Altitude,
-    OriginObject, BreakInUrgency, Intent, IntentType, NextTask, CardMetadata
+    OriginObject, BreakInUrgency, Intent, IntentType, NextTask, CardMetadata, Triage,
+    TriageRecommendation, EmailThread, EmailThreadMessage
 };
-use crate::connectors::gmail::{GmailClient, GmailMessage};
+use crate::models::DraftKind;
+use crate::services::gmail_drafts::GmailDraftService;
+use crate::services::triage::{triage, urgency_for, Interrupt, TriageContext};
+use crate::connectors::email::{EmailMessage, EmailSource};
+use crate::services::gmail_threads::{build_thread, thread_card_id, thread_doc_id};
+use crate::sqlite::repo::gmail_cache::GmailCacheRepo;
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use reqwest::Client;
 use sqlx::SqlitePool;
@@ -19,26 +26,88 @@ enum EmailCategory {
     Spam,          // Low-value/spam emails
 }
 
+// A message and DSPy's take on it, when DSPy answered
+type Classified = (EmailMessage, Option<DspyEmailClass>);
+
+// Turns mail from any `EmailSource` into cards: categorize (DSPy, else heuristics), then
+// one card per thread, with low-priority mail batched
 pub struct GmailCardService {
-    gmail_client: GmailClient,
+    triage: TriageContext,
+    drafts: GmailDraftService,
+    cache: Option<GmailCacheRepo>,
 }
 
 impl GmailCardService {
     pub async fn new(sqlite_pool: Option<SqlitePool>) -> Self {
-        let gmail_client = GmailClient::from_env_with_db(sqlite_pool).await;
-        Self { gmail_client }
+        Self {
+            triage: TriageContext::from_env(),
+            drafts: GmailDraftService::new(sqlite_pool.clone()),
+            cache: sqlite_pool.map(GmailCacheRepo::new),
+        }
     }
 
-    pub async fn fetch_gmail_cards(&mut self, limit: u32) -> Result<Vec<Card>> {
-        let messages = self.gmail_client.list_unread(limit).await?;
-        
+    // Straight from the source, classifying every message
+    pub async fn cards_from<S: EmailSource + ?Sized>(&self, source: &mut S, limit: u32) -> Result<Vec<Card>> {
+        let ids = source.list_new(limit).await?;
+        let mut classified = Vec::with_capacity(ids.len());
+        for id in ids {
+            // One unreadable message shouldn't cost the rest of the feed
+            let Ok(msg) = source.get_message(&id).await else { continue };
+            let class = self.classify_with_dspy(&msg).await.ok().flatten();
+            classified.push((msg, class));
+        }
+        // Whole threads, so summaries can quote messages that were already read
+        let mut threads: HashMap<String, Vec<EmailMessage>> = HashMap::new();
+        for (msg, _) in &classified {
+            if !threads.contains_key(&msg.thread_id) {
+                if let Ok(thread) = source.thread(&msg.thread_id).await {
+                    threads.insert(msg.thread_id.clone(), thread);
+                }
+            }
+        }
+        let mut cards = self.build_cards(classified, &threads);
+        self.drafts.attach(&mut cards).await;
+        Ok(cards)
+    }
+
+    // From the local copy the sync worker keeps; never calls Gmail or DSPy
+    pub async fn cached_cards(&self, limit: u32) -> Result<Vec<Card>> {
+        let cache = self.cache.as_ref().ok_or_else(|| anyhow::anyhow!("the Gmail cache needs SQLite"))?;
+        let classified = cache.unread(limit as i64).await?.into_iter()
+            .map(|(msg, class)| (msg, class.and_then(|c| serde_json::from_value(c).ok())))
+            .collect();
+        let mut cards = self.build_cards(classified, &HashMap::new());
+        self.drafts.attach(&mut cards).await;
+        Ok(cards)
+    }
+
+    // DSPy's classification in the form the cache stores; None when DSPy is off or failing
+    pub async fn classify(&self, message: &EmailMessage) -> Option<serde_json::Value> {
+        let class = self.classify_with_dspy(message).await.ok().flatten()?;
+        serde_json::to_value(class).ok()
+    }
+
+    // One card per thread, classified by its newest message. `full_threads` adds the thread's
+    // read messages for context where the source could provide them.
+    fn build_cards(&self, messages: Vec<Classified>, full_threads: &HashMap<String, Vec<EmailMessage>>) -> Vec<Card> {
+        // Threads keep the order they were listed in
+        let mut threads: Vec<(String, Vec<Classified>)> = Vec::new();
+        for (msg, class) in messages {
+            match threads.iter_mut().find(|(id, _)| *id == msg.thread_id) {
+                Some((_, thread)) => thread.push((msg, class)),
+                None => threads.push((msg.thread_id.clone(), vec![(msg, class)])),
+            }
+        }
+
         // Separate high and low priority emails
         let mut high_priority_cards = Vec::new();
         let mut low_priority_emails = Vec::new();
+        let mut low_priority_threads = HashMap::new();
         
-        for msg in messages {
+        for (thread_id, thread) in threads {
+            let email_thread = self.email_thread(&thread_id, &thread, full_threads.get(&thread_id));
+            let Some((msg, dspy_class)) = thread.into_iter().max_by_key(|(msg, _)| Self::message_time(msg)) else { continue };
             // Prefer DSPy classification; fall back to heuristic
-            let dspy_class = self.classify_with_dspy(&msg).await.ok().flatten();
             let (category, card_hint, interaction_mode) = match &dspy_class {
                 Some(dc) => (
                     Self::map_label_to_category(&dc.category_label),
@@ -56,21 +125,24 @@ impl GmailCardService {
                 EmailCategory::Personal => {
                     // Personal emails always go to main flow
                     let card = self.convert_to_card_with_class(msg, &category, dspy_class.as_ref(), card_hint.as_deref());
-                    high_priority_cards.push(card);
+                    high_priority_cards.push(Self::with_thread(card, email_thread));
                 },
                 EmailCategory::Sales => {
                     // Sales emails that need a decision
                     if interaction_mode.as_deref() == Some("batch_review") {
+                        low_priority_threads.insert(thread_id, email_thread);
                         low_priority_emails.push((msg, category, has_unsubscribe));
                     } else if self.is_relevant_sales(&msg) {
                         let card = self.convert_to_card_with_class(msg, &category, dspy_class.as_ref(), card_hint.as_deref());
-                        high_priority_cards.push(card);
+                        high_priority_cards.push(Self::with_thread(card, email_thread));
                     } else {
+                        low_priority_threads.insert(thread_id, email_thread);
                         low_priority_emails.push((msg, category, has_unsubscribe));
                     }
                 },
                 EmailCategory::Newsletter | EmailCategory::Notification | EmailCategory::Spam => {
                     // Batch these for review
+                    low_priority_threads.insert(thread_id, email_thread);
                     low_priority_emails.push((msg, category, has_unsubscribe));
                 }
             }
@@ -81,15 +153,56 @@ impl GmailCardService {
         
         // Always batch low-priority emails if there are any
         if !low_priority_emails.is_empty() {
-            let batch_card = self.create_enhanced_batch_card(low_priority_emails);
+            let batch_card = self.create_enhanced_batch_card(low_priority_emails, &low_priority_threads);
             cards.push(batch_card);
         }
-        
-        Ok(cards)
+        cards
+    }
+
+    fn email_thread(&self, thread_id: &str, unread: &[Classified], full: Option<&Vec<EmailMessage>>) -> EmailThread {
+        let newest = unread.iter().map(|(msg, _)| Self::message_time(msg)).max();
+        let messages: Vec<&EmailMessage> = match full {
+            // Nothing after the newest unread message, so a reply still answers that one
+            Some(full) if !full.is_empty() => full.iter().filter(|msg| Some(Self::message_time(msg)) <= newest).collect(),
+            _ => unread.iter().map(|(msg, _)| msg).collect(),
+        };
+        build_thread(thread_id, messages.into_iter().map(|msg| (
+            EmailThreadMessage {
+                id: msg.id.clone(),
+                from: msg.sender.clone(),
+                sender: self.extract_sender_name(msg),
+                date: msg.date.clone(),
+                snippet: msg.snippet.clone(),
+            },
+            msg.text().to_string(),
+            Self::message_time(msg),
+        )).collect())
+    }
+
+    // Re-keys a card built from the thread's newest message to the thread itself
+    fn with_thread(mut card: Card, thread: EmailThread) -> Card {
+        card.id = thread_card_id(&thread.thread_id);
+        card.origin_object = Some(OriginObject {
+            doc_id: thread_doc_id(&thread.thread_id),
+            block_id: Some(thread.thread_id.clone()),
+        });
+        card.metadata.get_or_insert_with(CardMetadata::default).email_thread = Some(thread);
+        card
+    }
+
+    // Text for a new draft on a card: a decline, or the first reply template for its category
+    pub fn draft_text(&self, message: &EmailMessage, category_label: Option<&str>, kind: DraftKind) -> String {
+        match kind {
+            DraftKind::Decline => self.generate_decline_template(message),
+            DraftKind::Reply => {
+                let category = category_label.map_or_else(|| self.categorize_email(message), Self::map_label_to_category);
+                self.generate_reply_templates(message, &category).into_iter().next().unwrap_or_default()
+            }
+        }
     }
     
-    fn categorize_email(&self, message: &GmailMessage) -> EmailCategory {
-        let snippet_lower = message.snippet.to_lowercase();
+    fn categorize_email(&self, message: &EmailMessage) -> EmailCategory {
+        let snippet_lower = message.text().to_lowercase();
         
         // Check for spam/low-value patterns
         if snippet_lower.contains("unsubscribe") ||
@@ -130,15 +243,28 @@ impl GmailCardService {
         EmailCategory::Notification
     }
 
-    fn convert_to_card(&self, message: GmailMessage, category: &EmailCategory) -> Card {
-        let (card_type, altitude) = self.determine_card_type(&message, category);
+    // Same message, same card id, so the feed and its cursors stay stable across fetches
+    fn message_card_id(message_id: &str) -> Uuid {
+        Uuid::new_v3(&Uuid::NAMESPACE_URL, format!("gmail:{}", message_id).as_bytes())
+    }
+
+    // When the message arrived, falling back to now for unparseable Date headers
+    pub fn message_time(message: &EmailMessage) -> chrono::DateTime<Utc> {
+        chrono::DateTime::parse_from_rfc2822(message.date.trim())
+            .map(|d| d.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now())
+    }
+
+    fn convert_to_card(&self, message: EmailMessage, category: &EmailCategory) -> Card {
+        let triage = self.triage_message(&message, category);
+        let (card_type, altitude) = self.determine_card_type(category, triage.as_ref());
         let title = self.extract_title(&message, category);
-        let content = self.create_card_content(&message, &card_type, category);
+        let content = self.create_card_content(&message, &card_type, category, triage.as_ref());
         let actions = self.determine_actions(&card_type, category);
         let reply_templates = self.generate_reply_templates(&message, category);
         
         Card {
-            id: Uuid::new_v4(),
+            id: Self::message_card_id(&message.id),
             card_type,
             altitude,
             title,
@@ -148,7 +274,7 @@ impl GmailCardService {
                 doc_id: format!("gmail_{}", message.id),
                 block_id: Some(message.thread_id.clone()),
             }),
-            created_at: Utc::now(),
+            created_at: Self::message_time(&message),
             status: CardStatus::Active,
             metadata: Some(CardMetadata {
                 email_sender: Some(self.extract_sender_name(&message)),
@@ -156,12 +282,17 @@ impl GmailCardService {
                 email_date: if message.date.is_empty() { None } else { Some(message.date.clone()) },
                 reply_templates: Some(reply_templates),
                 email_category: Some(format!("{:?}", category)),
+                score: None,
+                triage,
+                draft: None,
+                email_thread: None,
             }),
         }
     }
 
-    fn convert_to_card_with_class(&self, message: GmailMessage, category: &EmailCategory, class: Option<&DspyEmailClass>, card_hint: Option<&str>) -> Card {
-        let (mut card_type, mut altitude) = self.determine_card_type(&message, category);
+    fn convert_to_card_with_class(&self, message: EmailMessage, category: &EmailCategory, class: Option<&DspyEmailClass>, card_hint: Option<&str>) -> Card {
+        let triage = self.triage_message(&message, category);
+        let (mut card_type, mut altitude) = self.determine_card_type(category, triage.as_ref());
         if let Some(hint) = card_hint {
             // Trust DSPy card type mapping if provided
             match hint {
@@ -175,7 +306,7 @@ impl GmailCardService {
         }
 
         let title = self.extract_title(&message, category);
-        let mut content = self.create_card_content(&message, &card_type, category);
+        let mut content = self.create_card_content(&message, &card_type, category, triage.as_ref());
         let mut actions = self.determine_actions(&card_type, category);
         let mut reply_templates = self.generate_reply_templates(&message, category);
 
@@ -189,7 +320,7 @@ impl GmailCardService {
         }
 
         Card {
-            id: Uuid::new_v4(),
+            id: Self::message_card_id(&message.id),
             card_type,
             altitude,
             title,
@@ -199,7 +330,7 @@ impl GmailCardService {
                 doc_id: format!("gmail_{}", message.id),
                 block_id: Some(message.thread_id.clone()),
             }),
-            created_at: Utc::now(),
+            created_at: Self::message_time(&message),
             status: CardStatus::Active,
             metadata: Some(CardMetadata {
                 email_sender: Some(self.extract_sender_name(&message)),
@@ -208,12 +339,21 @@ impl GmailCardService {
                 reply_templates: Some(reply_templates),
                 email_category: class
                     .map(|c| c.category_label.clone())
-                    .or_else(|| Some(format!("{:?}", category)))
+                    .or_else(|| Some(format!("{:?}", category))),
+                score: None,
+                triage,
+                draft: None,
+                email_thread: None,
             }),
         }
     }
     
-    fn create_enhanced_batch_card(&self, emails: Vec<(GmailMessage, EmailCategory, bool)>) -> Card {
+    fn create_enhanced_batch_card(&self, emails: Vec<(EmailMessage, EmailCategory, bool)>, threads: &HashMap<String, EmailThread>) -> Card {
+        let thread_ids = |msg: &EmailMessage| -> Vec<String> {
+            threads.get(&msg.thread_id)
+                .map(|t| t.messages.iter().map(|m| m.id.clone()).collect())
+                .unwrap_or_else(|| vec![msg.id.clone()])
+        };
         let email_summaries: Vec<serde_json::Value> = emails.iter().map(|(msg, category, has_unsubscribe)| {
             serde_json::json!({
                 "id": msg.id,
@@ -222,11 +362,21 @@ impl GmailCardService {
                 "category": format!("{:?}", category).to_lowercase(),
                 "hasUnsubscribe": has_unsubscribe,
                 "snippet": msg.snippet,
+                // Raw From header and List-Unsubscribe targets for the Gmail batch actions
+                "from": msg.sender,
+                "unsubscribe": msg.list_unsubscribe,
+                // Every unread message in the thread, which the batch actions apply to
+                "messageIds": thread_ids(msg),
             })
         }).collect();
         
+        let all_ids: Vec<String> = emails.iter().flat_map(|(msg, _, _)| thread_ids(msg)).collect();
+        let mut ids: Vec<&str> = all_ids.iter().map(String::as_str).collect();
+        ids.sort();
+        let created_at = emails.iter().map(|(msg, _, _)| Self::message_time(msg)).max().unwrap_or_else(Utc::now);
+
         Card {
-            id: Uuid::new_v4(),
+            id: Self::message_card_id(&ids.join(",")),
             card_type: CardType::BatchReview,
             altitude: Altitude::Orient,
             title: format!("Batch Review: {} Low-Priority Emails", emails.len()),
@@ -234,33 +384,29 @@ impl GmailCardService {
                 emails: email_summaries,
                 suggested_actions: self.get_batch_actions(&emails),
             },
-            actions: vec![
-                CardAction::ProcessBatch,
-                CardAction::ExpandToFlow,
-                CardAction::Park,
-            ],
+            actions: [
+                vec![CardAction::ProcessBatch, CardAction::ArchiveAll],
+                if emails.iter().any(|(_, _, unsub)| *unsub) { vec![CardAction::UnsubscribeAll] } else { vec![] },
+                vec![CardAction::ExpandToFlow, CardAction::Park],
+            ].concat(),
             origin_object: Some(OriginObject {
                 doc_id: "gmail_batch".to_string(),
                 block_id: None,
             }),
-            created_at: Utc::now(),
+            created_at,
             status: CardStatus::Active,
             metadata: None,
         }
     }
     
-    fn detect_unsubscribe_link(&self, message: &GmailMessage) -> bool {
-        let text = message.snippet.to_lowercase();
-        text.contains("unsubscribe") || 
-        text.contains("manage preferences") ||
-        text.contains("email preferences") ||
-        text.contains("opt out")
+    fn detect_unsubscribe_link(&self, message: &EmailMessage) -> bool {
+        message.list_unsubscribe.is_some()
     }
     
-    fn is_relevant_sales(&self, message: &GmailMessage) -> bool {
+    fn is_relevant_sales(&self, message: &EmailMessage) -> bool {
         // Check if this is from a known vendor we're actively working with
         // or if it's a warm lead vs cold outreach
-        let snippet_lower = message.snippet.to_lowercase();
+        let snippet_lower = message.text().to_lowercase();
         
         // Signs of relevant sales outreach
         if snippet_lower.contains("follow up on our conversation") ||
@@ -281,7 +427,7 @@ impl GmailCardService {
         false
     }
     
-    fn get_batch_actions(&self, emails: &[(GmailMessage, EmailCategory, bool)]) -> Vec<String> {
+    fn get_batch_actions(&self, emails: &[(EmailMessage, EmailCategory, bool)]) -> Vec<String> {
         let mut actions = vec!["archive_all".to_string()];
         
         // Count categories
@@ -336,17 +482,22 @@ impl GmailCardService {
         }
     }
 
-    fn determine_card_type(&self, message: &GmailMessage, category: &EmailCategory) -> (CardType, Altitude) {
-        let snippet_lower = message.snippet.to_lowercase();
-        
+    // Personal mail is triaged like any other interrupt; anything not worth parking becomes a break-in
+    fn triage_message(&self, message: &EmailMessage, category: &EmailCategory) -> Option<Triage> {
+        if !matches!(category, EmailCategory::Personal) {
+            return None;
+        }
+        let sender = self.extract_sender_name(message);
+        let text = format!("{} {}", message.subject, message.text());
+        Some(triage(&Interrupt { sender: &sender, text: &text, category: Some("Personal") }, &self.triage))
+    }
+
+    fn determine_card_type(&self, category: &EmailCategory, triage: Option<&Triage>) -> (CardType, Altitude) {
         match category {
             EmailCategory::Personal => {
-                // Personal emails that need immediate response
-                if snippet_lower.contains("urgent") || 
-                   snippet_lower.contains("asap") {
-                    (CardType::BreakIn, Altitude::Do)
-                } else {
-                    (CardType::DoNow, Altitude::Do)
+                match triage.map(|t| t.recommendation) {
+                    Some(TriageRecommendation::Now) | Some(TriageRecommendation::AtBreak) => (CardType::BreakIn, Altitude::Do),
+                    _ => (CardType::DoNow, Altitude::Do),
                 }
             },
             EmailCategory::Sales => {
@@ -360,7 +511,7 @@ impl GmailCardService {
         }
     }
 
-    fn extract_title(&self, message: &GmailMessage, category: &EmailCategory) -> String {
+    fn extract_title(&self, message: &EmailMessage, category: &EmailCategory) -> String {
         // Use subject if available, otherwise use snippet
         if !message.subject.is_empty() {
             message.subject.clone()
@@ -381,13 +532,13 @@ impl GmailCardService {
         }
     }
 
-    fn create_card_content(&self, message: &GmailMessage, card_type: &CardType, category: &EmailCategory) -> CardContent {
+    fn create_card_content(&self, message: &EmailMessage, card_type: &CardType, category: &EmailCategory, triage: Option<&Triage>) -> CardContent {
         match card_type {
             CardType::BreakIn => CardContent::BreakIn {
                 source: "Gmail".to_string(),
                 message: message.snippet.clone(),
                 sender: self.extract_sender_name(&message),
-                urgency: BreakInUrgency::High,
+                urgency: triage.map_or(BreakInUrgency::High, |t| urgency_for(t.recommendation)),
             },
             CardType::DoNow => {
                 // Create enhanced DoNow content with email metadata
@@ -398,7 +549,7 @@ impl GmailCardService {
                     "From: {}\nSubject: {}\n\n{}",
                     self.extract_sender_name(&message),
                     message.subject,
-                    message.snippet
+                    message.text()
                 );
                 
                 CardContent::DoNow {
@@ -428,7 +579,7 @@ impl GmailCardService {
         }
     }
 
-    fn create_intent(&self, message: &GmailMessage, category: &EmailCategory) -> Intent {
+    fn create_intent(&self, message: &EmailMessage, category: &EmailCategory) -> Intent {
         let intent_type = match category {
             EmailCategory::Personal => IntentType::Operate,
             EmailCategory::Sales => IntentType::Decide,
@@ -469,6 +620,7 @@ impl GmailCardService {
                 CardAction::GenerateDraft,       // Generate custom response
                 CardAction::Open,                 // Open to read full context
                 CardAction::Park,                 // Defer decision
+                CardAction::BlockSender,          // Stop hearing from them
             ],
             EmailCategory::Newsletter | EmailCategory::Notification => vec![
                 CardAction::Open,          // Quick scan
@@ -477,11 +629,12 @@ impl GmailCardService {
             EmailCategory::Spam => vec![
                 CardAction::Open,          // Verify it's spam
                 CardAction::Park,          // Mark as spam/delete
+                CardAction::BlockSender,   // Filter future mail out of the inbox
             ],
         }
     }
     
-    fn extract_sender_name(&self, message: &GmailMessage) -> String {
+    fn extract_sender_name(&self, message: &EmailMessage) -> String {
         // Parse the sender field to extract just the name
         let sender = &message.sender;
         if sender.is_empty() {
@@ -501,8 +654,8 @@ impl GmailCardService {
         }
     }
     
-    pub fn generate_reply_templates(&self, message: &GmailMessage, category: &EmailCategory) -> Vec<String> {
-        let snippet_lower = message.snippet.to_lowercase();
+    pub fn generate_reply_templates(&self, message: &EmailMessage, category: &EmailCategory) -> Vec<String> {
+        let snippet_lower = message.text().to_lowercase();
         
         match category {
             EmailCategory::Personal => {
@@ -538,7 +691,7 @@ impl GmailCardService {
         }
     }
 
-    pub fn generate_decline_template(&self, message: &GmailMessage) -> String {
+    pub fn generate_decline_template(&self, message: &EmailMessage) -> String {
         let sender_name = self.extract_sender_name(message);
         
         // Generate a respectful decline based on the email content
@@ -562,8 +715,8 @@ impl GmailCardService {
         templates[index].clone()
     }
     
-    fn get_reasoning(&self, message: &GmailMessage, category: &EmailCategory) -> String {
-        let snippet_lower = message.snippet.to_lowercase();
+    fn get_reasoning(&self, message: &EmailMessage, category: &EmailCategory) -> String {
+        let snippet_lower = message.text().to_lowercase();
         
         match category {
             EmailCategory::Personal => {
@@ -613,7 +766,7 @@ struct DspyEmailClass {
 }
 
 impl GmailCardService {
-    async fn classify_with_dspy(&self, message: &GmailMessage) -> Result<Option<DspyEmailClass>> {
+    async fn classify_with_dspy(&self, message: &EmailMessage) -> Result<Option<DspyEmailClass>> {
         // Allow disabling via env
         if std::env::var("USE_DSPY").unwrap_or_else(|_| "true".to_string()) == "false" {
             return Ok(None);
@@ -624,6 +777,7 @@ impl GmailCardService {
         let body = serde_json::json!({
             "subject": message.subject,
             "snippet": message.snippet,
+            "body": message.text(),
             "sender": message.sender,
             "metadata": { }
         });
@@ -660,3 +814,51 @@ impl GmailCardService {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::email::{MailChange, MailState, MemorySource};
+
+    fn message(id: &str, thread_id: &str, from: &str, date: &str, body: &str, state: MailState) -> EmailMessage {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "threadId": thread_id,
+            "snippet": body,
+            "sender": from,
+            "subject": "Q3 plan",
+            "date": date,
+            "body": body,
+            "state": state,
+        })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_cards_from_memory_source() {
+        std::env::set_var("USE_DSPY", "false");
+        let mut newsletter = message("n1", "t2", "Weekly <news@example.com>", "Mon, 12 Oct 2026 08:00:00 +0000", "This week in review", MailState::Unread);
+        newsletter.list_unsubscribe = Some(Default::default());
+        // A colleague writing through a group list still gets their own card
+        let mut question = message("m2", "t1", "Ana <ana@example.com>", "Mon, 12 Oct 2026 09:00:00 +0000", "Hi Sam, can you review it?", MailState::Unread);
+        question.list_unsubscribe = Some(Default::default());
+        let mut source = MemorySource::new(vec![
+            message("m1", "t1", "Bo <bo@example.com>", "Sun, 11 Oct 2026 09:00:00 +0000", "Draft attached.", MailState::Read),
+            newsletter,
+            question,
+        ]);
+        let service = GmailCardService::new(None).await;
+
+        let cards = service.cards_from(&mut source, 10).await.unwrap();
+        assert_eq!(cards.len(), 2);
+        let thread = cards[0].metadata.as_ref().and_then(|m| m.email_thread.as_ref()).unwrap();
+        assert_eq!(cards[0].id, thread_card_id("t1"));
+        // The read message comes along for context
+        assert_eq!(thread.messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m1", "m2"]);
+        assert_eq!(thread.latest_ask.as_deref(), Some("Ana: Hi Sam, can you review it?"));
+        assert_eq!(cards[1].origin_object.as_ref().unwrap().doc_id, "gmail_batch");
+
+        source.mutate(&["n1".to_string()], MailChange::Archive).await.unwrap();
+        let cards = service.cards_from(&mut source, 10).await.unwrap();
+        assert_eq!(cards.iter().map(|c| c.id).collect::<Vec<_>>(), vec![thread_card_id("t1")]);
+    }
+}
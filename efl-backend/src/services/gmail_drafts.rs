@@ -0,0 +1,185 @@
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+use crate::connectors::email::EmailMessage;
+use crate::connectors::gmail::GmailClient;
+use crate::connectors::gmail_mime::{reply_raw, reply_subject};
+use crate::connectors::imap::is_imap_id;
+use crate::connectors::smtp::{SmtpConfig, SmtpSender};
+use crate::models::{Card, CardAction, CardDraft, CardMetadata, DraftKind, DraftState};
+use crate::services::gmail_cards::GmailCardService;
+use crate::services::gmail_threads::latest_message_id;
+use crate::sqlite::repo::drafts::DraftRepo;
+use crate::sqlite::repo::gmail_cache::GmailCacheRepo;
+
+// Replies to Gmail cards. GenerateDraft / DeclineRespectfully write a real Gmail draft in
+// the card's thread; nothing is sent until SendDraft is approved. Mail synced over IMAP has
+// no server-side drafts, so those are kept here and sent over SMTP.
+
+pub fn is_draft_action(action: &CardAction) -> bool {
+    matches!(action, CardAction::GenerateDraft | CardAction::DeclineRespectfully | CardAction::SendDraft | CardAction::DiscardDraft)
+}
+
+#[derive(Clone)]
+pub struct GmailDraftService {
+    repo: Option<DraftRepo>,
+    sqlite_pool: Option<SqlitePool>,
+}
+
+impl GmailDraftService {
+    pub fn new(sqlite_pool: Option<SqlitePool>) -> Self {
+        Self { repo: sqlite_pool.clone().map(DraftRepo::new), sqlite_pool }
+    }
+
+    fn repo(&self) -> Result<&DraftRepo> {
+        self.repo.as_ref().ok_or_else(|| anyhow!("Gmail drafts need SQLite"))
+    }
+
+    async fn cached_message(&self, id: &str) -> Result<EmailMessage> {
+        let pool = self.sqlite_pool.clone().ok_or_else(|| anyhow!("IMAP mail needs SQLite"))?;
+        GmailCacheRepo::new(pool).get(id).await?.ok_or_else(|| anyhow!("message {} isn't cached", id))
+    }
+
+    pub async fn get(&self, card_id: Uuid) -> Result<Option<CardDraft>> {
+        Ok(self.repo()?.get(card_id).await?)
+    }
+
+    // Writes (or rewrites) the card's draft. `body` overrides the generated text.
+    pub async fn draft(&self, card: &Card, kind: DraftKind, body: Option<String>) -> Result<CardDraft> {
+        let repo = self.repo()?;
+        let message_id = latest_message_id(card).ok_or_else(|| anyhow!("card {} isn't a Gmail message", card.id))?;
+        let imap = is_imap_id(message_id);
+        let mut client = GmailClient::from_env_with_db(self.sqlite_pool.clone()).await;
+        let message = match imap {
+            true => self.cached_message(message_id).await?,
+            false => {
+                client.ensure_access_token().await?;
+                client.get_message(message_id).await?
+            }
+        };
+
+        let body = match body {
+            Some(body) => body,
+            None => {
+                let templates = card.metadata.as_ref().and_then(|m| m.reply_templates.as_ref());
+                match (kind, templates.and_then(|t| t.first())) {
+                    (DraftKind::Reply, Some(template)) => template.clone(),
+                    _ => {
+                        let category = card.metadata.as_ref().and_then(|m| m.email_category.as_deref());
+                        GmailCardService::new(self.sqlite_pool.clone()).await.draft_text(&message, category, kind)
+                    }
+                }
+            }
+        };
+        let to = if message.reply_to.is_empty() { message.sender.clone() } else { message.reply_to.clone() };
+        let subject = reply_subject(&message.subject);
+        let raw = reply_raw(&to, &subject, &message.rfc822_message_id, &message.references, &body);
+
+        // Every rewrite gets a fresh id, so an approval only ever sends the text it was shown
+        let replaced = repo.get(card.id).await?.filter(|d| d.state == DraftState::Drafted);
+        let draft_id = match imap {
+            true => format!("local:{}", Uuid::new_v4()),
+            false => client.create_draft(&message.thread_id, &raw).await?,
+        };
+        // The Gmail draft it replaces goes, so Gmail doesn't collect stale copies
+        if let Some(old) = replaced.filter(|_| !imap) {
+            if let Err(e) = client.delete_draft(&old.draft_id).await {
+                tracing::warn!("couldn't delete replaced gmail draft {}: {}", old.draft_id, e);
+            }
+        }
+        let draft = CardDraft {
+            draft_id,
+            kind,
+            state: DraftState::Drafted,
+            message_id: message.id,
+            thread_id: message.thread_id,
+            to,
+            subject,
+            body,
+            updated_at: Utc::now(),
+        };
+        repo.save(card.id, &draft).await?;
+        tracing::info!("drafted {:?} {} for card {}", kind, draft.draft_id, card.id);
+        Ok(draft)
+    }
+
+    // Sends the card's draft. `approved_draft_id` must name the draft the user looked at,
+    // so an approval never sends a draft that was rewritten after it was shown.
+    pub async fn send(&self, card_id: Uuid, approved_draft_id: &str) -> Result<CardDraft> {
+        let repo = self.repo()?;
+        let mut draft = self.claim(card_id, approved_draft_id, DraftState::Sent).await?;
+        let sent = match is_imap_id(&draft.message_id) {
+            true => self.send_smtp(&draft).await,
+            false => {
+                let mut client = GmailClient::from_env_with_db(self.sqlite_pool.clone()).await;
+                client.send_draft(&draft.draft_id).await.map(|_| ())
+            }
+        };
+        if let Err(e) = sent {
+            // Not sent, so it can be approved again
+            repo.transition(card_id, &draft.draft_id, DraftState::Sent, DraftState::Drafted).await?;
+            return Err(e);
+        }
+        draft.state = DraftState::Sent;
+        Ok(draft)
+    }
+
+    async fn send_smtp(&self, draft: &CardDraft) -> Result<()> {
+        let message = self.cached_message(&draft.message_id).await?;
+        let config = SmtpConfig::from_env().ok_or_else(|| anyhow!("SMTP_HOST isn't set"))?;
+        SmtpSender::new(&config)?
+            .send_reply(&draft.to, &draft.subject, &message.rfc822_message_id, &message.references, &draft.body)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn discard(&self, card_id: Uuid) -> Result<CardDraft> {
+        let current = self.repo()?.get(card_id).await?.ok_or_else(|| anyhow!("card {} has no draft", card_id))?;
+        let mut draft = self.claim(card_id, &current.draft_id, DraftState::Discarded).await?;
+        if !is_imap_id(&draft.message_id) {
+            let mut client = GmailClient::from_env_with_db(self.sqlite_pool.clone()).await;
+            client.delete_draft(&draft.draft_id).await?;
+        }
+        draft.state = DraftState::Discarded;
+        Ok(draft)
+    }
+
+    // Moves the open draft `draft_id` to `to` in one conditional update, so two requests
+    // can't both act on it
+    async fn claim(&self, card_id: Uuid, draft_id: &str, to: DraftState) -> Result<CardDraft> {
+        let repo = self.repo()?;
+        if repo.transition(card_id, draft_id, DraftState::Drafted, to).await? {
+            return repo.get(card_id).await?.ok_or_else(|| anyhow!("card {} has no draft", card_id));
+        }
+        match repo.get(card_id).await? {
+            None => bail!("card {} has no draft", card_id),
+            Some(d) if d.draft_id != draft_id => bail!("draft {} was replaced by {}; review it again before sending", draft_id, d.draft_id),
+            Some(d) => bail!("draft {} is already {:?}", d.draft_id, d.state),
+        }
+    }
+
+    // Puts each card's draft on its metadata; an open draft also offers Send/Discard
+    pub async fn attach(&self, cards: &mut [Card]) {
+        let Some(repo) = &self.repo else { return };
+        let ids: Vec<Uuid> = cards.iter().map(|c| c.id).collect();
+        let drafts = match repo.for_cards(&ids).await {
+            Ok(drafts) => drafts,
+            Err(e) => {
+                tracing::warn!("loading gmail drafts failed: {}", e);
+                return;
+            }
+        };
+        for (card_id, draft) in drafts {
+            let Some(card) = cards.iter_mut().find(|c| c.id == card_id) else { continue };
+            if draft.state == DraftState::Drafted {
+                for action in [CardAction::SendDraft, CardAction::DiscardDraft] {
+                    if !card.actions.contains(&action) {
+                        card.actions.push(action);
+                    }
+                }
+            }
+            card.metadata.get_or_insert_with(CardMetadata::default).draft = Some(draft);
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::sync::{broadcast, Mutex, Notify, RwLock};
+use crate::connectors::email::EmailMessage;
+use crate::connectors::gmail::{is_unread, GmailClient, GmailError, GmailHistory};
+use crate::services::gmail_cards::GmailCardService;
+use crate::services::parking::ParkingService;
+use crate::services::wake_registry::gmail_thread_reply;
+use crate::sqlite::repo::gmail_cache::{GmailCacheRepo, SOURCE_GMAIL};
+use crate::sse::SseEvent;
+
+// Keeps a local copy of the unread inbox. The first run lists unread mail; after that
+// only the history since the stored historyId is pulled. New messages are parsed and
+// classified once, here, so the feed reads cards straight from SQLite.
+
+// Unread messages taken on a full sync, matching what the feed shows
+const SYNC_WINDOW: u32 = 100;
+const DEFAULT_INTERVAL_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailSyncReport {
+    // Listed from scratch rather than from history
+    pub full: bool,
+    pub history_id: String,
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+}
+
+impl GmailSyncReport {
+    fn changed(&self) -> bool {
+        self.added + self.updated + self.removed > 0
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GmailSyncStatus {
+    pub at: DateTime<Utc>,
+    pub report: Option<GmailSyncReport>,
+    pub error: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct GmailSyncWorker {
+    sqlite_pool: Option<SqlitePool>,
+    parking: ParkingService,
+    sse_tx: broadcast::Sender<SseEvent>,
+    nudge: Arc<Notify>,
+    // One sync at a time, whether from the timer or a request
+    running: Arc<Mutex<()>>,
+    last: Arc<RwLock<Option<GmailSyncStatus>>>,
+}
+
+impl GmailSyncWorker {
+    pub fn new(sqlite_pool: Option<SqlitePool>, parking: ParkingService, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        Self {
+            sqlite_pool,
+            parking,
+            sse_tx,
+            nudge: Arc::new(Notify::new()),
+            running: Arc::new(Mutex::new(())),
+            last: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    // Syncs every GMAIL_SYNC_INTERVAL_SECS (default 60) and whenever nudged. Without
+    // SQLite there is nowhere to keep the cache, so nothing runs.
+    pub fn spawn(sqlite_pool: Option<SqlitePool>, parking: ParkingService, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        let worker = Self::new(sqlite_pool, parking, sse_tx);
+        if worker.sqlite_pool.is_some() {
+            let interval = std::env::var("GMAIL_SYNC_INTERVAL_SECS").ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_INTERVAL_SECS);
+            let runner = worker.clone();
+            tokio::spawn(async move { runner.run(Duration::from_secs(interval.max(1))).await });
+        }
+        worker
+    }
+
+    async fn run(&self, every: Duration) {
+        let mut ticker = tokio::time::interval(every);
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {}
+                _ = self.nudge.notified() => {}
+            }
+            if let Err(e) = self.sync().await {
+                tracing::debug!("gmail sync skipped: {}", e);
+            }
+        }
+    }
+
+    // Asks the worker to sync soon, e.g. after the mailbox was changed from here
+    pub fn nudge(&self) {
+        self.nudge.notify_one();
+    }
+
+    pub async fn status(&self) -> Option<GmailSyncStatus> {
+        self.last.read().await.clone()
+    }
+
+    pub async fn sync(&self) -> Result<GmailSyncReport> {
+        let _running = self.running.lock().await;
+        let result = self.sync_once().await;
+        *self.last.write().await = Some(GmailSyncStatus {
+            at: Utc::now(),
+            report: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+        if let Ok(report) = &result {
+            if report.changed() {
+                tracing::info!("gmail sync: +{} ~{} -{} (history {})", report.added, report.updated, report.removed, report.history_id);
+                let _ = self.sse_tx.send(SseEvent { event: "gmail.sync".into(), data: json!(report).to_string() });
+            }
+        }
+        result
+    }
+
+    async fn sync_once(&self) -> Result<GmailSyncReport> {
+        let pool = self.sqlite_pool.clone().ok_or_else(|| anyhow!("the Gmail cache needs SQLite"))?;
+        let cache = GmailCacheRepo::new(pool.clone());
+        let mut client = GmailClient::from_env_with_db(Some(pool.clone())).await;
+        client.ensure_access_token().await?;
+        let classifier = GmailCardService::new(Some(pool)).await;
+
+        let Some(start) = cache.history_id().await? else {
+            return self.full_sync(&mut client, &cache, &classifier).await;
+        };
+        match client.history(&start).await {
+            Ok(history) => self.apply(&client, &cache, &classifier, history).await,
+            Err(e) if matches!(e.downcast_ref::<GmailError>(), Some(GmailError::HistoryExpired(_))) => {
+                tracing::warn!("{}", e);
+                self.full_sync(&mut client, &cache, &classifier).await
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn full_sync(&self, client: &mut GmailClient, cache: &GmailCacheRepo, classifier: &GmailCardService) -> Result<GmailSyncReport> {
+        // Taken before listing, so anything arriving meanwhile shows up in the next delta
+        let history_id = client.history_id().await?;
+        let ids = client.list_unread_ids(SYNC_WINDOW).await?;
+        let mut report = GmailSyncReport { full: true, history_id, ..Default::default() };
+        for id in &ids {
+            if !cache.contains(id).await? {
+                self.store(client, cache, classifier, id).await?;
+                report.added += 1;
+            }
+        }
+        cache.keep_unread(SOURCE_GMAIL, &ids).await?;
+        cache.set_history_id(&report.history_id).await?;
+        Ok(report)
+    }
+
+    // Any failure leaves the stored historyId alone, so the same range is retried next time
+    async fn apply(&self, client: &GmailClient, cache: &GmailCacheRepo, classifier: &GmailCardService, history: GmailHistory) -> Result<GmailSyncReport> {
+        let mut report = GmailSyncReport { history_id: history.history_id, ..Default::default() };
+        for change in history.changes {
+            if change.deleted {
+                cache.delete(&change.id).await?;
+                report.removed += 1;
+                continue;
+            }
+            let cached = cache.contains(&change.id).await?;
+            // Sent mail and the like arrives read and isn't cached until it's marked unread
+            let unread = change.labels.as_deref().is_none_or(is_unread);
+            let marked_unread = change.labels.as_deref().is_some_and(is_unread);
+            if !cached && ((change.added && unread) || marked_unread) {
+                self.store(client, cache, classifier, &change.id).await?;
+                report.added += 1;
+            } else if let Some(labels) = &change.labels {
+                if cached && cache.set_labels(&change.id, labels).await? {
+                    report.updated += 1;
+                }
+            }
+        }
+        cache.set_history_id(&report.history_id).await?;
+        Ok(report)
+    }
+
+    async fn store(&self, client: &GmailClient, cache: &GmailCacheRepo, classifier: &GmailCardService, id: &str) -> Result<()> {
+        let message = client.get_message(id).await?;
+        let class = match message.is_unread() {
+            true => classifier.classify(&message).await,
+            false => None,
+        };
+        let received_at = GmailCardService::message_time(&message);
+        cache.upsert(&message, class.as_ref(), received_at).await?;
+        publish_thread_wake(&self.parking, &message).await;
+        Ok(())
+    }
+}
+
+// A new message on a thread wakes cards parked on it
+pub async fn publish_thread_wake(parking: &ParkingService, message: &EmailMessage) {
+    let Ok(sent_at) = DateTime::parse_from_rfc2822(message.date.trim()) else { return };
+    let event = gmail_thread_reply(&message.thread_id);
+    if let Err(e) = parking.publish(&event, Some(sent_at.with_timezone(&Utc))).await {
+        tracing::warn!("gmail thread wake failed for {}: {}", message.thread_id, e);
+    }
+}
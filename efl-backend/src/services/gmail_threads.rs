@@ -0,0 +1,132 @@
+use uuid::Uuid;
+use crate::models::{Card, EmailThread, EmailThreadMessage};
+
+// Gmail cards stand for a whole thread. The card id comes from the thread id, so a new
+// reply rebuilds the same card instead of adding another one.
+
+// Messages quoted in the summary, newest last
+const SUMMARY_MESSAGES: usize = 3;
+const SUMMARY_LINE_CHARS: usize = 140;
+
+// Openers that make a sentence a request even without a question mark
+const REQUEST_OPENERS: &[&str] = &["please", "could you", "can you", "would you", "let me know", "let us know"];
+
+pub fn thread_card_id(thread_id: &str) -> Uuid {
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, format!("gmail-thread:{}", thread_id).as_bytes())
+}
+
+pub fn thread_doc_id(thread_id: &str) -> String {
+    format!("gmail_thread_{}", thread_id)
+}
+
+// The message a reply to the card answers: the newest one in its thread
+pub fn latest_message_id(card: &Card) -> Option<&str> {
+    let thread = card.metadata.as_ref().and_then(|m| m.email_thread.as_ref());
+    match thread {
+        Some(thread) => thread.messages.last().map(|m| m.id.as_str()),
+        // Cards built before threading carried the message in the doc id
+        None => card.origin_object.as_ref()
+            .and_then(|o| o.doc_id.strip_prefix("gmail_"))
+            .filter(|id| *id != "batch" && !id.starts_with("thread_")),
+    }
+}
+
+// Sentence-ish pieces of a message body
+fn sentences(text: &str) -> Vec<&str> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends = match c {
+            '\n' => true,
+            '.' | '?' | '!' => chars.peek().is_none_or(|(_, next)| next.is_whitespace()),
+            _ => false,
+        };
+        if ends {
+            let end = i + c.len_utf8();
+            out.push(text[start..end].trim());
+            start = end;
+        }
+    }
+    out.push(text[start..].trim());
+    out.retain(|s| s.len() > 1);
+    out
+}
+
+fn clip(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => format!("{}...", text[..end].trim_end()),
+        None => text.to_string(),
+    }
+}
+
+fn is_ask(sentence: &str) -> bool {
+    let lower = sentence.to_lowercase();
+    sentence.ends_with('?') || REQUEST_OPENERS.iter().any(|opener| lower.starts_with(opener))
+}
+
+// Assembles the thread from (message, body text) pairs in any order; `time` orders them
+pub fn build_thread<T: Ord + Copy>(thread_id: &str, mut messages: Vec<(EmailThreadMessage, String, T)>) -> EmailThread {
+    messages.sort_by_key(|(_, _, time)| *time);
+
+    let mut participants: Vec<String> = Vec::new();
+    for (message, _, _) in &messages {
+        if !participants.contains(&message.sender) {
+            participants.push(message.sender.clone());
+        }
+    }
+
+    let skipped = messages.len().saturating_sub(SUMMARY_MESSAGES);
+    let mut lines: Vec<String> = Vec::new();
+    if skipped > 0 {
+        lines.push(format!("{} earlier message(s)", skipped));
+    }
+    for (message, text, _) in messages.iter().skip(skipped) {
+        let first = sentences(text).into_iter().next().unwrap_or(&message.snippet);
+        lines.push(format!("{}: {}", message.sender, clip(first, SUMMARY_LINE_CHARS)));
+    }
+
+    let latest_ask = messages.iter().rev()
+        .find_map(|(message, text, _)| {
+            let ask = sentences(text).into_iter().rev().find(|s| is_ask(s))?;
+            Some(format!("{}: {}", message.sender, clip(ask, SUMMARY_LINE_CHARS)))
+        });
+
+    EmailThread {
+        thread_id: thread_id.to_string(),
+        messages: messages.into_iter().map(|(message, _, _)| message).collect(),
+        participants,
+        summary: lines.join("\n"),
+        latest_ask,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, sender: &str) -> EmailThreadMessage {
+        EmailThreadMessage {
+            id: id.to_string(),
+            from: format!("{sender} <{}@example.com>", sender.to_lowercase()),
+            sender: sender.to_string(),
+            date: String::new(),
+            snippet: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_thread_summary_and_latest_ask() {
+        let thread = build_thread("t1", vec![
+            (message("m3", "Ana"), "Thanks! I'll take a look.".to_string(), 3),
+            (message("m1", "Ana"), "Here is the Q3 plan. Can you review the budget section?".to_string(), 1),
+            (message("m2", "Bo"), "Looping in finance.\nPlease confirm the headcount by Friday.".to_string(), 2),
+        ]);
+        assert_eq!(thread.messages.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["m1", "m2", "m3"]);
+        assert_eq!(thread.participants, vec!["Ana", "Bo"]);
+        assert_eq!(thread.summary, "Ana: Here is the Q3 plan.\nBo: Looping in finance.\nAna: Thanks!");
+        assert_eq!(thread.latest_ask.as_deref(), Some("Bo: Please confirm the headcount by Friday."));
+        assert_eq!(thread_card_id("t1"), thread_card_id("t1"));
+        assert_ne!(thread_card_id("t1"), thread_card_id("t2"));
+    }
+}
@@ -0,0 +1,128 @@
+use std::time::{Duration, Instant};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::SqlitePool;
+use tokio::sync::broadcast;
+use crate::connectors::imap::{message_id, normalize, ImapClient, ImapConfig, MailboxState};
+use crate::services::gmail_cards::GmailCardService;
+use crate::services::gmail_sync::publish_thread_wake;
+use crate::services::parking::ParkingService;
+use crate::sqlite::repo::gmail_cache::{GmailCacheRepo, SOURCE_IMAP};
+use crate::sse::SseEvent;
+
+// Keeps the message cache in step with an IMAP mailbox. Stays connected and IDLEs between
+// syncs, so new mail shows up within seconds; each sync only fetches UIDs above the last one
+// seen. Messages land in the same cache as Gmail's, so the feed builds cards from both.
+
+// Unseen messages taken on a first sync, matching the Gmail window
+const SYNC_WINDOW: usize = 100;
+// Servers drop IDLE after 30 minutes (RFC 2177), so re-issue it before that
+const IDLE_WAIT: Duration = Duration::from_secs(25 * 60);
+const MIN_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImapSyncReport {
+    // Started over: no stored state, or the mailbox's UIDVALIDITY changed
+    pub full: bool,
+    pub uid_validity: u32,
+    pub last_uid: u32,
+    pub added: usize,
+}
+
+#[derive(Clone)]
+pub struct ImapSyncWorker {
+    config: ImapConfig,
+    cache: GmailCacheRepo,
+    parking: ParkingService,
+    sse_tx: broadcast::Sender<SseEvent>,
+}
+
+impl ImapSyncWorker {
+    // Runs only when IMAP_HOST is set and SQLite is available for the cache
+    pub fn spawn(sqlite_pool: Option<SqlitePool>, parking: ParkingService, sse_tx: broadcast::Sender<SseEvent>) {
+        let (Some(config), Some(pool)) = (ImapConfig::from_env(), sqlite_pool) else { return };
+        let worker = Self { config, cache: GmailCacheRepo::new(pool), parking, sse_tx };
+        tokio::spawn(async move { worker.run().await });
+    }
+
+    // Reconnects with backoff whenever the session drops
+    async fn run(&self) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.session().await {
+                tracing::warn!("imap sync for {} stopped: {}", self.config.account(), e);
+            }
+            if started.elapsed() > MAX_BACKOFF {
+                backoff = MIN_BACKOFF;
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+
+    async fn session(&self) -> Result<()> {
+        let mut client = ImapClient::connect(&self.config).await?;
+        loop {
+            // Re-selected each round for a fresh UIDNEXT
+            let mailbox = client.select(&self.config.mailbox).await?;
+            let report = self.sync(&mut client, &mailbox).await?;
+            if report.added > 0 {
+                tracing::info!("imap sync: +{} (uid {})", report.added, report.last_uid);
+                let _ = self.sse_tx.send(SseEvent { event: "imap.sync".into(), data: json!(report).to_string() });
+            }
+            client.idle(IDLE_WAIT).await?;
+        }
+    }
+
+    async fn sync(&self, client: &mut ImapClient, mailbox: &MailboxState) -> Result<ImapSyncReport> {
+        let account = self.config.account();
+        let stored = self.cache.imap_state(&account, &self.config.mailbox).await?;
+        let last_uid = stored.filter(|(validity, _)| *validity == mailbox.uid_validity).map(|(_, uid)| uid);
+        let mut report = ImapSyncReport {
+            full: last_uid.is_none(),
+            uid_validity: mailbox.uid_validity,
+            last_uid: last_uid.unwrap_or(0),
+            added: 0,
+        };
+
+        let unseen = client.uid_search("UNSEEN").await?;
+        let fetched = match last_uid {
+            Some(last) => client.uid_fetch(&format!("{}:*", last + 1)).await?,
+            None => {
+                let window = &unseen[unseen.len().saturating_sub(SYNC_WINDOW)..];
+                match window.is_empty() {
+                    true => vec![],
+                    false => client.uid_fetch(&window.iter().map(u32::to_string).collect::<Vec<_>>().join(",")).await?,
+                }
+            }
+        };
+        let classifier = GmailCardService::new(Some(self.cache.pool.clone())).await;
+        // "n:*" always returns the newest message, even when its UID is below n
+        for message in fetched.iter().filter(|m| m.uid > report.last_uid) {
+            let id = message_id(mailbox.uid_validity, message.uid);
+            let Some(parsed) = normalize(id, &message.raw, &message.flags) else {
+                tracing::warn!("imap message {} couldn't be parsed", message.uid);
+                continue;
+            };
+            let class = match parsed.is_unread() {
+                true => classifier.classify(&parsed).await,
+                false => None,
+            };
+            self.cache.upsert(&parsed, class.as_ref(), GmailCardService::message_time(&parsed)).await?;
+            publish_thread_wake(&self.parking, &parsed).await;
+            report.added += 1;
+        }
+        let newest = fetched.iter().map(|m| m.uid).max().unwrap_or(0);
+        report.last_uid = report.last_uid.max(newest).max(mailbox.uid_next.saturating_sub(1));
+
+        // Anything read in another client drops out of the feed
+        let unseen_ids: Vec<String> = unseen.iter().map(|uid| message_id(mailbox.uid_validity, *uid)).collect();
+        self.cache.keep_unread(SOURCE_IMAP, &unseen_ids).await?;
+        self.cache.set_imap_state(&account, &self.config.mailbox, report.uid_validity, report.last_uid).await?;
+        Ok(report)
+    }
+}
@@ -6,6 +6,9 @@ use crate::models::{WorkingSet, DocumentContext, Summary};
 use crate::memory::MemoryCache;
 use std::collections::HashMap;
 
+// Single-user dev mode: everyone shares one working set
+pub const CURRENT_WORKING_SET_ID: Uuid = Uuid::nil();
+
 pub struct MemoryService {
     db_pool: Option<PgPool>,
     cache: MemoryCache,
@@ -18,7 +21,7 @@ impl MemoryService {
     
     pub async fn get_current_working_set(&self) -> Result<WorkingSet> {
         // Try cache first
-        let working_set_id = Uuid::new_v4(); // In reality, get from session
+        let working_set_id = CURRENT_WORKING_SET_ID; // In reality, get from session
         
         if let Some(ws) = self.cache.get_working_set(&working_set_id).await {
             return Ok(ws);
@@ -47,12 +50,18 @@ impl MemoryService {
         let mut working_set = self.get_current_working_set().await?;
         
         if let Some(doc_id) = doc_id {
+            // Client edits to the same document invalidate pending undo/redo
+            let version = working_set.active_doc.as_ref()
+                .filter(|doc| doc.doc_id == doc_id)
+                .map(|doc| doc.version + 1)
+                .unwrap_or(0);
             working_set.active_doc = Some(DocumentContext {
                 doc_id: doc_id.clone(),
                 title: format!("Document {}", doc_id),
                 content: content.unwrap_or_default(),
                 focused_section,
                 last_blocks: vec![],
+                version,
             });
         }
         
@@ -62,6 +71,12 @@ impl MemoryService {
         Ok(working_set)
     }
     
+    pub async fn save_working_set(&self, mut working_set: WorkingSet) -> Result<WorkingSet> {
+        working_set.updated_at = Utc::now();
+        self.cache.set_working_set(working_set.id, working_set.clone()).await;
+        Ok(working_set)
+    }
+    
     pub async fn get_summary(&self, key: &str) -> Result<Option<Summary>> {
         // Try cache first
         if let Some(summary) = self.cache.get_summary(key).await {
@@ -71,4 +86,9 @@ impl MemoryService {
         // In a real implementation, fetch from database
         Ok(None)
     }
+    
+    pub async fn put_summary(&self, key: &str, summary: Summary) -> Result<Summary> {
+        self.cache.set_summary(key.to_string(), summary.clone()).await;
+        Ok(summary)
+    }
 }
@@ -1,10 +1,29 @@
 pub mod intent;
 pub mod card;
+pub mod card_fsm;
+pub mod breakin_inbox;
+pub mod card_projector;
+pub mod diff;
 pub mod feed;
 pub mod memory;
 pub mod trace;
+pub mod undo;
+pub mod wake_registry;
 pub mod mock_data;
 pub mod parking;
+pub mod park_time;
+pub mod ranking;
+pub mod triage;
+pub mod focus;
 pub mod telemetry;
 pub mod altimeter;
-pub mod gmail_cards;
\ No newline at end of file
+pub mod altitude;
+pub mod altitude_policy;
+pub mod gmail_cards;
+pub mod gmail_actions;
+pub mod gmail_drafts;
+pub mod gmail_sync;
+
+pub mod gmail_threads;
+pub mod imap_sync;
+pub mod slack_breakins;
@@ -0,0 +1,306 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+
+// Resolves what people type into the command bar ("after lunch", "tomorrow 9am",
+// "next Monday", "end of day Friday") into a wake instant, in the user's timezone.
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum ParkTimeError {
+    #[error("couldn't understand wake time \"{0}\"")]
+    Unrecognized(String),
+    #[error("wake time \"{0}\" is in the past")]
+    InPast(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkingHours {
+    pub timezone: Tz,
+    pub day_start: NaiveTime,
+    pub lunch_end: NaiveTime,
+    pub day_end: NaiveTime,
+}
+
+impl Default for WorkingHours {
+    fn default() -> Self {
+        Self {
+            timezone: Tz::UTC,
+            day_start: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            lunch_end: NaiveTime::from_hms_opt(13, 0, 0).unwrap(),
+            day_end: NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+        }
+    }
+}
+
+impl WorkingHours {
+    // USER_TIMEZONE (IANA name), WORK_DAY_START, LUNCH_END, WORK_DAY_END ("HH:MM")
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        let time = |key: &str, default: NaiveTime| {
+            std::env::var(key).ok()
+                .and_then(|v| NaiveTime::parse_from_str(v.trim(), "%H:%M").ok())
+                .unwrap_or(default)
+        };
+        Self {
+            timezone: std::env::var("USER_TIMEZONE").ok()
+                .and_then(|tz| tz.trim().parse().ok())
+                .unwrap_or(defaults.timezone),
+            day_start: time("WORK_DAY_START", defaults.day_start),
+            lunch_end: time("LUNCH_END", defaults.lunch_end),
+            day_end: time("WORK_DAY_END", defaults.day_end),
+        }
+    }
+
+    // The instant `time` on `date` falls at in the user's timezone
+    fn at(&self, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+        let naive = date.and_time(time);
+        match self.timezone.from_local_datetime(&naive).earliest() {
+            Some(local) => local.with_timezone(&Utc),
+            // Skipped by a DST jump: use the first valid minute after it
+            None => self.at(date, time + Duration::hours(1)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Day {
+    // No day given: today, or the next working day once the time has passed
+    Today,
+    Date(NaiveDate),
+    // A bare weekday: this week's, or next week's once it has passed
+    Weekday(NaiveDate),
+}
+
+pub fn parse_wake_time(input: &str, now: DateTime<Utc>, hours: &WorkingHours) -> Result<DateTime<Utc>, ParkTimeError> {
+    let unrecognized = || ParkTimeError::Unrecognized(input.to_string());
+    if let Ok(at) = DateTime::parse_from_rfc3339(input.trim()) {
+        return Ok(at.with_timezone(&Utc));
+    }
+
+    let text = input.trim().to_lowercase().replace(',', " ");
+    let mut words: Vec<&str> = text.split_whitespace()
+        .filter(|w| !matches!(*w, "at" | "on" | "until" | "by" | "the"))
+        .collect();
+    if words.is_empty() {
+        return Err(unrecognized());
+    }
+
+    if let Some(delta) = parse_relative(&words) {
+        // An amount too large to be a date is as good as unrecognized
+        let at = delta.and_then(|d| now.checked_add_signed(d)).ok_or_else(unrecognized)?;
+        if at <= now {
+            return Err(ParkTimeError::InPast(input.to_string()));
+        }
+        return Ok(at);
+    }
+
+    let today = now.with_timezone(&hours.timezone).date_naive();
+    let mut day = Day::Today;
+    let mut time = None;
+
+    while !words.is_empty() {
+        let consumed = if let Some((d, n)) = parse_day(&words, today) {
+            day = d;
+            n
+        } else if let Some((t, n)) = parse_time_of_day(&words, hours) {
+            time = Some(t);
+            n
+        } else {
+            return Err(unrecognized());
+        };
+        words.drain(..consumed);
+    }
+
+    let time = time.unwrap_or(hours.day_start);
+    let at = match day {
+        Day::Date(date) => hours.at(date, time),
+        Day::Weekday(date) => {
+            let at = hours.at(date, time);
+            if at > now { at } else { hours.at(date + Duration::weeks(1), time) }
+        }
+        Day::Today => {
+            let at = hours.at(today, time);
+            if at > now { at } else { hours.at(next_working_day(today), time) }
+        }
+    };
+    if at <= now {
+        return Err(ParkTimeError::InPast(input.to_string()));
+    }
+    Ok(at)
+}
+
+// Finds "due Friday", "by tomorrow 5pm", "deadline: EOD" in free text and resolves the phrase
+pub fn find_deadline(text: &str, now: DateTime<Utc>, hours: &WorkingHours) -> Option<DateTime<Utc>> {
+    let lowered = text.to_lowercase();
+    let words: Vec<&str> = lowered.split(|c: char| c.is_whitespace() || c == ',' || c == '.')
+        .filter(|w| !w.is_empty())
+        .collect();
+    words.iter().enumerate()
+        .filter(|(_, w)| matches!(w.trim_end_matches(':'), "due" | "by" | "before" | "deadline"))
+        .find_map(|(i, _)| {
+            // Longest phrase first so "friday 5pm" wins over "friday"
+            (1..=4).rev().find_map(|len| {
+                let phrase = words.get(i + 1..i + 1 + len)?.join(" ");
+                parse_wake_time(&phrase, now, hours).ok()
+            })
+        })
+}
+
+// "in 30 minutes", "in 2h", "45m", "3 days". Some(None) when the amount is out of range.
+fn parse_relative(words: &[&str]) -> Option<Option<TimeDelta>> {
+    let words = match words.first() {
+        Some(&"in") => &words[1..],
+        _ => words,
+    };
+    let (amount, unit) = match words {
+        [single] => {
+            let split = single.find(|c: char| !c.is_ascii_digit())?;
+            (single[..split].parse::<i64>().ok()?, &single[split..])
+        }
+        [amount, unit] => (
+            match *amount { "a" | "an" => 1, n => n.parse::<i64>().ok()? },
+            *unit,
+        ),
+        _ => return None,
+    };
+    let delta = match unit {
+        "m" | "min" | "mins" | "minute" | "minutes" => TimeDelta::try_minutes(amount),
+        "h" | "hr" | "hrs" | "hour" | "hours" => TimeDelta::try_hours(amount),
+        "d" | "day" | "days" => TimeDelta::try_days(amount),
+        "w" | "week" | "weeks" => TimeDelta::try_weeks(amount),
+        _ => return None,
+    };
+    Some(delta)
+}
+
+// Returns the day and how many words it used
+fn parse_day(words: &[&str], today: NaiveDate) -> Option<(Day, usize)> {
+    match words {
+        ["today", ..] => Some((Day::Date(today), 1)),
+        ["tomorrow", ..] | ["tmrw", ..] => Some((Day::Date(today + Duration::days(1)), 1)),
+        ["next", "week", ..] => Some((Day::Date(next_weekday(today, Weekday::Mon)), 2)),
+        ["end", "of", "week", ..] => Some((Day::Weekday(this_or_next_weekday(today, Weekday::Fri)), 3)),
+        ["next", name, ..] => parse_weekday(name).map(|wd| (Day::Date(next_weekday(today, wd)), 2)),
+        ["this", name, ..] => parse_weekday(name).map(|wd| (Day::Weekday(this_or_next_weekday(today, wd)), 2)),
+        [name, ..] => parse_weekday(name).map(|wd| (Day::Weekday(this_or_next_weekday(today, wd)), 1)),
+        [] => None,
+    }
+}
+
+// Returns the time and how many words it used
+fn parse_time_of_day(words: &[&str], hours: &WorkingHours) -> Option<(NaiveTime, usize)> {
+    match words {
+        ["after", "lunch", ..] => Some((hours.lunch_end, 2)),
+        ["end", "of", "day", ..] => Some((hours.day_end, 3)),
+        ["eod", ..] | ["cob", ..] => Some((hours.day_end, 1)),
+        ["morning", ..] => Some((hours.day_start, 1)),
+        ["first", "thing", ..] => Some((hours.day_start, 2)),
+        ["afternoon", ..] => Some((hours.lunch_end, 1)),
+        ["noon", ..] | ["midday", ..] => Some((NaiveTime::from_hms_opt(12, 0, 0)?, 1)),
+        [clock, "am" | "pm", ..] => parse_clock(&format!("{}{}", clock, words[1])).map(|t| (t, 2)),
+        [clock, ..] => parse_clock(clock).map(|t| (t, 1)),
+        [] => None,
+    }
+}
+
+// "9am", "3:30pm", "15:00", "9"
+fn parse_clock(s: &str) -> Option<NaiveTime> {
+    let (digits, meridiem) = if let Some(d) = s.strip_suffix("am") {
+        (d, Some(false))
+    } else if let Some(d) = s.strip_suffix("pm") {
+        (d, Some(true))
+    } else {
+        (s, None)
+    };
+    let (h, m) = match digits.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (digits.parse::<u32>().ok()?, 0),
+    };
+    let h = match meridiem {
+        Some(pm) if (1..=12).contains(&h) => h % 12 + if pm { 12 } else { 0 },
+        Some(_) => return None,
+        None => h,
+    };
+    NaiveTime::from_hms_opt(h, m, 0)
+}
+
+// "mon", "thurs", "friday"; any prefix of at least three letters
+fn parse_weekday(name: &str) -> Option<Weekday> {
+    const DAYS: [(&str, Weekday); 7] = [
+        ("monday", Weekday::Mon), ("tuesday", Weekday::Tue), ("wednesday", Weekday::Wed),
+        ("thursday", Weekday::Thu), ("friday", Weekday::Fri), ("saturday", Weekday::Sat),
+        ("sunday", Weekday::Sun),
+    ];
+    if name.len() < 3 {
+        return None;
+    }
+    DAYS.iter().find(|(full, _)| full.starts_with(name)).map(|(_, day)| *day)
+}
+
+// Strictly after `from`
+fn next_weekday(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (weekday.num_days_from_monday() as i64 - from.weekday().num_days_from_monday() as i64).rem_euclid(7);
+    from + Duration::days(if ahead == 0 { 7 } else { ahead })
+}
+
+fn this_or_next_weekday(from: NaiveDate, weekday: Weekday) -> NaiveDate {
+    if from.weekday() == weekday { from } else { next_weekday(from, weekday) }
+}
+
+fn next_working_day(from: NaiveDate) -> NaiveDate {
+    let mut day = from + Duration::days(1);
+    while matches!(day.weekday(), Weekday::Sat | Weekday::Sun) {
+        day += Duration::days(1);
+    }
+    day
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hours() -> WorkingHours {
+        WorkingHours { timezone: chrono_tz::America::New_York, ..WorkingHours::default() }
+    }
+
+    // Wednesday 2024-06-12 10:30 in New York (EDT, UTC-4)
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 6, 12, 14, 30, 0).unwrap()
+    }
+
+    fn local(input: &str) -> String {
+        parse_wake_time(input, now(), &hours()).unwrap()
+            .with_timezone(&hours().timezone)
+            .format("%a %Y-%m-%d %H:%M")
+            .to_string()
+    }
+
+    #[test]
+    fn test_phrases() {
+        assert_eq!(local("after lunch"), "Wed 2024-06-12 13:00");
+        assert_eq!(local("tomorrow 9am"), "Thu 2024-06-13 09:00");
+        assert_eq!(local("tomorrow at 3:30 pm"), "Thu 2024-06-13 15:30");
+        assert_eq!(local("next Monday"), "Mon 2024-06-17 09:00");
+        assert_eq!(local("end of day Friday"), "Fri 2024-06-14 17:00");
+        assert_eq!(local("Friday EOD"), "Fri 2024-06-14 17:00");
+        assert_eq!(local("next week"), "Mon 2024-06-17 09:00");
+        // Today's 9am has passed, so a bare "Wednesday" means next week's
+        assert_eq!(local("wednesday"), "Wed 2024-06-19 09:00");
+        assert_eq!(local("wed 4pm"), "Wed 2024-06-12 16:00");
+        assert_eq!(local("in 45 minutes"), "Wed 2024-06-12 11:15");
+        assert_eq!(local("2h"), "Wed 2024-06-12 12:30");
+        // Times already passed today roll to the next working day
+        assert_eq!(local("9am"), "Thu 2024-06-13 09:00");
+    }
+
+    #[test]
+    fn test_rejects_nonsense() {
+        assert!(matches!(parse_wake_time("whenever", now(), &hours()), Err(ParkTimeError::Unrecognized(_))));
+        assert!(matches!(parse_wake_time("today 8am", now(), &hours()), Err(ParkTimeError::InPast(_))));
+        assert!(parse_wake_time("monsoon", now(), &hours()).is_err());
+        assert!(matches!(parse_wake_time("0 minutes", now(), &hours()), Err(ParkTimeError::InPast(_))));
+        // Amounts past the end of time are refused, not panicked on
+        assert!(matches!(parse_wake_time("in 999999999999 days", now(), &hours()), Err(ParkTimeError::Unrecognized(_))));
+        assert!(matches!(parse_wake_time("99999999999 weeks", now(), &hours()), Err(ParkTimeError::Unrecognized(_))));
+        assert_eq!(find_deadline("due in 999999999999 days", now(), &hours()), None);
+    }
+}
@@ -1,156 +1,393 @@
 use std::sync::Arc;
-use tokio::sync::RwLock;
-use tokio::time::{interval, Duration};
+use tokio::sync::{broadcast, Notify, RwLock};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 use anyhow::Result;
 use std::collections::HashMap;
-use crate::models::{Card, CardStatus, CardType, CardContent, ParkedItem};
+use sqlx::SqlitePool;
+use crate::models::{Card, CardStatus, CardType, CardContent, ParkedItem, WakeCondition};
+use crate::services::card_fsm::{CardEvent, CardFsm, FsmError};
+use crate::services::park_time::{self, ParkTimeError, WorkingHours};
+use crate::services::wake_registry::{self, WakeRegistry};
+use crate::sse::SseEvent;
+use crate::sqlite::repo::parked::ParkedRepo;
+use crate::sqlite::repo::slack_map::SlackMapRepo;
+use crate::sqlite::repo::wakes::WakesRepo;
+
+// How long a woken card keeps its "just woke" boost in the feed
+const WOKEN_BOOST_MINUTES: i64 = 60;
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum SnoozeError {
+    #[error("card {0} isn't parked")]
+    NotParked(Uuid),
+    // Negative, or past what a timestamp can hold
+    #[error("snooze of {0} minutes is out of range")]
+    OutOfRange(i64),
+}
+
+// card id -> the woken card and when it woke
+type WokenCards = HashMap<Uuid, (Card, DateTime<Utc>)>;
+
+#[derive(Debug, Clone)]
+struct ParkedEntry {
+    // The card as it was before parking, restored as-is on unpark
+    original: Card,
+    wake_time: DateTime<Utc>,
+    reason: String,
+    // Events that wake the card before `wake_time`
+    events: Vec<String>,
+}
+
+impl ParkedEntry {
+    fn conditions(&self) -> Vec<WakeCondition> {
+        let mut conditions = vec![WakeCondition::Time(self.wake_time)];
+        conditions.extend(self.events.iter().map(|event| {
+            match event.strip_prefix("memory.summary_updated:") {
+                Some(key) => WakeCondition::MemoryChange(key.to_string()),
+                None => WakeCondition::Event(event.clone()),
+            }
+        }));
+        conditions
+    }
+
+    // What the feed shows while the card is parked
+    fn parked_card(&self) -> Card {
+        let mut card = self.original.clone();
+        card.status = CardStatus::Parked;
+        card.card_type = CardType::Parked;
+        card.content = CardContent::Parked {
+            original_card_id: self.original.id,
+            wake_time: self.wake_time,
+            wake_reason: self.reason.clone(),
+        };
+        card
+    }
+}
+
+#[derive(Clone)]
+struct ParkingStore {
+    parked: ParkedRepo,
+    wakes: WakesRepo,
+    slack: SlackMapRepo,
+    fsm: CardFsm,
+}
 
 #[derive(Clone)]
 pub struct ParkingService {
-    parked_cards: Arc<RwLock<HashMap<Uuid, (Card, DateTime<Utc>, String)>>>,
+    parked_cards: Arc<RwLock<HashMap<Uuid, ParkedEntry>>>,
+    store: Option<ParkingStore>,
+    registry: Arc<RwLock<WakeRegistry>>,
+    // Recently woken cards and when they woke, for the feed to surface
+    woken: Arc<RwLock<WokenCards>>,
+    hours: WorkingHours,
+    sse_tx: broadcast::Sender<SseEvent>,
+    // Signalled whenever a wake is added or moved so the scheduler re-plans its sleep
+    schedule_changed: Arc<Notify>,
 }
 
 impl ParkingService {
     pub fn new() -> Self {
+        let (sse_tx, _) = broadcast::channel(16);
+        Self::new_with_sqlite(None, sse_tx)
+    }
+
+    pub fn new_with_sqlite(sqlite_pool: Option<SqlitePool>, sse_tx: broadcast::Sender<SseEvent>) -> Self {
         let service = Self {
             parked_cards: Arc::new(RwLock::new(HashMap::new())),
+            registry: Arc::new(RwLock::new(WakeRegistry::default())),
+            woken: Arc::new(RwLock::new(HashMap::new())),
+            hours: WorkingHours::from_env(),
+            sse_tx,
+            schedule_changed: Arc::new(Notify::new()),
+            store: sqlite_pool.map(|pool| ParkingStore {
+                parked: ParkedRepo::new(pool.clone()),
+                wakes: WakesRepo::new(pool.clone()),
+                slack: SlackMapRepo::new(pool.clone()),
+                fsm: CardFsm::new(pool),
+            }),
         };
         
         // Start the wake scheduler
         let service_clone = service.clone();
         tokio::spawn(async move {
+            if let Err(e) = service_clone.rehydrate().await {
+                tracing::warn!("Failed to restore parked cards: {}", e);
+            }
             service_clone.wake_scheduler().await;
         });
         
         service
     }
+
+    // Reloads parked cards with pending wakes from SQLite after a restart
+    pub async fn rehydrate(&self) -> Result<usize> {
+        let Some(store) = &self.store else { return Ok(0) };
+        let mut restored = HashMap::new();
+        for wake in store.wakes.pending().await? {
+            match store.parked.get(&wake.card_id).await {
+                Ok(Some((original, reason))) => {
+                    restored.insert(original.id, ParkedEntry { original, wake_time: wake.wake_at, reason, events: vec![] });
+                }
+                Ok(None) => tracing::warn!("Pending wake {} has no parked card {}", wake.id, wake.card_id),
+                Err(e) => tracing::warn!("Skipping parked card {}: {}", wake.card_id, e),
+            }
+        }
+        let mut registry = self.registry.write().await;
+        for sub in store.wakes.pending_subscriptions().await? {
+            let Some(entry) = Uuid::parse_str(&sub.card_id).ok().and_then(|id| restored.get_mut(&id)) else { continue };
+            registry.subscribe(entry.original.id, sub.event.clone(), sub.created_at);
+            entry.events.push(sub.event);
+        }
+        drop(registry);
+        let count = restored.len();
+        self.parked_cards.write().await.extend(restored);
+        tracing::info!("Restored {} parked cards", count);
+        Ok(count)
+    }
     
-    pub async fn park_card(&self, mut card: Card, wake_time: DateTime<Utc>, reason: String) -> Result<Uuid> {
+    // Turns "after lunch", "tomorrow 9am", "end of day Friday" etc. into a wake instant
+    pub fn resolve_wake_time(&self, phrase: &str) -> Result<DateTime<Utc>, ParkTimeError> {
+        park_time::parse_wake_time(phrase, Utc::now(), &self.hours)
+    }
+
+    pub fn timezone(&self) -> &str {
+        self.hours.timezone.name()
+    }
+
+    pub async fn park_card(&self, card: Card, wake_time: DateTime<Utc>, reason: String) -> Result<Uuid> {
+        self.park_card_with_conditions(card, wake_time, vec![], reason).await
+    }
+
+    // Parks until `wake_time` or any of `conditions`, whichever comes first. Gmail cards and
+    // cards mapped to a Slack thread also wake on replies to their thread.
+    pub async fn park_card_with_conditions(
+        &self,
+        card: Card,
+        wake_time: DateTime<Utc>,
+        conditions: Vec<WakeCondition>,
+        reason: String,
+    ) -> Result<Uuid> {
         let original_card_id = card.id;
-        let original_content = card.content.clone();
-        
-        // Convert to parked card
-        card.status = CardStatus::Parked;
-        card.card_type = CardType::Parked;
-        card.content = CardContent::Parked {
-            original_card_id,
-            wake_time,
-            wake_reason: reason.clone(),
-        };
-        
-        // Store in parked collection
-        let mut parked = self.parked_cards.write().await;
-        parked.insert(original_card_id, (card, wake_time, reason));
+        let key = original_card_id.to_string();
 
-        // Persist wake in SQLite v0 if configured
-        let sqlite_url = std::env::var("SQLITE_URL").ok().or_else(|| {
-            let db = std::env::var("DATABASE_URL").unwrap_or_default();
-            if db.starts_with("sqlite://") { Some(db) } else { None }
-        });
-        if let Some(database_url) = sqlite_url {
-            if database_url.starts_with("sqlite://") {
-                if let Ok(sqlite) = crate::sqlite::db::SqliteDb::connect(&database_url).await {
-                    let repo = crate::sqlite::repo::wakes::WakesRepo::new(sqlite.pool);
-                    let _ = repo
-                        .schedule_time_wake(
-                            &original_card_id.to_string(),
-                            &wake_time.to_rfc3339(),
-                            "time",
-                        )
-                        .await;
-                }
+        let mut events: Vec<String> = conditions.iter().filter_map(wake_registry::event_name).collect();
+        if let Some(origin) = card.origin_object.as_ref().filter(|o| o.doc_id.starts_with("gmail_")) {
+            if let Some(thread_id) = &origin.block_id {
+                events.push(wake_registry::gmail_thread_reply(thread_id));
             }
         }
+        if let Some(store) = &self.store {
+            if let Some((channel, thread_ts)) = store.slack.find_thread_by_card(&key).await? {
+                events.push(wake_registry::slack_thread_reply(&channel, &thread_ts));
+            }
+        }
+        events.sort();
+        events.dedup();
+
+        if let Some(store) = &self.store {
+            // Cards that were never stored (e.g. demo cards) park without an FSM transition
+            let meta = serde_json::json!({ "wake_time": wake_time, "reason": reason });
+            match store.fsm.apply(original_card_id, CardEvent::Park, "user", Some(meta)).await {
+                Ok(_) | Err(FsmError::NotFound(_)) => {}
+                Err(e) => return Err(e.into()),
+            }
+            store.parked.save(&card, &reason).await?;
+            store.wakes.mark_fired(&key).await?;
+            store.wakes.schedule_time_wake(&key, &wake_time.to_rfc3339(), "time").await?;
+            for event in &events {
+                store.wakes.subscribe(&key, event).await?;
+            }
+        }
+
+        let now = Utc::now();
+        let mut registry = self.registry.write().await;
+        registry.remove_card(original_card_id);
+        for event in &events {
+            registry.subscribe(original_card_id, event.clone(), now);
+        }
+        drop(registry);
+        self.woken.write().await.remove(&original_card_id);
+        
+        // Store in parked collection
+        let mut parked = self.parked_cards.write().await;
+        parked.insert(original_card_id, ParkedEntry { original: card, wake_time, reason, events });
+        self.schedule_changed.notify_one();
 
         Ok(original_card_id)
     }
     
     pub async fn unpark_card(&self, card_id: Uuid) -> Result<Option<Card>> {
-        let mut parked = self.parked_cards.write().await;
-        
-        if let Some((mut card, _, _)) = parked.remove(&card_id) {
-            // Restore original card type and status
-            card.status = CardStatus::Active;
-            
-            // In a real implementation, we'd restore the original card type and content
-            // For now, we'll convert it back to a DoNow card as an example
-            if let CardContent::Parked { original_card_id, .. } = &card.content {
-                // Restore original content (would be fetched from DB in real impl)
-                card.card_type = CardType::DoNow;
-                // This is a simplified restoration - in practice, we'd store and restore the original content
+        self.unpark(card_id, "user").await
+    }
+
+    async fn unpark(&self, card_id: Uuid, actor: &str) -> Result<Option<Card>> {
+        let entry = self.parked_cards.write().await.remove(&card_id);
+        self.registry.write().await.remove_card(card_id);
+        let key = card_id.to_string();
+
+        let original = match (entry, &self.store) {
+            (Some(entry), _) => Some(entry.original),
+            (None, Some(store)) => store.parked.get(&key).await?.map(|(card, _)| card),
+            (None, None) => None,
+        };
+        let Some(mut card) = original else { return Ok(None) };
+
+        if let Some(store) = &self.store {
+            store.parked.remove(&key).await?;
+            store.wakes.mark_fired(&key).await?;
+            match store.fsm.apply(card_id, CardEvent::Wake, actor, None).await {
+                Ok(_) | Err(FsmError::NotFound(_)) => {}
+                Err(e) => tracing::warn!("Unparked card {} without waking it: {}", card_id, e),
             }
-            
-            Ok(Some(card))
-        } else {
-            Ok(None)
         }
+
+        // Original type and content come back untouched; only the status moves on
+        card.status = CardStatus::Active;
+        Ok(Some(card))
     }
     
     pub async fn get_parked_cards(&self) -> Vec<(Card, DateTime<Utc>, String)> {
         let parked = self.parked_cards.read().await;
-        parked.values().cloned().collect()
+        parked.values()
+            .map(|entry| (entry.parked_card(), entry.wake_time, entry.reason.clone()))
+            .collect()
     }
     
+    // Cards that woke within the last hour, newest first
+    pub async fn woken_cards(&self) -> Vec<Card> {
+        let cutoff = Utc::now() - chrono::Duration::minutes(WOKEN_BOOST_MINUTES);
+        let mut woken = self.woken.write().await;
+        woken.retain(|_, (_, at)| *at > cutoff);
+        let mut cards: Vec<(Card, DateTime<Utc>)> = woken.values().cloned().collect();
+        cards.sort_by_key(|(_, at)| std::cmp::Reverse(*at));
+        cards.into_iter().map(|(card, _)| card).collect()
+    }
+
+    // Ends a woken card's boost once it's been committed, dismissed or answered
+    pub async fn forget_woken(&self, card_id: Uuid) {
+        self.woken.write().await.remove(&card_id);
+    }
+
+    pub async fn parked_count(&self) -> usize {
+        self.parked_cards.read().await.len()
+    }
+
     pub async fn get_parked_items(&self) -> Vec<ParkedItem> {
         let parked = self.parked_cards.read().await;
         
-        parked.iter().map(|(id, (card, wake_time, reason))| {
+        parked.iter().map(|(id, entry)| {
             ParkedItem {
                 id: *id,
-                title: card.title.clone(),
-                wake_time: *wake_time,
-                altitude: card.altitude.clone(),
+                title: entry.original.title.clone(),
+                wake_time: entry.wake_time,
+                altitude: entry.original.altitude,
                 origin_card_id: *id,
-                context: Some(reason.clone()),
-                wake_conditions: vec![crate::models::WakeCondition::Time(*wake_time)],
+                context: Some(entry.reason.clone()),
+                wake_conditions: entry.conditions(),
             }
         }).collect()
     }
     
+    // Sleeps until the earliest wake is due, re-planning whenever the schedule changes.
+    // Wakes that came due while the server was down fire on the first pass.
     async fn wake_scheduler(&self) {
-        let mut ticker = interval(Duration::from_secs(30)); // Check every 30 seconds
-        
         loop {
-            ticker.tick().await;
-            
-            let now = Utc::now();
-            let mut to_wake = Vec::new();
-            
-            {
+            let next = {
                 let parked = self.parked_cards.read().await;
-                for (id, (_, wake_time, _)) in parked.iter() {
-                    if *wake_time <= now {
-                        to_wake.push(*id);
+                parked.values().map(|entry| entry.wake_time).min()
+            };
+            match next {
+                Some(at) if at <= Utc::now() => self.fire_due_wakes().await,
+                Some(at) => {
+                    let wait = (at - Utc::now()).to_std().unwrap_or_default();
+                    tokio::select! {
+                        _ = tokio::time::sleep(wait) => {}
+                        _ = self.schedule_changed.notified() => {}
                     }
                 }
+                None => self.schedule_changed.notified().await,
             }
-            
-            // Wake cards that are due
-            for card_id in to_wake {
-                if let Ok(Some(card)) = self.unpark_card(card_id).await {
-                    // In a real implementation, we'd emit an event or notify the feed
-                    tracing::info!("Waking card: {} - {}", card.id, card.title);
-                }
+        }
+    }
+
+    async fn fire_due_wakes(&self) {
+        let now = Utc::now();
+        let mut due: Vec<(Uuid, DateTime<Utc>)> = {
+            let parked = self.parked_cards.read().await;
+            parked.iter()
+                .filter(|(_, entry)| entry.wake_time <= now)
+                .map(|(id, entry)| (*id, entry.wake_time))
+                .collect()
+        };
+        due.sort_by_key(|(_, wake_time)| *wake_time);
+
+        for (card_id, _) in due {
+            if let Err(e) = self.wake_card(card_id, "time").await {
+                // Drop it from memory so a broken row can't spin the scheduler; SQLite keeps the wake
+                tracing::warn!("Failed to wake card {}: {}", card_id, e);
+                self.parked_cards.write().await.remove(&card_id);
+            }
+        }
+    }
+
+    // Wakes every card subscribed to `event`. `occurred_at` lets publishers replaying
+    // older messages avoid waking cards parked after them.
+    pub async fn publish(&self, event: &str, occurred_at: Option<DateTime<Utc>>) -> Result<Vec<Card>> {
+        let subscribers = self.registry.read().await.subscribers(event, occurred_at);
+        let mut woken = Vec::with_capacity(subscribers.len());
+        for card_id in subscribers {
+            if let Some(card) = self.wake_card(card_id, event).await? {
+                woken.push(card);
             }
         }
+        Ok(woken)
+    }
+
+    // Unparks the card and pushes `wake.fire` with the restored card; `trigger` says why it woke
+    pub async fn wake_card(&self, card_id: Uuid, trigger: &str) -> Result<Option<Card>> {
+        let wake_time = self.parked_cards.read().await.get(&card_id).map(|entry| entry.wake_time);
+        let Some(card) = self.unpark(card_id, "scheduler").await? else { return Ok(None) };
+        tracing::info!("Waking card: {} - {} ({})", card.id, card.title, trigger);
+        self.woken.write().await.insert(card.id, (card.clone(), Utc::now()));
+
+        let mut payload = serde_json::json!({
+            "kind": "wake.fire",
+            "id": card.id,
+            "card": card,
+            "trigger": trigger,
+        });
+        if let (Some(at), "time") = (wake_time, trigger) {
+            payload["wakeAt"] = serde_json::json!(at);
+            payload["delayMs"] = serde_json::json!((Utc::now() - at).num_milliseconds());
+        }
+        let _ = self.sse_tx.send(SseEvent { event: "wake.fire".into(), data: payload.to_string() });
+        Ok(Some(card))
     }
     
-    pub async fn snooze_card(&self, card_id: Uuid, additional_minutes: i64) -> Result<()> {
+    pub async fn snooze_card(&self, card_id: Uuid, additional_minutes: i64) -> Result<DateTime<Utc>> {
+        let current = self.parked_cards.read().await.get(&card_id).map(|entry| entry.wake_time);
+        let Some(current) = current else { return Err(SnoozeError::NotParked(card_id).into()) };
+        let wake_time = chrono::TimeDelta::try_minutes(additional_minutes)
+            .filter(|_| additional_minutes >= 0)
+            .and_then(|delta| current.checked_add_signed(delta))
+            .ok_or(SnoozeError::OutOfRange(additional_minutes))?;
+        self.reschedule(card_id, wake_time).await
+    }
+
+    // Moves a parked card's time wake; event conditions are left alone
+    pub async fn reschedule(&self, card_id: Uuid, wake_time: DateTime<Utc>) -> Result<DateTime<Utc>> {
         let mut parked = self.parked_cards.write().await;
         
-        if let Some((card, wake_time, reason)) = parked.get_mut(&card_id) {
-            *wake_time = *wake_time + chrono::Duration::minutes(additional_minutes);
-            
-            // Update the card content with new wake time
-            if let CardContent::Parked { wake_time: ref mut content_wake_time, .. } = &mut card.content {
-                *content_wake_time = *wake_time;
+        if let Some(entry) = parked.get_mut(&card_id) {
+            entry.wake_time = wake_time;
+            if let Some(store) = &self.store {
+                store.wakes.reschedule(&card_id.to_string(), &wake_time.to_rfc3339()).await?;
             }
-            
-            Ok(())
+            self.schedule_changed.notify_one();
+            Ok(wake_time)
         } else {
-            Err(anyhow::anyhow!("Card not found in parking"))
+            Err(SnoozeError::NotParked(card_id).into())
         }
     }
 }
@@ -202,6 +439,14 @@ mod tests {
         // Check it's parked
         let parked_cards = service.get_parked_cards().await;
         assert_eq!(parked_cards.len(), 1);
+
+        // Snoozing only ever pushes the wake later
+        assert_eq!(service.snooze_card(card_id, 30).await.unwrap(), wake_time + chrono::Duration::minutes(30));
+        let refused = |e: anyhow::Error| e.downcast::<SnoozeError>().unwrap();
+        assert_eq!(refused(service.snooze_card(card_id, -90).await.unwrap_err()), SnoozeError::OutOfRange(-90));
+        assert_eq!(refused(service.snooze_card(card_id, i64::MAX).await.unwrap_err()), SnoozeError::OutOfRange(i64::MAX));
+        let stranger = Uuid::new_v4();
+        assert_eq!(refused(service.snooze_card(stranger, 5).await.unwrap_err()), SnoozeError::NotParked(stranger));
         
         // Unpark it
         let unparked = service.unpark_card(card_id).await.unwrap();
@@ -211,4 +456,77 @@ mod tests {
         let parked_cards = service.get_parked_cards().await;
         assert_eq!(parked_cards.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_parked_card_survives_restart() {
+        use crate::models::CardState;
+        use crate::sqlite::repo::cards::CardsRepo;
+
+        let db = crate::sqlite::db::SqliteDb::connect_in_memory().await.unwrap();
+        let cards = CardsRepo::new(db.pool.clone());
+        let card = crate::services::mock_data::generate_mock_ship_card();
+        cards.save_card(&card).await.unwrap();
+
+        let (tx, _) = broadcast::channel(16);
+        let service = ParkingService::new_with_sqlite(Some(db.pool.clone()), tx.clone());
+        let wake_time = Utc::now() + chrono::Duration::hours(1);
+        service.park_card(card.clone(), wake_time, "Waiting on review".to_string()).await.unwrap();
+        assert_eq!(cards.get_state(&card.id.to_string()).await.unwrap(), Some(CardState::Parked));
+
+        // A fresh service only knows what SQLite remembers
+        let restarted = ParkingService::new_with_sqlite(Some(db.pool.clone()), tx);
+        assert_eq!(restarted.rehydrate().await.unwrap(), 1);
+        let (parked, _, reason) = restarted.get_parked_cards().await.remove(0);
+        assert!(matches!(parked.card_type, CardType::Parked));
+        assert_eq!(reason, "Waiting on review");
+
+        let restored = restarted.unpark_card(card.id).await.unwrap().unwrap();
+        assert!(matches!(restored.card_type, CardType::Ship));
+        assert_eq!(serde_json::to_value(&restored.content).unwrap(), serde_json::to_value(&card.content).unwrap());
+        assert_eq!(cards.get_state(&card.id.to_string()).await.unwrap(), Some(CardState::Active));
+        assert!(WakesRepo::new(db.pool.clone()).pending().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_due_wake_fires_on_stream() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let service = ParkingService::new_with_sqlite(None, tx);
+        let card = crate::services::mock_data::generate_mock_amplify_card();
+        service.park_card(card.clone(), Utc::now() - chrono::Duration::minutes(1), "Overdue".to_string()).await.unwrap();
+
+        let event = tokio::time::timeout(std::time::Duration::from_secs(2), rx.recv()).await.unwrap().unwrap();
+        assert_eq!(event.event, "wake.fire");
+        let data: serde_json::Value = serde_json::from_str(&event.data).unwrap();
+        assert_eq!(data["id"], serde_json::json!(card.id));
+        assert_eq!(data["card"]["title"], serde_json::json!(card.title));
+        assert!(service.get_parked_cards().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_event_wakes_before_time() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let service = ParkingService::new_with_sqlite(None, tx);
+        let card = crate::services::mock_data::generate_mock_do_now_card();
+        let event = wake_registry::slack_thread_reply("C42", "1700000000.000200");
+        service.park_card_with_conditions(
+            card.clone(),
+            Utc::now() + chrono::Duration::days(1),
+            vec![WakeCondition::Event(event.clone()), WakeCondition::MemoryChange("prd".to_string())],
+            "Waiting on a reply".to_string(),
+        ).await.unwrap();
+        assert_eq!(service.get_parked_items().await[0].wake_conditions.len(), 3);
+
+        assert!(service.publish("slack.thread_reply:C42:other", None).await.unwrap().is_empty());
+        let woken = service.publish(&event, None).await.unwrap();
+        assert_eq!(woken.len(), 1);
+        assert_eq!(service.woken_cards().await.len(), 1);
+
+        let fired = rx.recv().await.unwrap();
+        let data: serde_json::Value = serde_json::from_str(&fired.data).unwrap();
+        assert_eq!(data["trigger"], serde_json::json!(event));
+        assert!(service.publish(&wake_registry::summary_updated("prd"), None).await.unwrap().is_empty());
+
+        service.forget_woken(card.id).await;
+        assert!(service.woken_cards().await.is_empty());
+    }
 }
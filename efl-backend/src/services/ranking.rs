@@ -0,0 +1,311 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::models::{Altitude, BreakInUrgency, Card, CardContent, CardMetadata, CardType, ChipStatus, ScoreBreakdown, SignalScore};
+use crate::services::park_time::{find_deadline, WorkingHours};
+
+// Feed ranking: each signal scores a card in 0..=1 with a short reason, the ranker
+// weights and sums them, and the breakdown is written to `metadata.score`.
+
+pub struct RankContext {
+    pub now: DateTime<Utc>,
+    pub current_altitude: Altitude,
+    // Lowercased names/addresses from PRIORITY_SENDERS
+    pub priority_senders: Vec<String>,
+    // Cards that just woke from parking go to the head of the queue
+    pub woken: HashSet<Uuid>,
+    pub hours: WorkingHours,
+}
+
+impl RankContext {
+    pub fn new(current_altitude: Altitude) -> Self {
+        Self {
+            now: Utc::now(),
+            current_altitude,
+            priority_senders: priority_senders_from_env(),
+            woken: HashSet::new(),
+            hours: WorkingHours::from_env(),
+        }
+    }
+}
+
+// PRIORITY_SENDERS, lowercased; shared with break-in triage
+pub fn priority_senders_from_env() -> Vec<String> {
+    std::env::var("PRIORITY_SENDERS").unwrap_or_default()
+        .split(',')
+        .map(|s| s.trim().to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub trait RankSignal: Send + Sync {
+    fn name(&self) -> &'static str;
+    // None when the signal has nothing to say about the card
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)>;
+}
+
+pub struct FeedRanker {
+    signals: Vec<(Box<dyn RankSignal>, f64)>,
+}
+
+impl Default for FeedRanker {
+    fn default() -> Self {
+        Self::empty()
+            .with_signal(Urgency, 3.0)
+            .with_signal(DueDate, 2.5)
+            .with_signal(SenderPriority, 2.0)
+            .with_signal(Impact, 1.5)
+            .with_signal(AltitudeFit, 1.5)
+            .with_signal(Age, 0.5)
+    }
+}
+
+impl FeedRanker {
+    pub fn empty() -> Self {
+        Self { signals: vec![] }
+    }
+
+    pub fn with_signal(mut self, signal: impl RankSignal + 'static, weight: f64) -> Self {
+        self.signals.push((Box::new(signal), weight));
+        self
+    }
+
+    pub fn score(&self, card: &Card, ctx: &RankContext) -> ScoreBreakdown {
+        let signals: Vec<SignalScore> = self.signals.iter()
+            .filter_map(|(signal, weight)| {
+                let (value, reason) = signal.score(card, ctx)?;
+                let value = value.clamp(0.0, 1.0);
+                Some(SignalScore {
+                    signal: signal.name().to_string(),
+                    value,
+                    weight: *weight,
+                    contribution: value * weight,
+                    reason: Some(reason),
+                })
+            })
+            .collect();
+        ScoreBreakdown { total: signals.iter().map(|s| s.contribution).sum(), signals }
+    }
+
+    // Highest score first; ties go to the newer card, then by id so the order is stable
+    pub fn rank(&self, cards: Vec<Card>, ctx: &RankContext) -> Vec<Card> {
+        let mut scored: Vec<Card> = cards.into_iter()
+            .map(|mut card| {
+                let score = self.score(&card, ctx);
+                card.metadata.get_or_insert_with(CardMetadata::default).score = Some(score);
+                card
+            })
+            .collect();
+        scored.sort_by(|a, b| RankKey::of(a).feed_cmp(&RankKey::of(b)));
+        scored
+    }
+}
+
+pub fn total(card: &Card) -> f64 {
+    card.metadata.as_ref().and_then(|m| m.score.as_ref()).map_or(0.0, |s| s.total)
+}
+
+// A card's position in the feed: score, then newer first, then id
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RankKey {
+    pub score: f64,
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl RankKey {
+    pub fn of(card: &Card) -> Self {
+        Self { score: total(card), created_at: card.created_at, id: card.id }
+    }
+
+    // Less means `self` comes first in the feed
+    pub fn feed_cmp(&self, other: &Self) -> Ordering {
+        other.score.total_cmp(&self.score)
+            .then_with(|| other.created_at.cmp(&self.created_at))
+            .then_with(|| self.id.cmp(&other.id))
+    }
+}
+
+// Text the ranker reads for keywords and deadlines
+fn card_text(card: &Card) -> String {
+    let mut text = card.title.clone();
+    if let Some(subject) = card.metadata.as_ref().and_then(|m| m.email_subject.as_ref()) {
+        text.push(' ');
+        text.push_str(subject);
+    }
+    if let CardContent::BreakIn { message, .. } = &card.content {
+        text.push(' ');
+        text.push_str(message);
+    }
+    text.to_lowercase()
+}
+
+pub struct Urgency;
+
+impl RankSignal for Urgency {
+    fn name(&self) -> &'static str { "urgency" }
+
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)> {
+        if ctx.woken.contains(&card.id) {
+            return Some((1.0, "just woke from parking".to_string()));
+        }
+        if let CardContent::BreakIn { urgency, source, .. } = &card.content {
+            let value = match urgency {
+                BreakInUrgency::High => 1.0,
+                BreakInUrgency::Medium => 0.6,
+                BreakInUrgency::Low => 0.2,
+            };
+            return Some((value, format!("{:?} urgency break-in from {}", urgency, source).to_lowercase()));
+        }
+        let text = card_text(card);
+        ["urgent", "asap", "immediately", "blocker", "outage"].iter()
+            .find(|kw| text.contains(*kw))
+            .map(|kw| (0.8, format!("mentions \"{}\"", kw)))
+    }
+}
+
+pub struct Impact;
+
+impl RankSignal for Impact {
+    fn name(&self) -> &'static str { "impact" }
+
+    fn score(&self, card: &Card, _ctx: &RankContext) -> Option<(f64, String)> {
+        match &card.content {
+            CardContent::Ship { dod_chips, version_tag } => {
+                let red = dod_chips.iter().filter(|c| matches!(c.status, ChipStatus::Red)).count();
+                Some((0.9, format!("ships {} ({} of {} checks open)", version_tag, red, dod_chips.len())))
+            }
+            CardContent::DoNow { intent, .. } => Some((0.6, format!("moves \"{}\" forward", intent.name))),
+            CardContent::Amplify { suggestions, .. } => Some((0.5, format!("{} ways to amplify shipped work", suggestions.len()))),
+            CardContent::Orient { next_tasks } => {
+                let best = next_tasks.iter().map(|t| t.impact_score as f64).fold(0.0, f64::max);
+                Some((best.max(0.3), "sets up what's next".to_string()))
+            }
+            CardContent::BreakIn { .. } => Some((0.4, "someone is waiting on you".to_string())),
+            CardContent::BatchReview { emails, .. } => Some((0.2, format!("{} low-priority emails to clear", emails.len()))),
+            CardContent::Parked { .. } => None,
+        }
+    }
+}
+
+pub struct Age;
+
+impl RankSignal for Age {
+    fn name(&self) -> &'static str { "age" }
+
+    // Older cards creep up so nothing starves
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)> {
+        let hours = (ctx.now - card.created_at).num_minutes() as f64 / 60.0;
+        if hours < 1.0 {
+            return None;
+        }
+        Some(((hours / 48.0).min(1.0), format!("waiting {}h", hours.round())))
+    }
+}
+
+pub struct SenderPriority;
+
+impl RankSignal for SenderPriority {
+    fn name(&self) -> &'static str { "sender" }
+
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)> {
+        let sender = match &card.content {
+            CardContent::BreakIn { sender, .. } => Some(sender.clone()),
+            _ => card.metadata.as_ref().and_then(|m| m.email_sender.clone()),
+        }?;
+        let lowered = sender.to_lowercase();
+        if ctx.priority_senders.iter().any(|p| lowered.contains(p.as_str())) {
+            return Some((1.0, format!("{} is a priority sender", sender)));
+        }
+        match card.metadata.as_ref().and_then(|m| m.email_category.as_deref()) {
+            Some("Personal") => Some((0.5, format!("personal email from {}", sender))),
+            Some("Sales") | Some("Newsletter") | Some("Notification") | Some("Spam") => Some((0.0, format!("bulk sender {}", sender))),
+            _ if matches!(card.card_type, CardType::BreakIn) => Some((0.5, format!("direct message from {}", sender))),
+            _ => None,
+        }
+    }
+}
+
+pub struct DueDate;
+
+impl RankSignal for DueDate {
+    fn name(&self) -> &'static str { "due" }
+
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)> {
+        let due = find_deadline(&card_text(card), ctx.now, &ctx.hours)?;
+        let hours = (due - ctx.now).num_minutes() as f64 / 60.0;
+        let value = match hours {
+            h if h <= 4.0 => 1.0,
+            h if h <= 24.0 => 0.7,
+            h if h <= 72.0 => 0.4,
+            _ => 0.1,
+        };
+        let local = due.with_timezone(&ctx.hours.timezone).format("%a %H:%M");
+        Some((value, format!("due {}", local)))
+    }
+}
+
+pub struct AltitudeFit;
+
+impl RankSignal for AltitudeFit {
+    fn name(&self) -> &'static str { "altitude" }
+
+    fn score(&self, card: &Card, ctx: &RankContext) -> Option<(f64, String)> {
+        let level = |a: &Altitude| match a {
+            Altitude::Do => 0i32,
+            Altitude::Ship => 1,
+            Altitude::Amplify => 2,
+            Altitude::Orient => 3,
+        };
+        let distance = (level(&card.altitude) - level(&ctx.current_altitude)).abs();
+        let value = match distance {
+            0 => 1.0,
+            1 => 0.5,
+            _ => 0.1,
+        };
+        Some((value, format!("{:?} card while at {:?}", card.altitude, ctx.current_altitude)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::mock_data::*;
+
+    #[test]
+    fn test_rank_orders_and_explains() {
+        let mut ctx = RankContext::new(Altitude::Do);
+        ctx.priority_senders = vec!["sarah".to_string()];
+
+        let do_now = generate_mock_do_now_card();
+        let orient = generate_mock_orient_card();
+        let breakin = generate_mock_breakin_card();
+        let mut woken = generate_mock_amplify_card();
+        woken.created_at = ctx.now - chrono::Duration::hours(30);
+        ctx.woken.insert(woken.id);
+
+        let ranked = FeedRanker::default().rank(vec![orient.clone(), do_now.clone(), breakin.clone(), woken.clone()], &ctx);
+        assert_eq!(ranked.len(), 4);
+        assert_eq!(ranked.last().unwrap().id, orient.id);
+        assert!(total(&ranked[0]) >= total(&ranked[1]));
+
+        let score = ranked.iter().find(|c| c.id == woken.id).unwrap().metadata.as_ref().unwrap().score.clone().unwrap();
+        let urgency = score.signals.iter().find(|s| s.signal == "urgency").unwrap();
+        assert_eq!(urgency.reason.as_deref(), Some("just woke from parking"));
+        assert!(score.signals.iter().any(|s| s.signal == "age"));
+    }
+
+    #[test]
+    fn test_due_date_from_text() {
+        let ctx = RankContext::new(Altitude::Do);
+        let mut card = generate_mock_do_now_card();
+        card.title = "Send the deck by tomorrow 5pm".to_string();
+        let (value, reason) = DueDate.score(&card, &ctx).unwrap();
+        assert!(value >= 0.4);
+        assert!(reason.starts_with("due "));
+        card.title = "No deadline here".to_string();
+        assert!(DueDate.score(&card, &ctx).is_none());
+    }
+}
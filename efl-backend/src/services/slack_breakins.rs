@@ -0,0 +1,162 @@
+use anyhow::Result;
+use sqlx::SqlitePool;
+use uuid::Uuid;
+use crate::connectors::slack::{ts_to_datetime, SlackClient, SlackMessage};
+use crate::models::{Altitude, Card, CardAction, CardContent, CardMetadata, CardState, CardStatus, CardType, OriginObject};
+use crate::services::card::CardService;
+use crate::services::triage::{triage, urgency_for, Interrupt, TriageContext};
+use crate::sqlite::repo::cards::CardsRepo;
+use crate::sqlite::repo::slack_map::SlackMapRepo;
+
+// Slack DMs become BreakIn cards, one per thread. Cards are saved through CardService, so
+// the queue log can replay them, and mapped in slack_threads, so a later reply updates the
+// same card, and wakes it if it was parked.
+
+const DOC_PREFIX: &str = "slack_";
+
+pub fn breakin_card_id(channel: &str, thread_ts: &str) -> Uuid {
+    Uuid::new_v3(&Uuid::NAMESPACE_URL, format!("slack-thread:{}:{}", channel, thread_ts).as_bytes())
+}
+
+// Which card a message lands on
+struct Target {
+    id: Uuid,
+    // State of the card being updated; None for a new card
+    state: Option<CardState>,
+    // False when the thread already belongs to some other card (mapped via /slack/map)
+    map: bool,
+}
+
+pub struct Ingested {
+    pub card: Card,
+    // Stored state before this message; None for a new card
+    pub previous: Option<CardState>,
+}
+
+impl Ingested {
+    // New and active cards break in; any other card waits where it is, e.g. parked for its wake
+    pub fn breaks_in(&self) -> bool {
+        matches!(self.previous, None | Some(CardState::Active))
+    }
+}
+
+#[derive(Clone)]
+pub struct SlackBreakInService {
+    slack: Option<SlackClient>,
+    cards: CardService,
+    store: Option<(CardsRepo, SlackMapRepo)>,
+}
+
+impl SlackBreakInService {
+    pub fn new(sqlite_pool: Option<SqlitePool>, slack: Option<SlackClient>) -> Self {
+        Self {
+            slack,
+            cards: CardService::new_with_sqlite(None, sqlite_pool.clone()),
+            store: sqlite_pool.map(|pool| (CardsRepo::new(pool.clone()), SlackMapRepo::new(pool))),
+        }
+    }
+
+    // Builds the thread's card, or updates the one it already has, and saves it
+    pub async fn ingest(&self, message: &SlackMessage, ctx: &TriageContext) -> Result<Ingested> {
+        let thread_ts = message.thread_ts.as_deref().unwrap_or(&message.timestamp);
+        let sender = match &self.slack {
+            Some(slack) => slack.user_name(&message.user).await,
+            None => message.user.clone(),
+        };
+        let target = self.target(&message.channel, thread_ts).await?;
+        let triage = triage(&Interrupt { sender: &sender, text: &message.text, category: None }, ctx);
+
+        let card = Card {
+            id: target.id,
+            card_type: CardType::BreakIn,
+            altitude: Altitude::Do,
+            title: format!("DM from {}", sender),
+            content: CardContent::BreakIn {
+                source: message.channel.clone(),
+                message: message.text.clone(),
+                sender,
+                urgency: urgency_for(triage.recommendation),
+            },
+            actions: vec![CardAction::RespondNow, CardAction::RespondAtBreak, CardAction::Park],
+            origin_object: Some(OriginObject {
+                doc_id: format!("{}{}", DOC_PREFIX, message.channel),
+                block_id: Some(thread_ts.to_string()),
+            }),
+            created_at: ts_to_datetime(&message.timestamp).unwrap_or_else(chrono::Utc::now),
+            // An update keeps the card where it is: a parked card waits for its wake
+            status: target.state.map_or(CardStatus::Active, |s| s.status()),
+            metadata: Some(CardMetadata { triage: Some(triage), ..Default::default() }),
+        };
+        if let Some((_, threads)) = &self.store {
+            self.cards.save_card(&card, "slack").await?;
+            if target.map {
+                threads.upsert(&card.id.to_string(), &message.channel, thread_ts).await?;
+            }
+        }
+        Ok(Ingested { card, previous: target.state })
+    }
+
+    async fn target(&self, channel: &str, thread_ts: &str) -> Result<Target> {
+        let fresh = Target { id: breakin_card_id(channel, thread_ts), state: None, map: true };
+        let Some((cards, threads)) = &self.store else { return Ok(fresh) };
+        let Some(mapped) = threads.find_card_by_thread(channel, thread_ts).await? else { return Ok(fresh) };
+        let existing = cards.get_card(&mapped).await?
+            .filter(|c| c.origin_object.as_ref().is_some_and(|o| o.doc_id.starts_with(DOC_PREFIX)));
+        let Some(card) = existing else {
+            // Someone else's card follows this thread; it gets the wake, we keep a separate break-in
+            return Ok(Target { map: false, ..fresh });
+        };
+        match cards.get_state(&mapped).await? {
+            // Dismissed stays dismissed; a new reply is a new interrupt
+            Some(CardState::Dismissed) => Ok(Target { id: Uuid::new_v4(), state: None, map: true }),
+            state => Ok(Target { id: card.id, state, map: true }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite::db::SqliteDb;
+    use crate::sqlite::repo::queue::QueueRepo;
+
+    fn message(ts: &str, thread_ts: Option<&str>, text: &str) -> SlackMessage {
+        SlackMessage::from_api("D1", &serde_json::json!({ "user": "U1", "text": text, "ts": ts, "thread_ts": thread_ts })).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replies_update_the_thread_card() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        let service = SlackBreakInService::new(Some(db.pool.clone()), None);
+        let ctx = TriageContext::from_env();
+        let cards = CardsRepo::new(db.pool.clone());
+        let threads = SlackMapRepo::new(db.pool.clone());
+
+        let ingested = service.ingest(&message("1712345000.000100", None, "got a sec?"), &ctx).await.unwrap();
+        assert!(ingested.breaks_in());
+        let first = ingested.card;
+        assert_eq!(first.id, breakin_card_id("D1", "1712345000.000100"));
+        assert_eq!(first.title, "DM from U1");
+        assert_eq!(threads.find_card_by_thread("D1", "1712345000.000100").await.unwrap(), Some(first.id.to_string()));
+
+        let reply = service.ingest(&message("1712345060.000200", Some("1712345000.000100"), "it's about the launch"), &ctx).await.unwrap().card;
+        assert_eq!(reply.id, first.id);
+        let stored = cards.get_card(&first.id.to_string()).await.unwrap().unwrap();
+        assert!(matches!(stored.content, CardContent::BreakIn { ref message, .. } if message == "it's about the launch"));
+        // Snapshots in the queue log, so a rebuild brings the card back
+        let events = QueueRepo::new(db.pool.clone()).list_for_card(&first.id.to_string()).await.unwrap();
+        assert_eq!(events.iter().map(|e| e.event.as_str()).collect::<Vec<_>>(), vec!["append", "update"]);
+
+        // A reply to a parked card updates it but leaves it parked
+        cards.set_state(&first.id.to_string(), CardState::Parked).await.unwrap();
+        let parked = service.ingest(&message("1712345090.000250", Some("1712345000.000100"), "no rush"), &ctx).await.unwrap();
+        assert!(!parked.breaks_in());
+        assert_eq!(cards.get_state(&first.id.to_string()).await.unwrap(), Some(CardState::Parked));
+
+        // Once dismissed, the next reply is a new card and the thread follows it
+        cards.set_state(&first.id.to_string(), CardState::Dismissed).await.unwrap();
+        let again = service.ingest(&message("1712345120.000300", Some("1712345000.000100"), "ping"), &ctx).await.unwrap().card;
+        assert_ne!(again.id, first.id);
+        assert_eq!(threads.find_card_by_thread("D1", "1712345000.000100").await.unwrap(), Some(again.id.to_string()));
+    }
+}
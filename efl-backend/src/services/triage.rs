@@ -0,0 +1,218 @@
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use crate::memory::MemoryCache;
+use crate::models::{BreakInUrgency, Summary, Triage, TriageFactor, TriageRecommendation};
+use crate::services::park_time::{find_deadline, WorkingHours};
+use crate::services::ranking::priority_senders_from_env;
+
+// Break-in micro-triage shared by every connector. An interrupt is scored as
+// urgency × impact × readiness; at or above τ it preempts the queue (Respond Now),
+// otherwise urgent ones wait for the next break and the rest get parked.
+
+// τ when TRIAGE_THRESHOLD is unset
+pub const DEFAULT_THRESHOLD: f64 = 0.48;
+
+const URGENT_KEYWORDS: &[&str] = &["urgent", "asap", "immediately", "emergency", "blocker", "outage", "is down", "right now", "p0"];
+const CALM_KEYWORDS: &[&str] = &["no rush", "whenever", "when you get a chance", "low priority", "fyi"];
+const BULK_CATEGORIES: &[&str] = &["Sales", "Newsletter", "Notification", "Spam"];
+const STOPWORDS: &[&str] = &["about", "after", "again", "also", "been", "before", "could", "does", "from", "have", "just", "know", "like", "more", "need", "please", "should", "some", "than", "that", "their", "them", "then", "there", "they", "this", "what", "when", "where", "which", "will", "with", "would", "your"];
+
+// An incoming interrupt, whatever it came from
+pub struct Interrupt<'a> {
+    pub sender: &'a str,
+    pub text: &'a str,
+    // Email category when the interrupt is an email; bulk mail never preempts
+    pub category: Option<&'a str>,
+}
+
+pub struct TriageContext {
+    pub now: DateTime<Utc>,
+    pub threshold: f64,
+    // Lowercased names/addresses from PRIORITY_SENDERS
+    pub priority_senders: Vec<String>,
+    pub hours: WorkingHours,
+    // Cached summaries an answer could be drawn from
+    pub summaries: Vec<Summary>,
+}
+
+impl TriageContext {
+    pub fn from_env() -> Self {
+        Self {
+            now: Utc::now(),
+            threshold: std::env::var("TRIAGE_THRESHOLD").ok()
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|t| (0.0..=1.0).contains(t))
+                .unwrap_or(DEFAULT_THRESHOLD),
+            priority_senders: priority_senders_from_env(),
+            hours: WorkingHours::from_env(),
+            summaries: vec![],
+        }
+    }
+
+    pub fn with_summaries(mut self, cache: &MemoryCache) -> Self {
+        self.summaries = cache.summaries();
+        self
+    }
+}
+
+pub fn triage(interrupt: &Interrupt, ctx: &TriageContext) -> Triage {
+    let urgency = urgency(interrupt.text, ctx);
+    let impact = impact(interrupt, ctx);
+    let readiness = readiness(interrupt.text, &ctx.summaries);
+    let score = urgency.value * impact.value * readiness.value;
+
+    let (recommendation, label) = if score >= ctx.threshold {
+        (TriageRecommendation::Now, "Respond now")
+    } else if urgency.value >= 0.6 {
+        (TriageRecommendation::AtBreak, "Respond at next break")
+    } else {
+        (TriageRecommendation::Park, "Park")
+    };
+    let rationale = format!(
+        "{}: {}; {}; {} (score {:.2} vs τ {:.2})",
+        label, urgency.reason, impact.reason, readiness.reason, score, ctx.threshold
+    );
+    Triage {
+        urgency,
+        impact,
+        readiness,
+        score,
+        threshold: ctx.threshold,
+        recommendation,
+        preempt: recommendation == TriageRecommendation::Now,
+        rationale,
+    }
+}
+
+// Urgency the break-in card shows for a recommendation
+pub fn urgency_for(recommendation: TriageRecommendation) -> BreakInUrgency {
+    match recommendation {
+        TriageRecommendation::Now => BreakInUrgency::High,
+        TriageRecommendation::AtBreak => BreakInUrgency::Medium,
+        TriageRecommendation::Park => BreakInUrgency::Low,
+    }
+}
+
+fn factor(value: f64, reason: impl Into<String>) -> TriageFactor {
+    TriageFactor { value, reason: reason.into() }
+}
+
+fn urgency(text: &str, ctx: &TriageContext) -> TriageFactor {
+    let lowered = text.to_lowercase();
+    if let Some(kw) = URGENT_KEYWORDS.iter().find(|kw| lowered.contains(*kw)) {
+        return factor(0.9, format!("says \"{}\"", kw));
+    }
+    if let Some(due) = find_deadline(text, ctx.now, &ctx.hours) {
+        let hours = (due - ctx.now).num_minutes() as f64 / 60.0;
+        let value = match hours {
+            h if h <= 2.0 => 1.0,
+            h if h <= 24.0 => 0.7,
+            h if h <= 72.0 => 0.4,
+            _ => 0.2,
+        };
+        let local = due.with_timezone(&ctx.hours.timezone).format("%a %H:%M");
+        return factor(value, format!("due {}", local));
+    }
+    if let Some(kw) = CALM_KEYWORDS.iter().find(|kw| lowered.contains(*kw)) {
+        return factor(0.2, format!("says \"{}\"", kw));
+    }
+    if lowered.contains('?') {
+        return factor(0.5, "asks a question");
+    }
+    factor(0.4, "no urgency cues")
+}
+
+fn impact(interrupt: &Interrupt, ctx: &TriageContext) -> TriageFactor {
+    let lowered = interrupt.sender.to_lowercase();
+    if ctx.priority_senders.iter().any(|p| lowered.contains(p.as_str())) {
+        return factor(0.9, format!("{} is a priority sender", interrupt.sender));
+    }
+    match interrupt.category {
+        Some(category) if BULK_CATEGORIES.contains(&category) => factor(0.1, format!("bulk sender {}", interrupt.sender)),
+        Some(_) => factor(0.6, format!("personal email from {}", interrupt.sender)),
+        None => factor(0.6, format!("direct message from {}", interrupt.sender)),
+    }
+}
+
+// Lowercased words worth matching on
+fn terms(text: &str) -> HashSet<String> {
+    text.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.len() >= 4 && !STOPWORDS.contains(w))
+        .map(str::to_string)
+        .collect()
+}
+
+// How much of the message a cached summary already covers: half the message's
+// terms showing up in one summary counts as fully answerable
+fn readiness(text: &str, summaries: &[Summary]) -> TriageFactor {
+    let wanted = terms(text);
+    if wanted.is_empty() {
+        return factor(0.8, "nothing to look up");
+    }
+    let best = summaries.iter()
+        .map(|s| {
+            let known = terms(&format!("{} {}", s.title, s.bullets.join(" ")));
+            (wanted.intersection(&known).count() as f64 / wanted.len() as f64, s)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0));
+    match best {
+        Some((coverage, summary)) if coverage > 0.0 => {
+            let value = (0.4 + 1.2 * coverage).min(1.0);
+            factor(value, format!("answerable from cached summary \"{}\" ({:.0}% covered)", summary.title, coverage * 100.0))
+        }
+        _ => factor(0.4, "no cached context to answer from"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::SummaryType;
+
+    fn ctx() -> TriageContext {
+        let mut ctx = TriageContext::from_env();
+        ctx.threshold = DEFAULT_THRESHOLD;
+        ctx.priority_senders = vec!["sarah".to_string()];
+        ctx.summaries = vec![Summary {
+            id: uuid::Uuid::new_v4(),
+            summary_type: SummaryType::Document,
+            level: 1,
+            title: "Q3 launch plan".to_string(),
+            bullets: vec!["Launch date moved to October 14".to_string(), "Pricing tiers unchanged".to_string()],
+            created_at: Utc::now(),
+            source_id: "doc-1".to_string(),
+        }];
+        ctx
+    }
+
+    #[test]
+    fn test_recommendations() {
+        let ctx = ctx();
+
+        // Urgent, from a priority sender, and the answer is in memory
+        let now = triage(&Interrupt { sender: "Sarah Chen", text: "Urgent: is the launch date still October 14?", category: None }, &ctx);
+        assert_eq!(now.recommendation, TriageRecommendation::Now);
+        assert!(now.preempt);
+        assert!(now.rationale.contains("Q3 launch plan"));
+
+        // Just as urgent, but nothing cached to answer with
+        let at_break = triage(&Interrupt { sender: "Sarah Chen", text: "Urgent: can you review the vendor contract?", category: None }, &ctx);
+        assert_eq!(at_break.recommendation, TriageRecommendation::AtBreak);
+        assert!(!at_break.preempt);
+        assert!(at_break.score < ctx.threshold);
+
+        let park = triage(&Interrupt { sender: "Deals", text: "No rush, our spring catalog is out", category: Some("Sales") }, &ctx);
+        assert_eq!(park.recommendation, TriageRecommendation::Park);
+        assert!(matches!(urgency_for(park.recommendation), BreakInUrgency::Low));
+    }
+
+    #[test]
+    fn test_deadline_drives_urgency() {
+        let ctx = ctx();
+        let soon = urgency("Can you send the numbers by tomorrow 9am", &ctx);
+        assert!(soon.value >= 0.4);
+        assert!(soon.reason.starts_with("due "));
+        assert_eq!(urgency("hey, quick thought", &ctx).value, 0.4);
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use tokio::sync::{broadcast, RwLock};
+use uuid::Uuid;
+use crate::memory::MemoryCache;
+use crate::models::{Card, CardContent, CardState, Diff, DiffOpType, DiffOperation, Edit, EditType};
+use crate::services::diff::{diff_operations, DiffGranularity};
+use crate::services::memory::MemoryService;
+use crate::sse::SseEvent;
+
+// How long an undone change can still be redone
+const REDO_WINDOW_MINUTES: i64 = 10;
+
+#[derive(thiserror::Error, Debug)]
+pub enum UndoError {
+    #[error("no active document to apply the change to")]
+    NoActiveDocument,
+    #[error("document no longer contains the diff's text")]
+    DiffNotFound,
+    #[error("document changed since version {expected} (now {actual})")]
+    DocumentChanged { expected: u64, actual: u64 },
+    #[error("card {0} is not the most recent change to its document")]
+    OutOfOrder(Uuid),
+    #[error("card {0}'s change is no longer in the undo history")]
+    HistoryLost(Uuid),
+    #[error("nothing to redo for card {0}")]
+    NothingToRedo(Uuid),
+    #[error("redo window for card {0} has expired")]
+    RedoExpired(Uuid),
+    #[error("invalid diff operation: {0}")]
+    InvalidOperation(String),
+    #[error("memory {0}")]
+    Memory(#[from] anyhow::Error),
+}
+
+// A committed diff as applied to a document
+#[derive(Debug, Clone, Serialize)]
+pub struct AppliedChange {
+    pub card_id: Uuid,
+    pub doc_id: String,
+    pub diff: Diff,
+    // Byte offset of `diff.before` within the document when it was applied
+    pub offset: usize,
+    pub version_before: u64,
+    pub version_after: u64,
+    pub committed_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct DocHistory {
+    undo: Vec<AppliedChange>,
+    redo: Vec<(AppliedChange, DateTime<Utc>)>,
+}
+
+#[derive(Clone)]
+pub struct UndoService {
+    history: Arc<RwLock<HashMap<String, DocHistory>>>,
+    memory_cache: MemoryCache,
+    sse_tx: broadcast::Sender<SseEvent>,
+}
+
+impl UndoService {
+    pub fn new(memory_cache: MemoryCache, sse_tx: broadcast::Sender<SseEvent>) -> Self {
+        Self {
+            history: Arc::new(RwLock::new(HashMap::new())),
+            memory_cache,
+            sse_tx,
+        }
+    }
+
+    fn memory(&self) -> MemoryService {
+        MemoryService::new(None, self.memory_cache.clone())
+    }
+
+    // Applies a DoNow card's diff to the active document and pushes it on that document's undo stack.
+    // Cards without a diff, or without an active document, commit without touching any document.
+    pub async fn record_commit(&self, card: &Card) -> Result<Option<AppliedChange>, UndoError> {
+        let CardContent::DoNow { diff: Some(diff), .. } = &card.content else { return Ok(None) };
+        let mut ws = self.memory().get_current_working_set().await?;
+        let Some(doc) = ws.active_doc.as_mut() else { return Ok(None) };
+
+        let offset = doc.content.find(&diff.before).ok_or(UndoError::DiffNotFound)?;
+        // Cards created with only before/after text get their operations computed here
+        let operations = if diff.operations.is_empty() {
+            diff_operations(&diff.before, &diff.after, DiffGranularity::Word)
+        } else {
+            diff.operations.clone()
+        };
+        let after = apply_operations(&diff.before, &operations)?;
+        doc.content.replace_range(offset..offset + diff.before.len(), &after);
+        let change = AppliedChange {
+            card_id: card.id,
+            doc_id: doc.doc_id.clone(),
+            diff: Diff { before: diff.before.clone(), after, operations },
+            offset,
+            version_before: doc.version,
+            version_after: doc.version + 1,
+            committed_at: Utc::now(),
+        };
+        doc.version = change.version_after;
+        push_edit(&mut ws.recent_edits, &change, &change.diff.before, &change.diff.after);
+        self.memory().save_working_set(ws).await?;
+
+        let mut history = self.history.write().await;
+        let entry = history.entry(change.doc_id.clone()).or_default();
+        entry.undo.push(change.clone());
+        entry.redo.clear();
+        Ok(Some(change))
+    }
+
+    // Reverts the card's committed diff by applying its inverse operations.
+    // Returns None if the card never changed a document.
+    pub async fn undo(&self, card_id: Uuid) -> Result<Option<AppliedChange>, UndoError> {
+        let mut history = self.history.write().await;
+        let Some((doc_id, entry)) = history.iter_mut().find(|(_, h)| h.undo.iter().any(|c| c.card_id == card_id)) else {
+            return Ok(None);
+        };
+        if entry.undo.last().map(|c| c.card_id) != Some(card_id) {
+            return Err(UndoError::OutOfOrder(card_id));
+        }
+        let change = entry.undo.last().cloned().expect("checked above");
+
+        let mut ws = self.memory().get_current_working_set().await?;
+        let doc = ws.active_doc.as_mut()
+            .filter(|d| &d.doc_id == doc_id)
+            .ok_or(UndoError::NoActiveDocument)?;
+        if doc.version != change.version_after {
+            return Err(UndoError::DocumentChanged { expected: change.version_after, actual: doc.version });
+        }
+        let span = change.offset..change.offset + change.diff.after.len();
+        if doc.content.get(span.clone()) != Some(change.diff.after.as_str()) {
+            return Err(UndoError::DiffNotFound);
+        }
+        let inverse = invert_operations(&change.diff.before, &change.diff.operations)?;
+        let restored = apply_operations(&change.diff.after, &inverse)?;
+        doc.content.replace_range(span, &restored);
+        doc.version += 1;
+        let version = doc.version;
+        push_edit(&mut ws.recent_edits, &change, &change.diff.after, &restored);
+        self.memory().save_working_set(ws).await?;
+
+        entry.undo.pop();
+        let undone = AppliedChange { version_after: version, ..change };
+        entry.redo.push((undone.clone(), Utc::now()));
+        Ok(Some(undone))
+    }
+
+    // Re-applies an undone change if it's still within the redo window
+    pub async fn redo(&self, card_id: Uuid) -> Result<AppliedChange, UndoError> {
+        let mut history = self.history.write().await;
+        let Some((doc_id, entry)) = history.iter_mut().find(|(_, h)| h.redo.iter().any(|(c, _)| c.card_id == card_id)) else {
+            return Err(UndoError::NothingToRedo(card_id));
+        };
+        let Some((change, undone_at)) = entry.redo.last().cloned().filter(|(c, _)| c.card_id == card_id) else {
+            return Err(UndoError::OutOfOrder(card_id));
+        };
+        if Utc::now() - undone_at > Duration::minutes(REDO_WINDOW_MINUTES) {
+            entry.redo.clear();
+            return Err(UndoError::RedoExpired(card_id));
+        }
+
+        let mut ws = self.memory().get_current_working_set().await?;
+        let doc = ws.active_doc.as_mut()
+            .filter(|d| &d.doc_id == doc_id)
+            .ok_or(UndoError::NoActiveDocument)?;
+        if doc.version != change.version_after {
+            return Err(UndoError::DocumentChanged { expected: change.version_after, actual: doc.version });
+        }
+        let span = change.offset..change.offset + change.diff.before.len();
+        if doc.content.get(span.clone()) != Some(change.diff.before.as_str()) {
+            return Err(UndoError::DiffNotFound);
+        }
+        doc.content.replace_range(span, &change.diff.after);
+        let redone = AppliedChange {
+            version_before: doc.version,
+            version_after: doc.version + 1,
+            committed_at: Utc::now(),
+            ..change
+        };
+        doc.version = redone.version_after;
+        push_edit(&mut ws.recent_edits, &redone, &redone.diff.before, &redone.diff.after);
+        self.memory().save_working_set(ws).await?;
+
+        entry.redo.pop();
+        entry.undo.push(redone.clone());
+        Ok(redone)
+    }
+
+    // Emits `card.update` so clients pick up the new state and document version
+    pub fn publish(&self, card_id: Uuid, state: CardState, change: Option<&AppliedChange>) {
+        let mut payload = serde_json::json!({
+            "id": card_id,
+            "patch": { "state": state, "status": state.status() },
+        });
+        if let Some(c) = change {
+            payload["document"] = serde_json::json!({ "docId": c.doc_id, "version": c.version_after });
+        }
+        let _ = self.sse_tx.send(SseEvent { event: "card.update".into(), data: payload.to_string() });
+    }
+}
+
+fn push_edit(edits: &mut Vec<Edit>, change: &AppliedChange, before: &str, after: &str) {
+    edits.push(Edit {
+        id: Uuid::new_v4(),
+        timestamp: Utc::now(),
+        doc_id: change.doc_id.clone(),
+        before: before.to_string(),
+        after: after.to_string(),
+        edit_type: EditType::Replace,
+    });
+}
+
+fn check_range(text: &str, op: &DiffOperation) -> Result<(), UndoError> {
+    let (start, end) = op.range;
+    if start > end || end > text.len() || !text.is_char_boundary(start) || !text.is_char_boundary(end) {
+        return Err(UndoError::InvalidOperation(format!("range {}..{} in {} bytes", start, end, text.len())));
+    }
+    Ok(())
+}
+
+// In document order, each range valid in `text` and clear of the one before it. Ranges are
+// offsets into the original text, so overlapping edits have no meaning.
+fn sorted_operations<'a>(text: &str, operations: &'a [DiffOperation]) -> Result<Vec<&'a DiffOperation>, UndoError> {
+    let mut ops: Vec<&DiffOperation> = operations.iter().collect();
+    ops.sort_by_key(|op| op.range);
+    let mut previous_end = 0;
+    for op in &ops {
+        check_range(text, op)?;
+        if op.range.0 < previous_end {
+            return Err(UndoError::InvalidOperation(format!("range {}..{} overlaps one ending at {}", op.range.0, op.range.1, previous_end)));
+        }
+        previous_end = op.range.1;
+    }
+    Ok(ops)
+}
+
+// Applies operations whose ranges are byte offsets into `text`
+pub fn apply_operations(text: &str, operations: &[DiffOperation]) -> Result<String, UndoError> {
+    let ops = sorted_operations(text, operations)?;
+    // Work back to front so earlier ranges stay valid
+    let mut out = text.to_string();
+    for op in ops.into_iter().rev() {
+        let (start, end) = op.range;
+        let content = op.content.as_deref().unwrap_or("");
+        match op.op_type {
+            DiffOpType::Add => out.insert_str(start, content),
+            DiffOpType::Remove => out.replace_range(start..end, ""),
+            DiffOpType::Replace => out.replace_range(start..end, content),
+        }
+    }
+    Ok(out)
+}
+
+// Builds operations that turn the result of `operations` back into `before`
+pub fn invert_operations(before: &str, operations: &[DiffOperation]) -> Result<Vec<DiffOperation>, UndoError> {
+    let ops = sorted_operations(before, operations)?;
+    let mut shift: isize = 0;
+    let mut inverse = Vec::with_capacity(ops.len());
+    for op in ops {
+        let (start, end) = op.range;
+        let removed = &before[start..end];
+        let inserted = match op.op_type {
+            DiffOpType::Remove => "",
+            _ => op.content.as_deref().unwrap_or(""),
+        };
+        let new_start = (start as isize + shift) as usize;
+        let new_end = new_start + inserted.len();
+        inverse.push(match op.op_type {
+            DiffOpType::Add => DiffOperation { op_type: DiffOpType::Remove, range: (new_start, new_end), content: None },
+            DiffOpType::Remove => DiffOperation { op_type: DiffOpType::Add, range: (new_start, new_start), content: Some(removed.to_string()) },
+            DiffOpType::Replace => DiffOperation { op_type: DiffOpType::Replace, range: (new_start, new_end), content: Some(removed.to_string()) },
+        });
+        shift += inserted.len() as isize - removed.len() as isize;
+    }
+    Ok(inverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn op(op_type: DiffOpType, range: (usize, usize), content: Option<&str>) -> DiffOperation {
+        DiffOperation { op_type, range, content: content.map(str::to_string) }
+    }
+
+    #[test]
+    fn test_inverse_restores_original() {
+        let before = "The quick brown fox jumps — over the lazy dog";
+        let ops = vec![
+            op(DiffOpType::Replace, (4, 9), Some("slow")),
+            op(DiffOpType::Remove, (25, 29), None),
+            op(DiffOpType::Add, (before.len(), before.len()), Some(" 🐕")),
+        ];
+        let after = apply_operations(before, &ops).unwrap();
+        assert_eq!(after, "The slow brown fox jumps over the lazy dog 🐕");
+        let inverse = invert_operations(before, &ops).unwrap();
+        assert_eq!(apply_operations(&after, &inverse).unwrap(), before);
+    }
+
+    #[test]
+    fn test_rejects_ranges_inside_a_char() {
+        let ops = vec![op(DiffOpType::Remove, (1, 2), None)];
+        assert!(apply_operations("é", &ops).is_err());
+    }
+
+    #[test]
+    fn test_rejects_overlapping_ranges() {
+        let ops = vec![op(DiffOpType::Remove, (5, 12), None), op(DiffOpType::Remove, (0, 12), None)];
+        assert!(matches!(apply_operations("hello, world", &ops), Err(UndoError::InvalidOperation(_))));
+        assert!(matches!(invert_operations("hello, world", &ops), Err(UndoError::InvalidOperation(_))));
+        // Touching ranges are fine
+        let ops = vec![op(DiffOpType::Remove, (5, 12), None), op(DiffOpType::Replace, (0, 5), Some("hi"))];
+        assert_eq!(apply_operations("hello, world", &ops).unwrap(), "hi");
+    }
+}
@@ -0,0 +1,82 @@
+use std::collections::{HashMap, HashSet};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::models::WakeCondition;
+
+// Named events a parked card can wait on. Connectors publish these; the parking
+// service wakes every card subscribed to the event.
+
+pub fn slack_thread_reply(channel: &str, thread_ts: &str) -> String {
+    format!("slack.thread_reply:{}:{}", channel, thread_ts)
+}
+
+pub fn gmail_thread_reply(thread_id: &str) -> String {
+    format!("gmail.thread_reply:{}", thread_id)
+}
+
+pub fn summary_updated(key: &str) -> String {
+    format!("memory.summary_updated:{}", key)
+}
+
+// The event a condition listens for; time conditions are handled by the scheduler
+pub fn event_name(condition: &WakeCondition) -> Option<String> {
+    match condition {
+        WakeCondition::Time(_) => None,
+        WakeCondition::Event(name) => Some(name.clone()),
+        WakeCondition::MemoryChange(key) => Some(summary_updated(key)),
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct WakeRegistry {
+    // event -> card -> when the card subscribed
+    subscriptions: HashMap<String, HashMap<Uuid, DateTime<Utc>>>,
+}
+
+impl WakeRegistry {
+    pub fn subscribe(&mut self, card_id: Uuid, event: String, since: DateTime<Utc>) {
+        self.subscriptions.entry(event).or_default().insert(card_id, since);
+    }
+
+    pub fn remove_card(&mut self, card_id: Uuid) {
+        self.subscriptions.retain(|_, cards| {
+            cards.remove(&card_id);
+            !cards.is_empty()
+        });
+    }
+
+    // Cards waiting on `event`. When the publisher knows when the event happened,
+    // cards that subscribed afterwards are skipped so old messages don't wake them.
+    pub fn subscribers(&self, event: &str, occurred_at: Option<DateTime<Utc>>) -> HashSet<Uuid> {
+        self.subscriptions.get(event)
+            .map(|cards| cards.iter()
+                .filter(|(_, since)| occurred_at.is_none_or(|at| at > **since))
+                .map(|(id, _)| *id)
+                .collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscribers_respect_subscription_time() {
+        let mut registry = WakeRegistry::default();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        let event = slack_thread_reply("C1", "1700000000.000100");
+        registry.subscribe(a, event.clone(), now - chrono::Duration::hours(1));
+        registry.subscribe(b, event.clone(), now);
+
+        let earlier = now - chrono::Duration::minutes(5);
+        assert_eq!(registry.subscribers(&event, Some(earlier)), HashSet::from([a]));
+        assert_eq!(registry.subscribers(&event, None).len(), 2);
+        assert!(registry.subscribers(&gmail_thread_reply("t1"), None).is_empty());
+
+        registry.remove_card(a);
+        assert_eq!(registry.subscribers(&event, None), HashSet::from([b]));
+        assert_eq!(event_name(&WakeCondition::MemoryChange("prd".into())).as_deref(), Some("memory.summary_updated:prd"));
+    }
+}
@@ -1,4 +1,22 @@
-use sqlx::{Sqlite, SqlitePool};
+use std::str::FromStr;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
+use sqlx::SqlitePool;
+
+// Applied in order on every connect. Each statement is safe to run again: tables and
+// indexes use `if not exists`, and an `add column` for a column that's already there is skipped.
+const MIGRATIONS: &[&str] = &[
+    include_str!("../../sqlite_migrations/0001_init.sql"),
+    include_str!("../../sqlite_migrations/0002_slack_map.sql"),
+    include_str!("../../sqlite_migrations/0003_cards_store.sql"),
+    include_str!("../../sqlite_migrations/0004_parking.sql"),
+    include_str!("../../sqlite_migrations/0005_wake_subscriptions.sql"),
+    include_str!("../../sqlite_migrations/0006_altitude_state.sql"),
+    include_str!("../../sqlite_migrations/0007_gmail_drafts.sql"),
+    include_str!("../../sqlite_migrations/0008_gmail_sync.sql"),
+    include_str!("../../sqlite_migrations/0009_imap_sync.sql"),
+    include_str!("../../sqlite_migrations/0010_slack_users.sql"),
+    include_str!("../../sqlite_migrations/0011_oauth_tokens.sql"),
+];
 
 #[derive(Clone)]
 pub struct SqliteDb {
@@ -8,10 +26,74 @@ pub struct SqliteDb {
 
 impl SqliteDb {
     pub async fn connect(database_url: &str) -> anyhow::Result<Self> {
-        let pool = SqlitePool::connect(database_url).await?;
-        // Run embedded migrations for SQLite schema
-        // Temporarily skip migration check - database already has the tables
-        // sqlx::migrate!("./sqlite_migrations").run(&pool).await?;
+        let options = SqliteConnectOptions::from_str(database_url)?.create_if_missing(true);
+        let pool = SqlitePoolOptions::new().connect_with(options).await?;
+        Self::migrate(&pool).await?;
         Ok(Self { pool, url: database_url.to_string() })
     }
+
+    // Single-connection in-memory database with the schema applied, for tests
+    #[cfg(test)]
+    pub async fn connect_in_memory() -> anyhow::Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await?;
+        Self::migrate(&pool).await?;
+        Ok(Self { pool, url: "sqlite::memory:".to_string() })
+    }
+
+    pub async fn migrate(pool: &SqlitePool) -> anyhow::Result<()> {
+        for statement in MIGRATIONS.iter().flat_map(|sql| statements(sql)) {
+            if let Some((table, column)) = added_column(&statement) {
+                if has_column(pool, &table, &column).await? {
+                    continue;
+                }
+            }
+            sqlx::raw_sql(&statement).execute(pool).await?;
+        }
+        Ok(())
+    }
+}
+
+// Statements of a migration file, comments dropped
+fn statements(sql: &str) -> Vec<String> {
+    let code: String = sql.lines()
+        .map(|line| line.split("--").next().unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n");
+    code.split(';').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+// (table, column) for "alter table <t> add column <c> ..."
+fn added_column(statement: &str) -> Option<(String, String)> {
+    let words: Vec<String> = statement.split_whitespace().map(str::to_lowercase).collect();
+    match words.as_slice() {
+        [alter, table_kw, table, add, column_kw, column, ..]
+            if alter == "alter" && table_kw == "table" && add == "add" && column_kw == "column" =>
+            Some((table.clone(), column.clone())),
+        _ => None,
+    }
+}
+
+async fn has_column(pool: &SqlitePool, table: &str, column: &str) -> anyhow::Result<bool> {
+    let found: Option<(String,)> = sqlx::query_as("select name from pragma_table_info(?) where lower(name) = ?")
+        .bind(table)
+        .bind(column)
+        .fetch_optional(pool)
+        .await?;
+    Ok(found.is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_migrations_run_twice() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        SqliteDb::migrate(&db.pool).await.unwrap();
+        assert!(has_column(&db.pool, "cards", "deleted_at").await.unwrap());
+        assert!(has_column(&db.pool, "gmail_messages", "source").await.unwrap());
+    }
 }
@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+use crate::models::{Altitude, Gate};
+
+// Per-user altitude and the gates passed on the way there
+#[derive(Clone)]
+pub struct AltitudeRepo {
+    pub pool: SqlitePool,
+}
+
+fn label(altitude: Altitude) -> String {
+    serde_json::to_value(altitude).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+fn parse(label: &str) -> Option<Altitude> {
+    serde_json::from_value(serde_json::Value::String(label.to_string())).ok()
+}
+
+impl AltitudeRepo {
+    pub fn new(pool: SqlitePool) -> Self { Self { pool } }
+
+    // (current, previous)
+    pub async fn get(&self, user_id: &str) -> sqlx::Result<Option<(Altitude, Option<Altitude>)>> {
+        let row = sqlx::query("select current, previous from altitude_state where user_id = ?1")
+            .bind(user_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else { return Ok(None) };
+        let current: String = row.get(0);
+        let previous: Option<String> = row.get(1);
+        Ok(parse(&current).map(|c| (c, previous.as_deref().and_then(parse))))
+    }
+
+    // Moves the user through `gate` and records it in one transaction
+    pub async fn record_transition(&self, user_id: &str, gate: &Gate) -> sqlx::Result<()> {
+        let payload = serde_json::to_value(gate).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "insert into altitude_state (user_id, current, previous) values (?1, ?2, ?3)
+             on conflict(user_id) do update set current=excluded.current, previous=excluded.previous, updated_at=current_timestamp"
+        )
+        .bind(user_id)
+        .bind(label(gate.to_altitude))
+        .bind(label(gate.from_altitude))
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query("insert into altitude_gates (user_id, gate, passed_at) values (?1, ?2, ?3)")
+            .bind(user_id)
+            .bind(&payload)
+            .bind(gate.timestamp.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    // Oldest first
+    pub async fn gates_since(&self, user_id: &str, since: DateTime<Utc>) -> sqlx::Result<Vec<Gate>> {
+        let rows = sqlx::query("select gate from altitude_gates where user_id = ?1 and passed_at >= ?2 order by passed_at, id")
+            .bind(user_id)
+            .bind(since.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await?;
+        let mut gates = Vec::with_capacity(rows.len());
+        for row in rows {
+            let text: String = row.get(0);
+            match serde_json::from_str(&text) {
+                Ok(gate) => gates.push(gate),
+                Err(e) => tracing::warn!("skipping unreadable altitude gate: {}", e),
+            }
+        }
+        Ok(gates)
+    }
+}
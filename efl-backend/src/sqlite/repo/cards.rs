@@ -1,47 +1,208 @@
 use serde_json::Value;
-use sqlx::{Row, SqlitePool};
+use sqlx::{Row, SqliteExecutor, SqlitePool};
+use crate::models::{Altitude, Card, CardState, CardType};
 
 #[derive(Clone)]
 pub struct CardsRepo {
     pub pool: SqlitePool,
 }
 
+// Optional filters for listing cards; `None` means "any".
+#[derive(Debug, Clone, Default)]
+pub struct CardFilter {
+    pub state: Option<CardState>,
+    pub card_type: Option<CardType>,
+    pub altitude: Option<Altitude>,
+    pub limit: Option<i64>,
+}
+
 impl CardsRepo {
     pub fn new(pool: SqlitePool) -> Self { Self { pool } }
 
-    pub async fn upsert(&self, id: &str, kind: &str, state: &str, payload: &Value) -> sqlx::Result<()> {
+    pub async fn get(&self, id: &str) -> sqlx::Result<Option<(String, String, Value)>> {
+        fetch(&self.pool, id).await
+    }
+
+    // Typed helpers on top of the raw kind/state/payload rows
+
+    // Inserts or replaces the card payload. New rows start in the state implied by the card's
+    // status; existing rows keep their FSM state, which only `set_state` changes.
+    pub async fn save_card(&self, card: &Card) -> sqlx::Result<()> {
+        let payload = serde_json::to_value(card).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
         let sql = r#"
-            insert into cards (id, kind, state, payload)
-            values (?1, ?2, ?3, ?4)
+            insert into cards (id, kind, state, payload, altitude)
+            values (?1, ?2, ?3, ?4, ?5)
             on conflict(id) do update set
-              state=excluded.state,
+              kind=excluded.kind,
               payload=excluded.payload,
+              altitude=excluded.altitude,
+              deleted_at=null,
               updated_at=current_timestamp
         "#;
         sqlx::query(sql)
-            .bind(id)
-            .bind(kind)
-            .bind(state)
-            .bind(payload)
+            .bind(card.id.to_string())
+            .bind(kind_label(&card.card_type))
+            .bind(CardState::from_status(&card.status).as_str())
+            .bind(&payload)
+            .bind(altitude_label(&card.altitude))
             .execute(&self.pool)
             .await?;
         Ok(())
     }
 
-    pub async fn get(&self, id: &str) -> sqlx::Result<Option<(String, String, Value)>> {
-        let row = sqlx::query("select kind, state, payload from cards where id = ?1")
+    pub async fn get_state(&self, id: &str) -> sqlx::Result<Option<CardState>> {
+        Ok(self.get(id).await?.and_then(|(_, state, _)| CardState::parse(&state)))
+    }
+
+    pub async fn set_state(&self, id: &str, state: CardState) -> sqlx::Result<()> {
+        sqlx::query("update cards set state = ?2, updated_at = current_timestamp where id = ?1")
             .bind(id)
-            .fetch_optional(&self.pool)
+            .bind(state.as_str())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_card(&self, id: &str) -> sqlx::Result<Option<Card>> {
+        match self.get(id).await? {
+            Some((_, _, payload)) => decode_card(payload).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list_cards(&self, filter: &CardFilter) -> sqlx::Result<Vec<Card>> {
+        let sql = r#"
+            select payload from cards
+            where deleted_at is null
+              and (?1 is null or state = ?1)
+              and (?2 is null or kind = ?2)
+              and (?3 is null or altitude = ?3)
+            order by updated_at desc, created_at desc
+            limit ?4
+        "#;
+        let rows = sqlx::query(sql)
+            .bind(filter.state.map(|s| s.as_str()))
+            .bind(filter.card_type.as_ref().map(kind_label))
+            .bind(filter.altitude.as_ref().map(altitude_label))
+            .bind(filter.limit.unwrap_or(-1))
+            .fetch_all(&self.pool)
             .await?;
-        if let Some(row) = row {
-            let kind: String = row.get(0);
-            let state: String = row.get(1);
-            // payload can be JSON or TEXT; fetch as String and parse
-            let payload_text: String = row.get(2);
-            let payload = serde_json::from_str(&payload_text).unwrap_or(Value::Null);
-            Ok(Some((kind, state, payload)))
-        } else {
-            Ok(None)
+        let mut cards = Vec::with_capacity(rows.len());
+        for row in rows {
+            let payload_text: String = row.get(0);
+            match serde_json::from_str::<Card>(&payload_text) {
+                Ok(card) => cards.push(card),
+                Err(e) => tracing::warn!("skipping card with unreadable payload: {}", e),
+            }
         }
+        Ok(cards)
+    }
+
+    // Marks a card deleted without dropping the row; returns false if nothing matched
+    pub async fn soft_delete(&self, id: &str) -> sqlx::Result<bool> {
+        let res = sqlx::query("update cards set deleted_at = current_timestamp, updated_at = current_timestamp where id = ?1 and deleted_at is null")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
+
+pub async fn fetch<'e>(db: impl SqliteExecutor<'e>, id: &str) -> sqlx::Result<Option<(String, String, Value)>> {
+    let row = sqlx::query("select kind, state, payload from cards where id = ?1 and deleted_at is null")
+        .bind(id)
+        .fetch_optional(db)
+        .await?;
+    if let Some(row) = row {
+        let kind: String = row.get(0);
+        let state: String = row.get(1);
+        // payload can be JSON or TEXT; fetch as String and parse
+        let payload_text: String = row.get(2);
+        let payload = serde_json::from_str(&payload_text).unwrap_or(Value::Null);
+        Ok(Some((kind, state, payload)))
+    } else {
+        Ok(None)
+    }
+}
+
+// Saves the card in state `to`, but only if the row is still in `expected` (the raw stored
+// value); false means another action got there first
+pub async fn update_if_state<'e>(db: impl SqliteExecutor<'e>, card: &Card, expected: &str, to: CardState) -> sqlx::Result<bool> {
+    let payload = serde_json::to_value(card).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+    let res = sqlx::query(r#"
+        update cards set payload = ?2, state = ?3, altitude = ?4, updated_at = current_timestamp
+        where id = ?1 and state = ?5 and deleted_at is null
+    "#)
+        .bind(card.id.to_string())
+        .bind(&payload)
+        .bind(to.as_str())
+        .bind(altitude_label(&card.altitude))
+        .bind(expected)
+        .execute(db)
+        .await?;
+    Ok(res.rows_affected() == 1)
+}
+
+pub fn decode_card(payload: Value) -> sqlx::Result<Card> {
+    serde_json::from_value(payload).map_err(|e| sqlx::Error::Decode(Box::new(e)))
+}
+
+pub fn kind_label(card_type: &CardType) -> &'static str {
+    match card_type {
+        CardType::DoNow => "DoNow",
+        CardType::Ship => "Ship",
+        CardType::Amplify => "Amplify",
+        CardType::Orient => "Orient",
+        CardType::Parked => "Parked",
+        CardType::BreakIn => "BreakIn",
+        CardType::BatchReview => "BatchReview",
+    }
+}
+
+pub fn altitude_label(altitude: &Altitude) -> &'static str {
+    match altitude {
+        Altitude::Do => "Do",
+        Altitude::Ship => "Ship",
+        Altitude::Amplify => "Amplify",
+        Altitude::Orient => "Orient",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CardPatch;
+    use crate::services::mock_data::{generate_mock_do_now_card, generate_mock_ship_card};
+
+    #[tokio::test]
+    async fn test_save_list_update_delete() {
+        let db = crate::sqlite::db::SqliteDb::connect_in_memory().await.unwrap();
+        let repo = CardsRepo::new(db.pool);
+
+        let do_now = generate_mock_do_now_card();
+        let ship = generate_mock_ship_card();
+        repo.save_card(&do_now).await.unwrap();
+        repo.save_card(&ship).await.unwrap();
+
+        let fetched = repo.get_card(&do_now.id.to_string()).await.unwrap().unwrap();
+        assert_eq!(fetched.title, do_now.title);
+
+        let ships = repo.list_cards(&CardFilter { card_type: Some(CardType::Ship), ..Default::default() }).await.unwrap();
+        assert_eq!(ships.len(), 1);
+        assert_eq!(ships[0].id, ship.id);
+
+        let mut updated = fetched.clone();
+        CardPatch { title: Some("Renamed".to_string()), ..Default::default() }.apply(&mut updated);
+        repo.save_card(&updated).await.unwrap();
+        assert_eq!(repo.get_card(&do_now.id.to_string()).await.unwrap().unwrap().title, "Renamed");
+        repo.set_state(&do_now.id.to_string(), CardState::Committed).await.unwrap();
+        repo.save_card(&updated).await.unwrap();
+        let committed = repo.list_cards(&CardFilter { state: Some(CardState::Committed), ..Default::default() }).await.unwrap();
+        assert_eq!(committed.len(), 1);
+
+        assert!(repo.soft_delete(&do_now.id.to_string()).await.unwrap());
+        assert!(repo.get_card(&do_now.id.to_string()).await.unwrap().is_none());
+        assert!(!repo.soft_delete(&do_now.id.to_string()).await.unwrap());
+        assert_eq!(repo.list_cards(&CardFilter::default()).await.unwrap().len(), 1);
     }
 }
@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use uuid::Uuid;
+use crate::models::{CardDraft, DraftKind, DraftState};
+
+// The Gmail draft written for each card
+#[derive(Clone)]
+pub struct DraftRepo {
+    pub pool: SqlitePool,
+}
+
+fn label<T: serde::Serialize>(value: T) -> String {
+    serde_json::to_value(value).ok().and_then(|v| v.as_str().map(str::to_string)).unwrap_or_default()
+}
+
+fn parse<T: serde::de::DeserializeOwned>(label: &str) -> Option<T> {
+    serde_json::from_value(serde_json::Value::String(label.to_string())).ok()
+}
+
+fn from_row(row: &SqliteRow) -> Option<CardDraft> {
+    let kind: String = row.get("kind");
+    let state: String = row.get("state");
+    let updated_at: String = row.get("updated_at");
+    Some(CardDraft {
+        draft_id: row.get("draft_id"),
+        kind: parse::<DraftKind>(&kind)?,
+        state: parse::<DraftState>(&state)?,
+        message_id: row.get("message_id"),
+        thread_id: row.get("thread_id"),
+        to: row.get("to_addr"),
+        subject: row.get("subject"),
+        body: row.get("body"),
+        updated_at: DateTime::parse_from_rfc3339(&updated_at).map(|d| d.with_timezone(&Utc)).ok()?,
+    })
+}
+
+const COLUMNS: &str = "card_id, draft_id, kind, state, message_id, thread_id, to_addr, subject, body, updated_at";
+
+impl DraftRepo {
+    pub fn new(pool: SqlitePool) -> Self { Self { pool } }
+
+    pub async fn save(&self, card_id: Uuid, draft: &CardDraft) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into gmail_drafts (card_id, draft_id, kind, state, message_id, thread_id, to_addr, subject, body, updated_at)
+             values (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             on conflict(card_id) do update set
+               draft_id=excluded.draft_id, kind=excluded.kind, state=excluded.state, message_id=excluded.message_id,
+               thread_id=excluded.thread_id, to_addr=excluded.to_addr, subject=excluded.subject, body=excluded.body,
+               updated_at=excluded.updated_at"
+        )
+        .bind(card_id.to_string())
+        .bind(&draft.draft_id)
+        .bind(label(draft.kind))
+        .bind(label(draft.state))
+        .bind(&draft.message_id)
+        .bind(&draft.thread_id)
+        .bind(&draft.to)
+        .bind(&draft.subject)
+        .bind(&draft.body)
+        .bind(draft.updated_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, card_id: Uuid) -> sqlx::Result<Option<CardDraft>> {
+        let row = sqlx::query(&format!("select {COLUMNS} from gmail_drafts where card_id = ?1"))
+            .bind(card_id.to_string())
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().and_then(from_row))
+    }
+
+    pub async fn for_cards(&self, card_ids: &[Uuid]) -> sqlx::Result<Vec<(Uuid, CardDraft)>> {
+        if card_ids.is_empty() {
+            return Ok(vec![]);
+        }
+        let placeholders = vec!["?"; card_ids.len()].join(", ");
+        let sql = format!("select {COLUMNS} from gmail_drafts where card_id in ({placeholders})");
+        let mut query = sqlx::query(&sql);
+        for id in card_ids {
+            query = query.bind(id.to_string());
+        }
+        let rows = query.fetch_all(&self.pool).await?;
+        Ok(rows.iter()
+            .filter_map(|row| {
+                let card_id: String = row.get("card_id");
+                Some((Uuid::parse_str(&card_id).ok()?, from_row(row)?))
+            })
+            .collect())
+    }
+
+    // Moves the draft from `from` to `to` only if it is still `draft_id` in state `from`;
+    // false means it was rewritten or another request got to it first
+    pub async fn transition(&self, card_id: Uuid, draft_id: &str, from: DraftState, to: DraftState) -> sqlx::Result<bool> {
+        let result = sqlx::query("update gmail_drafts set state = ?4, updated_at = ?5 where card_id = ?1 and draft_id = ?2 and state = ?3")
+            .bind(card_id.to_string())
+            .bind(draft_id)
+            .bind(label(from))
+            .bind(label(to))
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sqlite::db::SqliteDb;
+
+    #[tokio::test]
+    async fn test_draft_round_trip() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        let repo = DraftRepo::new(db.pool);
+        let card_id = Uuid::new_v4();
+        let mut draft = CardDraft {
+            draft_id: "r-123".to_string(),
+            kind: DraftKind::Reply,
+            state: DraftState::Drafted,
+            message_id: "m1".to_string(),
+            thread_id: "t1".to_string(),
+            to: "Ana <ana@example.com>".to_string(),
+            subject: "Re: Launch".to_string(),
+            body: "Sounds good.".to_string(),
+            updated_at: Utc::now(),
+        };
+        repo.save(card_id, &draft).await.unwrap();
+        draft.draft_id = "r-456".to_string();
+        repo.save(card_id, &draft).await.unwrap();
+        // Only the current draft can be claimed, and only once
+        assert!(!repo.transition(card_id, "r-123", DraftState::Drafted, DraftState::Sent).await.unwrap());
+        assert!(repo.transition(card_id, "r-456", DraftState::Drafted, DraftState::Sent).await.unwrap());
+        assert!(!repo.transition(card_id, "r-456", DraftState::Drafted, DraftState::Sent).await.unwrap());
+
+        let stored = repo.get(card_id).await.unwrap().unwrap();
+        assert_eq!(stored.draft_id, "r-456");
+        assert_eq!(stored.state, DraftState::Sent);
+        assert_eq!(repo.for_cards(&[card_id, Uuid::new_v4()]).await.unwrap().len(), 1);
+        assert!(!repo.transition(Uuid::new_v4(), "r-456", DraftState::Drafted, DraftState::Discarded).await.unwrap());
+    }
+}
@@ -0,0 +1,233 @@
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::SqliteRow;
+use sqlx::{Row, SqlitePool};
+use crate::connectors::email::{EmailMessage, MailState};
+use crate::connectors::gmail::is_unread;
+use crate::connectors::imap::is_imap_id;
+
+// The only Gmail mailbox we sync
+const ACCOUNT: &str = "me";
+
+pub const SOURCE_GMAIL: &str = "gmail";
+pub const SOURCE_IMAP: &str = "imap";
+
+// Local copy of the inbox kept by the sync workers, whether it came from Gmail or IMAP
+#[derive(Clone)]
+pub struct GmailCacheRepo {
+    pub pool: SqlitePool,
+}
+
+fn source_of(message: &EmailMessage) -> &'static str {
+    if is_imap_id(&message.id) { SOURCE_IMAP } else { SOURCE_GMAIL }
+}
+
+// The unread column is kept current by the syncs and mark-read; the stored JSON isn't
+fn decode(row: &SqliteRow) -> Option<EmailMessage> {
+    let mut message: EmailMessage = serde_json::from_str(row.get("message")).ok()?;
+    message.state = match (row.get::<bool, _>("unread"), message.state) {
+        (true, _) => MailState::Unread,
+        (false, MailState::Unread) => MailState::Read,
+        (false, state) => state,
+    };
+    Some(message)
+}
+
+fn encode<T: serde::Serialize>(value: &T) -> sqlx::Result<String> {
+    serde_json::to_string(value).map_err(|e| sqlx::Error::Encode(Box::new(e)))
+}
+
+impl GmailCacheRepo {
+    pub fn new(pool: SqlitePool) -> Self { Self { pool } }
+
+    pub async fn history_id(&self) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar("select history_id from gmail_sync_state where account = ?1")
+            .bind(ACCOUNT)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn set_history_id(&self, history_id: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into gmail_sync_state (account, history_id, synced_at) values (?1, ?2, current_timestamp)
+             on conflict(account) do update set history_id=excluded.history_id, synced_at=current_timestamp"
+        )
+        .bind(ACCOUNT)
+        .bind(history_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // (uid_validity, last_uid) for an IMAP mailbox
+    pub async fn imap_state(&self, account: &str, mailbox: &str) -> sqlx::Result<Option<(u32, u32)>> {
+        let row = sqlx::query("select uid_validity, last_uid from imap_sync_state where account = ?1 and mailbox = ?2")
+            .bind(account)
+            .bind(mailbox)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| (row.get::<i64, _>("uid_validity") as u32, row.get::<i64, _>("last_uid") as u32)))
+    }
+
+    pub async fn set_imap_state(&self, account: &str, mailbox: &str, uid_validity: u32, last_uid: u32) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into imap_sync_state (account, mailbox, uid_validity, last_uid, synced_at) values (?1, ?2, ?3, ?4, current_timestamp)
+             on conflict(account, mailbox) do update set
+               uid_validity=excluded.uid_validity, last_uid=excluded.last_uid, synced_at=current_timestamp"
+        )
+        .bind(account)
+        .bind(mailbox)
+        .bind(uid_validity as i64)
+        .bind(last_uid as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, id: &str) -> sqlx::Result<Option<EmailMessage>> {
+        let row = sqlx::query("select message, unread from gmail_messages where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.as_ref().and_then(decode))
+    }
+
+    pub async fn contains(&self, id: &str) -> sqlx::Result<bool> {
+        let found: Option<i64> = sqlx::query_scalar("select 1 from gmail_messages where id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(found.is_some())
+    }
+
+    // Gmail labels are left to `set_labels`, from the history sync
+    pub async fn upsert(&self, message: &EmailMessage, class: Option<&serde_json::Value>, received_at: DateTime<Utc>) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into gmail_messages (id, thread_id, message, class, unread, received_at, source, updated_at)
+             values (?1, ?2, ?3, ?4, ?5, ?6, ?7, current_timestamp)
+             on conflict(id) do update set
+               thread_id=excluded.thread_id, message=excluded.message, class=excluded.class,
+               unread=excluded.unread, received_at=excluded.received_at, updated_at=current_timestamp"
+        )
+        .bind(&message.id)
+        .bind(&message.thread_id)
+        .bind(encode(message)?)
+        .bind(class.map(encode).transpose()?)
+        .bind(message.is_unread())
+        .bind(received_at.to_rfc3339())
+        .bind(source_of(message))
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // Returns false when the message isn't cached
+    pub async fn set_labels(&self, id: &str, labels: &[String]) -> sqlx::Result<bool> {
+        let result = sqlx::query("update gmail_messages set labels = ?2, unread = ?3, updated_at = current_timestamp where id = ?1")
+            .bind(id)
+            .bind(encode(&labels)?)
+            .bind(is_unread(labels))
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // Local effect of archive/mark-read, ahead of the next sync
+    pub async fn mark_read(&self, ids: &[String]) -> sqlx::Result<()> {
+        for id in ids {
+            sqlx::query("update gmail_messages set unread = 0, updated_at = current_timestamp where id = ?1")
+                .bind(id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    // After a full sync: whatever the source no longer lists as unread was read elsewhere
+    pub async fn keep_unread(&self, source: &str, ids: &[String]) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("update gmail_messages set unread = 0 where unread = 1 and source = ?1")
+            .bind(source)
+            .execute(&mut *tx)
+            .await?;
+        for id in ids {
+            sqlx::query("update gmail_messages set unread = 1 where id = ?1")
+                .bind(id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await
+    }
+
+    pub async fn delete(&self, id: &str) -> sqlx::Result<()> {
+        sqlx::query("delete from gmail_messages where id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // Newest first, with the cached classification
+    pub async fn unread(&self, limit: i64) -> sqlx::Result<Vec<(EmailMessage, Option<serde_json::Value>)>> {
+        let rows = sqlx::query("select message, unread, class from gmail_messages where unread = 1 order by received_at desc limit ?1")
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter()
+            .filter_map(|row| {
+                let class: Option<String> = row.get("class");
+                Some((decode(row)?, class.and_then(|c| serde_json::from_str(&c).ok())))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::connectors::gmail::message_from_payload;
+    use crate::sqlite::db::SqliteDb;
+
+    fn message(id: &str, labels: &[&str]) -> EmailMessage {
+        let labels: Vec<String> = labels.iter().map(|l| l.to_string()).collect();
+        message_from_payload(id.to_string(), format!("t-{id}"), "hi".to_string(), None, &labels)
+    }
+
+    #[tokio::test]
+    async fn test_cache_tracks_unread() {
+        let db = SqliteDb::connect_in_memory().await.unwrap();
+        let repo = GmailCacheRepo::new(db.pool);
+        assert!(repo.history_id().await.unwrap().is_none());
+        repo.set_history_id("100").await.unwrap();
+        repo.set_history_id("120").await.unwrap();
+        assert_eq!(repo.history_id().await.unwrap().as_deref(), Some("120"));
+
+        let now = Utc::now();
+        let class = serde_json::json!({ "categoryLabel": "personal" });
+        repo.upsert(&message("m1", &["INBOX", "UNREAD"]), Some(&class), now).await.unwrap();
+        repo.upsert(&message("m2", &["INBOX", "UNREAD"]), None, now - chrono::Duration::hours(1)).await.unwrap();
+        repo.upsert(&message("m3", &["INBOX"]), None, now).await.unwrap();
+
+        let unread = repo.unread(10).await.unwrap();
+        assert_eq!(unread.iter().map(|(m, _)| m.id.as_str()).collect::<Vec<_>>(), vec!["m1", "m2"]);
+        assert_eq!(unread[0].1.as_ref(), Some(&class));
+
+        assert!(repo.set_labels("m1", &["INBOX".to_string()]).await.unwrap());
+        assert!(!repo.set_labels("gone", &[]).await.unwrap());
+        repo.upsert(&message("imap:7:1", &["INBOX", "UNREAD"]), None, now - chrono::Duration::hours(2)).await.unwrap();
+        repo.keep_unread(SOURCE_GMAIL, &["m3".to_string()]).await.unwrap();
+        // IMAP mail is left to the IMAP sync
+        let ids = |unread: Vec<(EmailMessage, _)>| unread.into_iter().map(|(m, _)| m.id).collect::<Vec<_>>();
+        assert_eq!(ids(repo.unread(10).await.unwrap()), vec!["m3", "imap:7:1"]);
+        repo.keep_unread(SOURCE_IMAP, &[]).await.unwrap();
+        repo.delete("m3").await.unwrap();
+        assert!(!repo.contains("m3").await.unwrap());
+        assert!(repo.unread(10).await.unwrap().is_empty());
+        let read = repo.get("imap:7:1").await.unwrap().unwrap();
+        assert_eq!((read.thread_id.as_str(), read.state), ("t-imap:7:1", MailState::Read));
+
+        assert!(repo.imap_state("me@host", "INBOX").await.unwrap().is_none());
+        repo.set_imap_state("me@host", "INBOX", 7, 40).await.unwrap();
+        repo.set_imap_state("me@host", "INBOX", 7, 42).await.unwrap();
+        assert_eq!(repo.imap_state("me@host", "INBOX").await.unwrap(), Some((7, 42)));
+    }
+}
@@ -1,5 +1,10 @@
 pub mod cards;
 pub mod queue;
 pub mod wakes;
+pub mod parked;
 pub mod traces;
 pub mod slack_map;
+pub mod altitude;
+pub mod drafts;
+pub mod gmail_cache;
+pub mod slack_users;
@@ -0,0 +1,45 @@
+use sqlx::{Row, SqlitePool};
+use crate::models::Card;
+
+// Cards exactly as they were before parking, so unpark can restore them
+#[derive(Clone)]
+pub struct ParkedRepo {
+    pub pool: SqlitePool,
+}
+
+impl ParkedRepo {
+    pub fn new(pool: SqlitePool) -> Self { Self { pool } }
+
+    pub async fn save(&self, original: &Card, reason: &str) -> sqlx::Result<()> {
+        let payload = serde_json::to_value(original).map_err(|e| sqlx::Error::Encode(Box::new(e)))?;
+        sqlx::query(
+            "insert into parked_cards (card_id, original, reason) values (?1, ?2, ?3)
+             on conflict(card_id) do update set original=excluded.original, reason=excluded.reason, parked_at=current_timestamp"
+        )
+        .bind(original.id.to_string())
+        .bind(&payload)
+        .bind(reason)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, card_id: &str) -> sqlx::Result<Option<(Card, String)>> {
+        let row = sqlx::query("select original, reason from parked_cards where card_id = ?1")
+            .bind(card_id)
+            .fetch_optional(&self.pool)
+            .await?;
+        let Some(row) = row else { return Ok(None) };
+        let original: String = row.get(0);
+        let card = serde_json::from_str(&original).map_err(|e| sqlx::Error::Decode(Box::new(e)))?;
+        Ok(Some((card, row.get(1))))
+    }
+
+    pub async fn remove(&self, card_id: &str) -> sqlx::Result<bool> {
+        let res = sqlx::query("delete from parked_cards where card_id = ?1")
+            .bind(card_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected() > 0)
+    }
+}
@@ -1,22 +1,67 @@
+use serde::Serialize;
 use serde_json::Value;
-use sqlx::SqlitePool;
+use sqlx::{Row, SqliteExecutor, SqlitePool};
 
 #[derive(Clone)]
 pub struct QueueRepo {
     pub pool: SqlitePool,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueEvent {
+    pub id: i64,
+    pub card_id: String,
+    pub event: String,
+    pub actor: String,
+    pub meta: Option<Value>,
+    pub created_at: String,
+}
+
 impl QueueRepo {
     pub fn new(pool: SqlitePool) -> Self { Self { pool } }
 
     pub async fn append_event(&self, card_id: &str, event: &str, actor: &str, meta: Option<&Value>) -> sqlx::Result<i64> {
-        let res = sqlx::query("insert into queue_events (card_id, event, actor, meta) values (?1,?2,?3,?4)")
+        insert_event(&self.pool, card_id, event, actor, meta).await
+    }
+
+    // Events for one card in the order they were appended
+    pub async fn list_for_card(&self, card_id: &str) -> sqlx::Result<Vec<QueueEvent>> {
+        let rows = sqlx::query("select id, card_id, event, actor, meta, cast(created_at as text) from queue_events where card_id = ?1 order by id")
             .bind(card_id)
-            .bind(event)
-            .bind(actor)
-            .bind(meta)
-            .execute(&self.pool)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.iter().map(row_to_event).collect())
+    }
+
+    pub async fn card_ids(&self) -> sqlx::Result<Vec<String>> {
+        let rows = sqlx::query("select card_id from queue_events group by card_id order by min(id)")
+            .fetch_all(&self.pool)
             .await?;
-        Ok(res.last_insert_rowid())
+        Ok(rows.iter().map(|r| r.get::<String, _>(0)).collect())
+    }
+}
+
+// `append_event` on any executor, so it can join a transaction
+pub async fn insert_event<'e>(db: impl SqliteExecutor<'e>, card_id: &str, event: &str, actor: &str, meta: Option<&Value>) -> sqlx::Result<i64> {
+    let res = sqlx::query("insert into queue_events (card_id, event, actor, meta) values (?1,?2,?3,?4)")
+        .bind(card_id)
+        .bind(event)
+        .bind(actor)
+        .bind(meta)
+        .execute(db)
+        .await?;
+    Ok(res.last_insert_rowid())
+}
+
+fn row_to_event(row: &sqlx::sqlite::SqliteRow) -> QueueEvent {
+    // meta can be JSON or TEXT; fetch as String and parse
+    let meta: Option<String> = row.get(4);
+    QueueEvent {
+        id: row.get(0),
+        card_id: row.get(1),
+        event: row.get(2),
+        actor: row.get(3),
+        meta: meta.and_then(|m| serde_json::from_str(&m).ok()),
+        created_at: row.get(5),
     }
 }
@@ -7,7 +7,15 @@ pub struct SlackMapRepo { pub pool: SqlitePool }
 impl SlackMapRepo {
     pub fn new(pool: SqlitePool) -> Self { Self { pool } }
 
+    // A thread maps to one card; mapping it again moves it to `card_id`
     pub async fn upsert(&self, card_id: &str, channel: &str, thread_ts: &str) -> sqlx::Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("delete from slack_threads where channel = ?1 and thread_ts = ?2 and card_id != ?3")
+            .bind(channel)
+            .bind(thread_ts)
+            .bind(card_id)
+            .execute(&mut *tx)
+            .await?;
         sqlx::query(
             "insert into slack_threads (card_id, channel, thread_ts) values (?1,?2,?3)
              on conflict(card_id) do update set channel=excluded.channel, thread_ts=excluded.thread_ts"
@@ -15,18 +23,24 @@ impl SlackMapRepo {
         .bind(card_id)
         .bind(channel)
         .bind(thread_ts)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await?;
-        Ok(())
+        tx.commit().await
     }
 
     pub async fn find_card_by_thread(&self, channel: &str, thread_ts: &str) -> sqlx::Result<Option<String>> {
-        let row = sqlx::query("select card_id from slack_threads where channel = ?1 and thread_ts = ?2")
+        sqlx::query_scalar("select card_id from slack_threads where channel = ?1 and thread_ts = ?2")
             .bind(channel)
             .bind(thread_ts)
             .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn find_thread_by_card(&self, card_id: &str) -> sqlx::Result<Option<(String, String)>> {
+        let row = sqlx::query("select channel, thread_ts from slack_threads where card_id = ?1")
+            .bind(card_id)
+            .fetch_optional(&self.pool)
             .await?;
-        Ok(row.map(|r| r.get::<String, _>(0)))
+        Ok(row.map(|r| (r.get::<String, _>(0), r.get::<String, _>(1))))
     }
 }
-
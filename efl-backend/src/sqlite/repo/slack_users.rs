@@ -0,0 +1,35 @@
+use sqlx::SqlitePool;
+
+// Slack display names resolved through users.info
+#[derive(Clone)]
+pub struct SlackUserRepo {
+    pub pool: SqlitePool,
+}
+
+impl SlackUserRepo {
+    pub fn new(pool: SqlitePool) -> Self { Self { pool } }
+
+    // None when unknown or older than `max_age_hours`, so renames are picked up eventually
+    pub async fn display_name(&self, user_id: &str, max_age_hours: i64) -> sqlx::Result<Option<String>> {
+        sqlx::query_scalar(
+            "select display_name from slack_users
+             where user_id = ?1 and fetched_at > datetime('now', printf('-%d hours', ?2))"
+        )
+        .bind(user_id)
+        .bind(max_age_hours)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn upsert(&self, user_id: &str, display_name: &str) -> sqlx::Result<()> {
+        sqlx::query(
+            "insert into slack_users (user_id, display_name, fetched_at) values (?1, ?2, current_timestamp)
+             on conflict(user_id) do update set display_name=excluded.display_name, fetched_at=current_timestamp"
+        )
+        .bind(user_id)
+        .bind(display_name)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
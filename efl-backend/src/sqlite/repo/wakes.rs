@@ -1,10 +1,25 @@
-use sqlx::SqlitePool;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::{Row, SqlitePool};
 
 #[derive(Clone)]
 pub struct WakesRepo {
     pub pool: SqlitePool,
 }
 
+#[derive(Debug, Clone)]
+pub struct PendingWake {
+    pub id: i64,
+    pub card_id: String,
+    pub wake_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub struct WakeSubscription {
+    pub card_id: String,
+    pub event: String,
+    pub created_at: DateTime<Utc>,
+}
+
 impl WakesRepo {
     pub fn new(pool: SqlitePool) -> Self { Self { pool } }
 
@@ -17,4 +32,66 @@ impl WakesRepo {
             .await?;
         Ok(res.last_insert_rowid())
     }
+
+    // Wakes that haven't fired yet, soonest first
+    pub async fn pending(&self) -> sqlx::Result<Vec<PendingWake>> {
+        let rows = sqlx::query("select id, card_id, cast(wake_at as text) from wakes where fired_at is null order by wake_at, id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|row| PendingWake {
+            id: row.get(0),
+            card_id: row.get(1),
+            wake_at: parse_sqlite_datetime(&row.get::<String, _>(2)),
+        }).collect())
+    }
+
+    pub async fn reschedule(&self, card_id: &str, wake_at: &str) -> sqlx::Result<u64> {
+        let res = sqlx::query("update wakes set wake_at = datetime(?2) where card_id = ?1 and fired_at is null")
+            .bind(card_id)
+            .bind(wake_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+
+    pub async fn subscribe(&self, card_id: &str, event: &str) -> sqlx::Result<i64> {
+        let res = sqlx::query("insert into wake_subscriptions (card_id, event) values (?1, ?2)")
+            .bind(card_id)
+            .bind(event)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.last_insert_rowid())
+    }
+
+    pub async fn pending_subscriptions(&self) -> sqlx::Result<Vec<WakeSubscription>> {
+        let rows = sqlx::query("select card_id, event, cast(created_at as text) from wake_subscriptions where fired_at is null order by id")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|row| WakeSubscription {
+            card_id: row.get(0),
+            event: row.get(1),
+            created_at: parse_sqlite_datetime(&row.get::<String, _>(2)),
+        }).collect())
+    }
+
+    // Closes every pending wake and subscription for the card, whether it fired or was unparked by hand
+    pub async fn mark_fired(&self, card_id: &str) -> sqlx::Result<u64> {
+        let res = sqlx::query("update wakes set fired_at = current_timestamp where card_id = ?1 and fired_at is null")
+            .bind(card_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("update wake_subscriptions set fired_at = current_timestamp where card_id = ?1 and fired_at is null")
+            .bind(card_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(res.rows_affected())
+    }
+}
+
+// SQLite `datetime()` values are UTC "YYYY-MM-DD HH:MM:SS"
+pub fn parse_sqlite_datetime(s: &str) -> DateTime<Utc> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .map(|dt| dt.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(s).map(|dt| dt.with_timezone(&Utc)))
+        .unwrap_or_else(|_| Utc::now())
 }
@@ -1,4 +1,7 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, RwLock};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SseEvent {
@@ -6,3 +9,106 @@ pub struct SseEvent {
     pub data: String,
 }
 
+// Events kept for clients resuming with Last-Event-ID
+const JOURNAL_CAPACITY: usize = 500;
+
+// Numbers every event published on the bus and keeps the most recent ones, so a
+// reconnecting client can replay exactly what it missed. Ids look like
+// "<boot>-<seq>"; ids from an earlier process never match and fall back to a hydrate.
+#[derive(Clone)]
+pub struct SseJournal {
+    boot: i64,
+    events: Arc<RwLock<VecDeque<(u64, SseEvent)>>>,
+    tx: broadcast::Sender<(u64, SseEvent)>,
+}
+
+impl SseJournal {
+    pub fn spawn(bus: &broadcast::Sender<SseEvent>) -> Self {
+        let (tx, _) = broadcast::channel(256);
+        let journal = Self {
+            boot: chrono::Utc::now().timestamp(),
+            events: Arc::new(RwLock::new(VecDeque::new())),
+            tx,
+        };
+        let mut rx = bus.subscribe();
+        let writer = journal.clone();
+        tokio::spawn(async move {
+            let mut seq = 0u64;
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        seq += 1;
+                        writer.record(seq, event).await;
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => tracing::warn!("sse journal dropped {} events", n),
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        });
+        journal
+    }
+
+    async fn record(&self, seq: u64, event: SseEvent) {
+        let mut events = self.events.write().await;
+        events.push_back((seq, event.clone()));
+        if events.len() > JOURNAL_CAPACITY {
+            events.pop_front();
+        }
+        drop(events);
+        let _ = self.tx.send((seq, event));
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<(u64, SseEvent)> {
+        self.tx.subscribe()
+    }
+
+    pub fn event_id(&self, seq: u64) -> String {
+        format!("{}-{}", self.boot, seq)
+    }
+
+    // Sequence number of the latest journaled event
+    pub async fn head(&self) -> u64 {
+        self.events.read().await.back().map_or(0, |(seq, _)| *seq)
+    }
+
+    // Events after `last_event_id`, or None when the id is unknown or already evicted
+    pub async fn since(&self, last_event_id: &str) -> Option<Vec<(u64, SseEvent)>> {
+        let (boot, seq) = last_event_id.split_once('-')?;
+        if boot.parse::<i64>().ok()? != self.boot {
+            return None;
+        }
+        let seq: u64 = seq.parse().ok()?;
+        let events = self.events.read().await;
+        let oldest = events.front().map_or(1, |(s, _)| *s);
+        let head = events.back().map_or(0, |(s, _)| *s);
+        // Anything between the client's id and our oldest entry is gone
+        if seq > head || seq + 1 < oldest {
+            return None;
+        }
+        Some(events.iter().filter(|(s, _)| *s > seq).cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume_replays_only_missed_events() {
+        let (bus, _) = broadcast::channel(16);
+        let journal = SseJournal::spawn(&bus);
+        let mut live = journal.subscribe();
+        for i in 0..3 {
+            bus.send(SseEvent { event: "card.update".into(), data: i.to_string() }).unwrap();
+        }
+        for _ in 0..3 {
+            live.recv().await.unwrap();
+        }
+
+        let missed = journal.since(&journal.event_id(1)).await.unwrap();
+        assert_eq!(missed.iter().map(|(_, e)| e.data.as_str()).collect::<Vec<_>>(), vec!["1", "2"]);
+        assert!(journal.since(&journal.event_id(3)).await.unwrap().is_empty());
+        assert!(journal.since("1-1").await.is_none());
+        assert!(journal.since(&journal.event_id(9)).await.is_none());
+    }
+}
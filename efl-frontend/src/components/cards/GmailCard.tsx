@@ -30,6 +30,7 @@ export const GmailCard: React.FC<GmailCardProps> = ({ card, onAction }) => {
   const date = metadata?.emailDate ? new Date(metadata.emailDate).toLocaleString() : '';
   const replyTemplates = metadata?.replyTemplates || [];
   const category = metadata?.emailCategory || 'Email';
+  const thread = metadata?.emailThread;
   
   // Extract preview from content and decode HTML entities
   const rawPreview = card.content?.type === 'do_now' ? card.content.preview : card.title;
@@ -202,11 +203,27 @@ export const GmailCard: React.FC<GmailCardProps> = ({ card, onAction }) => {
         {subject}
       </h2>
       
-      {/* Email preview */}
+      {/* Thread summary when several unread replies share this card, otherwise the preview */}
       <div className="bg-white rounded-lg p-4 mb-4 border border-gray-200">
-        <p className="text-gray-700 leading-relaxed">
-          {preview}
-        </p>
+        {thread && thread.messages.length > 1 ? (
+          <>
+            <p className="text-xs text-gray-500 mb-2">
+              {thread.messages.length} unread · {thread.participants.join(', ')}
+            </p>
+            <p className="text-gray-700 leading-relaxed whitespace-pre-line">
+              {decodeHtmlEntities(thread.summary)}
+            </p>
+          </>
+        ) : (
+          <p className="text-gray-700 leading-relaxed">
+            {preview}
+          </p>
+        )}
+        {thread?.latestAsk && (
+          <p className="mt-3 text-sm font-medium text-gray-900">
+            Ask: {decodeHtmlEntities(thread.latestAsk)}
+          </p>
+        )}
       </div>
       
       {/* Quick actions */}
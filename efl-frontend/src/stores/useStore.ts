@@ -194,11 +194,8 @@ export const useStore = create<AppState>((set) => ({
   }),
   
   breakIn: (card) => set((state) => {
-    // Calculate urgency*impact*readiness threshold
-    const urgency = (card.data && 'content' in card.data && 'urgency' in card.data.content && card.data.content.urgency === 'high') ? 0.8 : 0.4;
-    const impact = 0.6; // placeholder until we have real impact scoring
-    const readiness = 1; // placeholder until we have memory stack
-    const shouldInterrupt = urgency * impact * readiness >= 0.48;
+    // The server triages every interrupt; only scores at or above τ preempt the queue
+    const shouldInterrupt = card.data?.metadata?.triage?.preempt ?? false;
     
     if (shouldInterrupt) {
       return {
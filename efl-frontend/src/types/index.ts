@@ -25,7 +25,7 @@ export interface ContextSignals {
 export type CardType = 'do_now' | 'ship' | 'amplify' | 'orient' | 'parked' | 'break_in';
 export type Altitude = 'do' | 'ship' | 'amplify' | 'orient';
 export type CardStatus = 'active' | 'pending' | 'completed' | 'parked' | 'cancelled';
-export type CardAction = 'commit' | 'undo' | 'park' | 'show_diff' | 'respond_now' | 'respond_at_break' | 'open' | 'generate_draft';
+export type CardAction = 'commit' | 'undo' | 'park' | 'show_diff' | 'respond_now' | 'respond_at_break' | 'open' | 'generate_draft' | 'decline_respectfully' | 'send_draft' | 'discard_draft';
 
 export interface Card {
   id: string;
@@ -46,6 +46,56 @@ export interface CardMetadata {
   emailDate?: string;
   replyTemplates?: string[];
   emailCategory?: string;
+  triage?: Triage;
+  draft?: CardDraft;
+  emailThread?: EmailThread;
+}
+
+// The unread messages of the Gmail thread behind a card, oldest first
+export interface EmailThread {
+  threadId: string;
+  messages: EmailThreadMessage[];
+  participants: string[];
+  summary: string;
+  latestAsk?: string;
+}
+
+export interface EmailThreadMessage {
+  id: string;
+  from: string;
+  sender: string;
+  date: string;
+  snippet: string;
+}
+
+// A Gmail draft written for the card; it is only sent after the user approves it
+export interface CardDraft {
+  draftId: string;
+  kind: 'reply' | 'decline';
+  state: 'drafted' | 'sent' | 'discarded';
+  messageId: string;
+  threadId: string;
+  to: string;
+  subject: string;
+  body: string;
+  updatedAt: string;
+}
+
+export interface TriageFactor {
+  value: number;
+  reason: string;
+}
+
+// Server-side break-in triage; only `preempt` interrupts the queue
+export interface Triage {
+  urgency: TriageFactor;
+  impact: TriageFactor;
+  readiness: TriageFactor;
+  score: number;
+  threshold: number;
+  recommendation: 'Now' | 'AtBreak' | 'Park';
+  preempt: boolean;
+  rationale: string;
 }
 
 export interface OriginObject {